@@ -1,9 +1,15 @@
 const DEFAULT_GAIN = 0.08;
+const DEFAULT_FREQUENCY = 261.63;
 
 export default class Buzzer {
   ctx: AudioContext;
   osc: OscillatorNode | undefined;
   gainNode: GainNode;
+  frequency = DEFAULT_FREQUENCY;
+  sampleSource: AudioBufferSourceNode | undefined;
+  // XO-CHIP audio pattern, played instead of the oscillator
+  pattern: AudioBuffer | undefined;
+  patternSource: AudioBufferSourceNode | undefined;
 
   constructor() {
     this.ctx = new AudioContext();
@@ -13,21 +19,78 @@ export default class Buzzer {
   }
 
   play() {
-    if (!this.osc) {
+    if (this.pattern) {
+      if (!this.patternSource) {
+        this.patternSource = this.ctx.createBufferSource();
+        this.patternSource.buffer = this.pattern;
+        this.patternSource.loop = true;
+        this.patternSource.connect(this.gainNode);
+        this.patternSource.start();
+      }
+    } else if (!this.osc) {
       this.osc = this.ctx.createOscillator();
       this.osc.type = "square";
-      this.osc.frequency.value = 261.63;
+      this.osc.frequency.value = this.frequency;
       this.osc.connect(this.gainNode);
       this.osc.start();
     }
   }
 
+  setFrequency(frequency: number | undefined) {
+    this.frequency = frequency ?? DEFAULT_FREQUENCY;
+    if (this.osc) {
+      this.osc.frequency.value = this.frequency;
+    }
+  }
+
   stop() {
     if (this.osc) {
       this.osc.stop();
       this.osc.disconnect();
       this.osc = undefined;
     }
+    if (this.patternSource) {
+      this.patternSource.stop();
+      this.patternSource.disconnect();
+      this.patternSource = undefined;
+    }
+  }
+
+  setPattern(samples: Float32Array | undefined, sampleRate: number) {
+    const playing = this.osc !== undefined || this.patternSource !== undefined;
+    this.stop();
+    if (samples && samples.length > 0) {
+      this.pattern = this.ctx.createBuffer(1, samples.length, sampleRate);
+      this.pattern.copyToChannel(samples, 0);
+    } else {
+      this.pattern = undefined;
+    }
+    if (playing) {
+      this.play();
+    }
+  }
+
+  playSample(samples: Float32Array, sampleRate: number, loop: boolean) {
+    this.stopSample();
+    if (samples.length === 0) {
+      return;
+    }
+
+    const buffer = this.ctx.createBuffer(1, samples.length, sampleRate);
+    buffer.copyToChannel(samples, 0);
+    this.sampleSource = this.ctx.createBufferSource();
+    this.sampleSource.buffer = buffer;
+    this.sampleSource.loop = loop;
+    this.sampleSource.connect(this.gainNode);
+    this.sampleSource.start();
+  }
+
+  stopSample() {
+    if (this.sampleSource) {
+      this.sampleSource.stop();
+      this.sampleSource.disconnect();
+      this.sampleSource = undefined;
+    }
   }
 
   mute() {
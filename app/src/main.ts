@@ -1,4 +1,12 @@
-import wasmInit, { loadRom, Emu } from "chip8";
+import wasmInit, {
+  loadRom,
+  loadCartridge,
+  themePresetNames,
+  Emu,
+  Filter,
+  FontSet,
+  RecordingFormat,
+} from "chip8";
 import Buzzer from "./audio";
 
 const ROMS = [
@@ -6,34 +14,31 @@ const ROMS = [
   { name: "wait_for_key.ch8", url: "roms/wait_for_key.ch8" },
   { name: "buzz.ch8", url: "roms/buzz.ch8" },
 ];
-const DISPLAY_LEN = 64 * 32;
-const THEMES = [
-  {
-    name: "Noire Truth",
-    off: "#1e1c32",
-    on: "#c6baac",
-  },
-  {
-    name: "1-bit Monitor Glow",
-    off: "#222323",
-    on: "#f0f6f0",
-  },
-  {
-    name: "Paperback-2",
-    off: "#382b26",
-    on: "#b8c2b9",
-  },
-  {
-    name: "Gato Roboto Goop",
-    off: "#210009",
-    on: "#00ffae",
-  },
-  {
-    name: "Y's Postapocalyptic Sunset",
-    off: "#1d0f44",
-    on: "#f44e38",
-  },
+const FILTERS = [
+  { name: "None", filter: Filter.Nearest },
+  { name: "Scale2x", filter: Filter.Scale2x },
+  { name: "Scale3x", filter: Filter.Scale3x },
+  { name: "Scanlines", filter: Filter.Scanlines },
+  { name: "Pixel grid", filter: Filter.PixelGrid },
+  { name: "LCD dot matrix", filter: Filter.DotMatrix },
 ];
+const PERSISTENCE_MODES = [
+  { name: "Off", apply: (emu: Emu) => emu.disablePersistence() },
+  { name: "Phosphor (fast)", apply: (emu: Emu) => emu.setPhosphorDecay(0.5) },
+  { name: "Phosphor (slow)", apply: (emu: Emu) => emu.setPhosphorDecay(0.2) },
+  { name: "Blend 2 frames", apply: (emu: Emu) => emu.setFrameBlending(2) },
+  { name: "Blend 3 frames", apply: (emu: Emu) => emu.setFrameBlending(3) },
+];
+const FONTS = [
+  { name: "Platform default", apply: (emu: Emu) => emu.resetFont() },
+  { name: "COSMAC VIP", apply: (emu: Emu) => emu.setFontSet(FontSet.Vip) },
+  { name: "ETI-660", apply: (emu: Emu) => emu.setFontSet(FontSet.Eti660) },
+  { name: "DREAM 6800", apply: (emu: Emu) => emu.setFontSet(FontSet.Dream6800) },
+  { name: "SUPER-CHIP", apply: (emu: Emu) => emu.setFontSet(FontSet.SuperChip) },
+  { name: "Octo", apply: (emu: Emu) => emu.setFontSet(FontSet.Octo) },
+];
+// instructions shown in the console when a ROM crashes
+const TRACE_LENGTH = 64;
 
 let animationFrameRequestId: number;
 let keyDownController: AbortController | undefined;
@@ -42,34 +47,274 @@ let buzzer: Buzzer | undefined = undefined;
 
 const config = {
   cyclesPerFrame: 12,
-  theme: THEMES[0],
+  theme: "Noire Truth",
+  importedTheme: undefined as { name: string; data: string } | undefined,
+  filter: FILTERS[0],
+  scale: 1,
+  persistence: PERSISTENCE_MODES[0],
+  font: FONTS[0],
+  importedFont: undefined as Uint8Array | undefined,
+  // one breakpoint or tracepoint per line
+  breakpoints: "",
 };
 
 main();
 
 async function main() {
+  await wasmInit();
   setupRomSelector(ROMS);
   setupConfigPanel();
 }
 
-function wireConfigPanelToEmulator(emu: Emu) {
+function wireConfigPanelToEmulator(emu: Emu, onFilterChanged: () => void) {
   const themeSelect = document.querySelector<HTMLSelectElement>(
     "#chip8-config-theme-selector"
   );
 
-  const handleThemeChanged = (event: Event) => {
-    const idx = parseInt((event.target as HTMLInputElement).value);
-    config.theme = THEMES[idx];
-    emu.setTheme(config.theme.off, config.theme.on);
+  const handleThemeChanged = () => {
+    config.theme = themeSelect?.value ?? config.theme;
+    config.importedTheme = undefined;
+    applyTheme(emu);
+  };
+
+  const themeImportInput = document.querySelector<HTMLInputElement>(
+    "#chip8-config-theme-import"
+  );
+
+  const handleThemeImported = async () => {
+    const file = themeImportInput?.files?.[0];
+    if (!file) {
+      return;
+    }
+    config.importedTheme = { name: file.name, data: await file.text() };
+    try {
+      applyTheme(emu);
+    } catch (err) {
+      console.error(`Could not import theme ${file.name}`, err);
+      config.importedTheme = undefined;
+      applyTheme(emu);
+    }
+  };
+
+  const themeExportButton = document.querySelector<HTMLButtonElement>(
+    "#chip8-config-theme-export"
+  );
+
+  const handleThemeExported = () => {
+    const blob = new Blob([emu.exportThemeJson()], {
+      type: "application/json",
+    });
+    downloadBlob(blob, "theme.json");
+  };
+
+  const screenshotButton = document.querySelector<HTMLButtonElement>(
+    "#chip8-screenshot"
+  );
+
+  const handleScreenshot = () => {
+    const blob = new Blob([emu.screenshot(8)], { type: "image/png" });
+    downloadBlob(blob, "chip8.png");
+  };
+
+  const filterSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-filter-selector"
+  );
+  const scaleInput = document.querySelector<HTMLInputElement>(
+    "#chip8-config-scale-selector"
+  );
+
+  const handleFilterChanged = () => {
+    config.filter = FILTERS[parseInt(filterSelect?.value ?? "0")];
+    config.scale = parseInt(scaleInput?.value ?? "1");
+    onFilterChanged();
+  };
+
+  const persistenceSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-persistence-selector"
+  );
+
+  const handlePersistenceChanged = () => {
+    const idx = parseInt(persistenceSelect?.value ?? "0");
+    config.persistence = PERSISTENCE_MODES[idx];
+    config.persistence.apply(emu);
+  };
+
+  const fontSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-font-selector"
+  );
+
+  const handleFontChanged = () => {
+    config.font = FONTS[parseInt(fontSelect?.value ?? "0")];
+    config.importedFont = undefined;
+    applyFont(emu);
+  };
+
+  const fontImportInput = document.querySelector<HTMLInputElement>(
+    "#chip8-config-font-import"
+  );
+
+  const handleFontImported = async () => {
+    const file = fontImportInput?.files?.[0];
+    if (!file) {
+      return;
+    }
+    config.importedFont = new Uint8Array(await file.arrayBuffer());
+    try {
+      applyFont(emu);
+    } catch (err) {
+      console.error(`Could not import font ${file.name}`, err);
+      config.importedFont = undefined;
+      applyFont(emu);
+    }
+  };
+
+  const symbolsImportInput = document.querySelector<HTMLInputElement>(
+    "#chip8-config-symbols-import"
+  );
+
+  // symbols belong to the running ROM, so they are not kept in the config
+  const handleSymbolsImported = async () => {
+    const file = symbolsImportInput?.files?.[0];
+    if (!file) {
+      return;
+    }
+    try {
+      emu.importSymbols(await file.text());
+    } catch (err) {
+      console.error(`Could not import symbols ${file.name}`, err);
+    }
+  };
+
+  const breakpointsInput = document.querySelector<HTMLTextAreaElement>(
+    "#chip8-config-breakpoints"
+  );
+
+  const handleBreakpointsChanged = () => {
+    config.breakpoints = breakpointsInput?.value ?? "";
+    applyBreakpoints(emu);
+  };
+
+  const recordButton = document.querySelector<HTMLButtonElement>(
+    "#chip8-record"
+  );
+
+  const handleRecord = () => {
+    if (emu.isRecording()) {
+      const blob = new Blob([emu.stopRecording()], { type: "image/gif" });
+      downloadBlob(blob, "chip8.gif");
+    } else {
+      // stop by itself after a minute, so memory does not grow forever
+      emu.startRecording(RecordingFormat.Gif, 4, 60 * 60);
+    }
+    if (recordButton) {
+      recordButton.textContent = emu.isRecording() ? "Stop recording" : "Record GIF";
+    }
   };
 
   themeSelect?.addEventListener("change", handleThemeChanged);
+  themeImportInput?.addEventListener("change", handleThemeImported);
+  themeExportButton?.addEventListener("click", handleThemeExported);
+  screenshotButton?.addEventListener("click", handleScreenshot);
+  recordButton?.addEventListener("click", handleRecord);
+  filterSelect?.addEventListener("change", handleFilterChanged);
+  scaleInput?.addEventListener("change", handleFilterChanged);
+  persistenceSelect?.addEventListener("change", handlePersistenceChanged);
+  fontSelect?.addEventListener("change", handleFontChanged);
+  fontImportInput?.addEventListener("change", handleFontImported);
+  symbolsImportInput?.addEventListener("change", handleSymbolsImported);
+  breakpointsInput?.addEventListener("change", handleBreakpointsChanged);
 
   return () => {
     themeSelect?.removeEventListener("change", handleThemeChanged);
+    themeImportInput?.removeEventListener("change", handleThemeImported);
+    themeExportButton?.removeEventListener("click", handleThemeExported);
+    screenshotButton?.removeEventListener("click", handleScreenshot);
+    recordButton?.removeEventListener("click", handleRecord);
+    filterSelect?.removeEventListener("change", handleFilterChanged);
+    scaleInput?.removeEventListener("change", handleFilterChanged);
+    persistenceSelect?.removeEventListener("change", handlePersistenceChanged);
+    fontSelect?.removeEventListener("change", handleFontChanged);
+    fontImportInput?.removeEventListener("change", handleFontImported);
+    symbolsImportInput?.removeEventListener("change", handleSymbolsImported);
+    breakpointsInput?.removeEventListener("change", handleBreakpointsChanged);
   };
 }
 
+function applyTheme(emu: Emu) {
+  if (config.importedTheme) {
+    emu.importTheme(config.importedTheme.name, config.importedTheme.data);
+  } else {
+    emu.setThemePreset(config.theme);
+  }
+  updatePageColors(emu);
+}
+
+function applyFont(emu: Emu) {
+  if (config.importedFont) {
+    emu.importFont(config.importedFont);
+  } else {
+    config.font.apply(emu);
+  }
+}
+
+function applyBreakpoints(emu: Emu) {
+  emu.clearBreakpoints();
+  for (const line of config.breakpoints.split("\n")) {
+    if (!line.trim()) {
+      continue;
+    }
+    try {
+      emu.addBreakpoint(line);
+    } catch (err) {
+      console.error(`Invalid breakpoint "${line}"`, err);
+    }
+  }
+}
+
+function updatePageColors(emu: Emu) {
+  const canvas = document.querySelector<HTMLCanvasElement>("#chip8-canvas");
+  if (canvas) {
+    canvas.style.borderColor = emu.borderColor();
+  }
+  document.body.style.backgroundColor = emu.backgroundColor();
+}
+
+// Shows the database metadata of the ROM and uses its recommended speed.
+// Returns whether the ROM came with its own colours.
+function applyRomInfo(emu: Emu): boolean {
+  const romInfoEl = document.querySelector<HTMLElement>("#chip8-rom-info");
+  const info = emu.romInfo();
+  if (!info) {
+    if (romInfoEl) {
+      romInfoEl.textContent = "";
+    }
+    return false;
+  }
+
+  if (romInfoEl) {
+    const authors = info.authors.join(", ");
+    const platform = info.platformName ?? info.platform;
+    romInfoEl.textContent = [info.title, authors, platform]
+      .filter((text) => !!text)
+      .join(" · ");
+  }
+
+  if (info.tickrate) {
+    config.cyclesPerFrame = info.tickrate;
+    const cyclesInput = document.querySelector<HTMLInputElement>(
+      "#chip8-ipf-selector"
+    );
+    if (cyclesInput) {
+      cyclesInput.value = info.tickrate.toString();
+      cyclesInput.dispatchEvent(new Event("change"));
+    }
+  }
+
+  const hasColors = info.colors.length > 0;
+  info.free();
+  return hasColors;
+}
+
 async function startEmulatorWithRom(romUrl: string) {
   keyDownController = new AbortController();
   keyUpController = new AbortController();
@@ -81,8 +326,16 @@ async function startEmulatorWithRom(romUrl: string) {
   const wasm = await wasmInit();
   const emu = await loadRomInEmu(romUrl);
 
-  const configCleanUp = wireConfigPanelToEmulator(emu);
-  emu.setTheme(config.theme.off, config.theme.on);
+  const hasRomColors = applyRomInfo(emu);
+  if (hasRomColors) {
+    updatePageColors(emu);
+  } else {
+    applyTheme(emu);
+  }
+  config.persistence.apply(emu);
+  applyFont(emu);
+  applyBreakpoints(emu);
+  emu.enableTracing(TRACE_LENGTH);
 
   document.addEventListener(
     "keydown",
@@ -100,10 +353,26 @@ async function startEmulatorWithRom(romUrl: string) {
     { signal: keyUpController.signal }
   );
 
+  const continueButton =
+    document.querySelector<HTMLButtonElement>("#chip8-continue");
+
+  const handleContinue = () => {
+    if (continueButton) {
+      continueButton.disabled = true;
+    }
+    animationFrameRequestId = requestAnimationFrame(updateFrame);
+  };
+
   const cleanUp = () => {
     configCleanUp();
+    continueButton?.removeEventListener("click", handleContinue);
+    if (continueButton) {
+      continueButton.disabled = true;
+    }
 
     buzzer?.stop();
+    buzzer?.stopSample();
+    buzzer?.setPattern(undefined, 0);
     keyDownController?.abort();
     keyUpController?.abort();
 
@@ -112,39 +381,120 @@ async function startEmulatorWithRom(romUrl: string) {
     }
   };
 
-  const sharedBuffer = new Uint8Array(wasm.memory.buffer);
   const canvas = document.querySelector<HTMLCanvasElement>("#chip8-canvas");
   const ctx = canvas?.getContext("2d");
   if (!ctx || !canvas) {
     throw new Error("Valid canvas not found");
   }
-  const imageData = ctx.createImageData(canvas.width, canvas.height);
+
+  let imageData: ImageData;
+  const resizeCanvas = () => {
+    emu.setFilter(config.filter.filter, config.scale);
+    canvas.width = emu.outputWidth();
+    canvas.height = emu.outputHeight();
+    imageData = ctx.createImageData(canvas.width, canvas.height);
+  };
+  resizeCanvas();
+
+  const configCleanUp = wireConfigPanelToEmulator(emu, resizeCanvas);
+
+  loadRplFlags(emu);
 
   const updateFrame = () => {
-    let shallHalt = emu.run(config.cyclesPerFrame);
+    let shallHalt: boolean;
+    try {
+      shallHalt = emu.run(config.cyclesPerFrame);
+    } catch (err) {
+      console.error(`Last instructions before the crash:\n${emu.traceText()}`);
+      console.error(`Call stack:\n${emu.formatStack()}`);
+      throw err;
+    }
+
+    const tracepointLog = emu.takeTracepointLog();
+    if (tracepointLog) {
+      console.log(tracepointLog);
+    }
+
+    if (emu.takeRplFlagsChanged()) {
+      saveRplFlags(emu);
+    }
+
+    if (emu.takeAudioPatternChanged()) {
+      const pattern = emu.audioPattern();
+      buzzer?.setPattern(pattern?.samples, pattern?.sampleRate ?? 0);
+      pattern?.free();
+    }
 
+    if (emu.takeDigitalSoundChanged()) {
+      const sound = emu.digitalSound();
+      if (sound) {
+        buzzer?.playSample(sound.samples, sound.sampleRate, sound.looping);
+        sound.free();
+      } else {
+        buzzer?.stopSample();
+      }
+    }
+
+    // SUPER-CHIP and MegaChip programs can switch the display size
+    if (
+      canvas.width !== emu.outputWidth() ||
+      canvas.height !== emu.outputHeight()
+    ) {
+      canvas.width = emu.outputWidth();
+      canvas.height = emu.outputHeight();
+      imageData = ctx.createImageData(canvas.width, canvas.height);
+    }
+
+    buzzer?.setFrequency(emu.toneFrequency());
     if (emu.isBuzzing()) {
       buzzer?.play();
     } else {
       buzzer?.stop();
     }
 
-    const outputPointer = emu.displayBuffer();
-    const bufferData = sharedBuffer.slice(
-      outputPointer,
-      outputPointer + 4 * DISPLAY_LEN
-    );
-    imageData.data.set(bufferData);
-    ctx.putImageData(imageData, 0, 0);
+    const dirtyRect = emu.dirtyRect();
+    if (dirtyRect) {
+      const outputPointer = emu.displayBuffer();
+      const bufferData = new Uint8Array(
+        wasm.memory.buffer,
+        outputPointer,
+        imageData.data.length
+      );
+      // copy only the part of each row inside the dirty rect
+      const rowBytes = imageData.width * 4;
+      for (let y = dirtyRect.y; y < dirtyRect.y + dirtyRect.height; y++) {
+        const start = y * rowBytes + dirtyRect.x * 4;
+        const end = start + dirtyRect.width * 4;
+        imageData.data.set(bufferData.subarray(start, end), start);
+      }
+      ctx.putImageData(
+        imageData,
+        0,
+        0,
+        dirtyRect.x,
+        dirtyRect.y,
+        dirtyRect.width,
+        dirtyRect.height
+      );
+      dirtyRect.free();
+    }
 
+    const breakpoint = emu.breakpointHit();
     if (shallHalt) {
       console.debug("Chip-8 VM halted");
       cleanUp();
+    } else if (breakpoint !== undefined) {
+      const hits = emu.breakpointHits(breakpoint);
+      console.info(`Breakpoint hit ${hits} times\n${emu.formatStack()}`);
+      if (continueButton) {
+        continueButton.disabled = false;
+      }
     } else {
       animationFrameRequestId = requestAnimationFrame(updateFrame);
     }
   };
 
+  continueButton?.addEventListener("click", handleContinue);
   updateFrame();
 
   return cleanUp;
@@ -164,12 +514,25 @@ function setupRomSelector(roms: { name: string; url: string }[]) {
 
   let cleanUp: () => void;
 
-  selectEl?.addEventListener("change", async () => {
+  const startRom = async (url: string) => {
     if (cleanUp) {
       await cleanUp();
     }
-    let url = selectEl.value;
     cleanUp = await startEmulatorWithRom(url);
+  };
+
+  selectEl?.addEventListener("change", () => startRom(selectEl.value));
+
+  const fileInput = document.querySelector<HTMLInputElement>(
+    "#chip8-rom-file"
+  );
+  fileInput?.addEventListener("change", async () => {
+    const file = fileInput.files?.[0];
+    if (!file) {
+      return;
+    }
+
+    await startRom(URL.createObjectURL(file));
   });
 }
 
@@ -198,13 +561,43 @@ function setupConfigPanel() {
   const themeSelect = document.querySelector<HTMLSelectElement>(
     "#chip8-config-theme-selector"
   );
-  for (const [index, { name }] of THEMES.entries()) {
+  for (const name of themePresetNames()) {
     const option = new Option();
-    option.value = index.toString();
+    option.value = name;
     option.innerText = name;
     themeSelect?.appendChild(option);
   }
 
+  const filterSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-filter-selector"
+  );
+  for (const [index, { name }] of FILTERS.entries()) {
+    const option = new Option();
+    option.value = index.toString();
+    option.innerText = name;
+    filterSelect?.appendChild(option);
+  }
+
+  const persistenceSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-persistence-selector"
+  );
+  for (const [index, { name }] of PERSISTENCE_MODES.entries()) {
+    const option = new Option();
+    option.value = index.toString();
+    option.innerText = name;
+    persistenceSelect?.appendChild(option);
+  }
+
+  const fontSelect = document.querySelector<HTMLSelectElement>(
+    "#chip8-config-font-selector"
+  );
+  for (const [index, { name }] of FONTS.entries()) {
+    const option = new Option();
+    option.value = index.toString();
+    option.innerText = name;
+    fontSelect?.appendChild(option);
+  }
+
   const audioCheckbox = document.querySelector<HTMLInputElement>(
     "#chip8-config-audio"
   );
@@ -222,6 +615,36 @@ function setupConfigPanel() {
   audioCheckbox?.addEventListener("change", updateAudioConfig);
 }
 
+// SUPER-CHIP games keep high scores in the RPL user flags, which are saved
+// per ROM so they survive reloads.
+function rplFlagsKey(emu: Emu) {
+  return `chip8-rpl-flags-${emu.romHash()}`;
+}
+
+function loadRplFlags(emu: Emu) {
+  const saved = localStorage.getItem(rplFlagsKey(emu));
+  if (saved) {
+    try {
+      emu.importRplFlags(new Uint8Array(JSON.parse(saved)));
+    } catch (err) {
+      console.error("Could not restore RPL flags", err);
+    }
+  }
+}
+
+function saveRplFlags(emu: Emu) {
+  const flags = Array.from(emu.exportRplFlags());
+  localStorage.setItem(rplFlagsKey(emu), JSON.stringify(flags));
+}
+
+function downloadBlob(blob: Blob, filename: string) {
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(blob);
+  link.download = filename;
+  link.click();
+  URL.revokeObjectURL(link.href);
+}
+
 async function fetchRom(url: string) {
   try {
     const response = await fetch(url);
@@ -240,6 +663,11 @@ async function loadRomInEmu(url: string) {
   const buffer = await file.arrayBuffer();
   const rom = new Uint8Array(buffer);
 
-  const emu = loadRom(rom);
+  // Octo cartridges are GIF images carrying the program and its options
+  const isCartridge = String.fromCharCode(...rom.subarray(0, 4)) === "GIF8";
+  const emu = isCartridge ? loadCartridge(rom) : loadRom(rom);
+  for (const warning of emu.romWarnings()) {
+    console.warn(`${url}: ${warning}`);
+  }
   return emu;
 }
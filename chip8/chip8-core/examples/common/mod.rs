@@ -0,0 +1,32 @@
+//! Helpers shared by the examples.
+
+use chip8_core::{Platform, RandomSource, Vm};
+
+pub fn platform(name: &str) -> Option<Platform> {
+    match name {
+        "chip8" => Some(Platform::Chip8),
+        "hybrid-vip" => Some(Platform::HybridVip),
+        "chip8-hires" => Some(Platform::Chip8HiRes),
+        "chip8x" => Some(Platform::Chip8X),
+        "eti660" => Some(Platform::Eti660),
+        "superchip" => Some(Platform::SuperChip),
+        "xochip" => Some(Platform::XoChip),
+        "megachip" => Some(Platform::MegaChip),
+        _ => None,
+    }
+}
+
+/// Runs `frames` frames of `cycles_per_frame` instructions, stopping early if
+/// the ROM crashes.
+#[allow(dead_code)]
+pub fn run_frames<R: RandomSource>(vm: &mut Vm<R>, frames: usize, cycles_per_frame: usize) {
+    for _ in 0..frames {
+        for _ in 0..cycles_per_frame {
+            if let Err(err) = vm.tick() {
+                eprintln!("Stopped: {}", err);
+                return;
+            }
+        }
+        vm.tick_timers();
+    }
+}
@@ -0,0 +1,66 @@
+//! Runs a ROM for a number of frames and reports which parts of it were
+//! executed, read and written.
+//!
+//! ```text
+//! cargo run -p chip8-core --example coverage -- <platform> <rom> <frames> [--lcov] [cycles per frame]
+//! ```
+//!
+//! Without `--lcov` the ROM is printed as an annotated disassembly. Random
+//! numbers are always 0 and no keys are pressed.
+
+mod common;
+
+use std::process::ExitCode;
+use std::{env, fs};
+
+use chip8_core::Vm;
+
+const DEFAULT_CYCLES_PER_FRAME: usize = 16;
+
+fn run(args: &[String]) -> Result<String, String> {
+    let [platform_name, rom_path, frames, rest @ ..] = args else {
+        return Err("usage: coverage <platform> <rom> <frames> [--lcov] [cycles per frame]".into());
+    };
+    let platform =
+        common::platform(platform_name).ok_or(format!("Unknown platform {}", platform_name))?;
+    let rom = fs::read(rom_path).map_err(|err| format!("Could not read {}: {}", rom_path, err))?;
+    platform.validate_rom(&rom).map_err(|err| err.to_string())?;
+
+    let number = |arg: &String| arg.parse().map_err(|_| format!("Invalid number {}", arg));
+    let frames: usize = number(frames)?;
+    let (lcov, rest) = match rest {
+        [flag, rest @ ..] if flag == "--lcov" => (true, rest),
+        _ => (false, rest),
+    };
+    let cycles_per_frame = match rest.first() {
+        Some(arg) => number(arg)?,
+        None => DEFAULT_CYCLES_PER_FRAME,
+    };
+
+    let mut vm = Vm::for_platform(platform, &rom, || 0);
+    vm.enable_coverage();
+    common::run_frames(&mut vm, frames, cycles_per_frame);
+
+    let coverage = vm.coverage().expect("coverage is enabled");
+    let start = platform.load_address() as u32;
+    let range = start..start + rom.len() as u32;
+    Ok(if lcov {
+        coverage.to_lcov(rom_path, range)
+    } else {
+        coverage.annotate(vm.memory(), platform, range)
+    })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(report) => {
+            print!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
+}
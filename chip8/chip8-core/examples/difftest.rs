@@ -0,0 +1,81 @@
+//! Runs a ROM along a reference trace and reports the first instruction
+//! that does something else.
+//!
+//! ```text
+//! cargo run -p chip8-core --example difftest -- <platform> <rom> <trace> [cycles per frame]
+//! cargo run -p chip8-core --example difftest -- <platform> <rom> --record <steps> [cycles per frame]
+//! ```
+//!
+//! `--record` prints a trace of this interpreter instead, to check later
+//! changes against. Random numbers are always 0, so traces are repeatable.
+
+mod common;
+
+use std::process::ExitCode;
+use std::{env, fs};
+
+use chip8_core::{ReferenceTrace, Vm};
+
+const DEFAULT_CYCLES_PER_FRAME: usize = 16;
+
+fn run(args: &[String]) -> Result<bool, String> {
+    let [platform_name, rom_path, rest @ ..] = args else {
+        return Err(
+            "usage: difftest <platform> <rom> (<trace> | --record <steps>) [cycles per frame]"
+                .into(),
+        );
+    };
+    let platform =
+        common::platform(platform_name).ok_or(format!("Unknown platform {}", platform_name))?;
+    let rom = fs::read(rom_path).map_err(|err| format!("Could not read {}: {}", rom_path, err))?;
+    platform.validate_rom(&rom).map_err(|err| err.to_string())?;
+    let mut vm = Vm::for_platform(platform, &rom, || 0);
+
+    let number = |arg: Option<&String>, default| match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("Invalid number {}", arg)),
+        None => Ok(default),
+    };
+
+    if let [flag, steps, rest @ ..] = rest {
+        if flag == "--record" {
+            let steps = number(Some(steps), 0)?;
+            let cycles_per_frame = number(rest.first(), DEFAULT_CYCLES_PER_FRAME)?;
+            print!(
+                "{}",
+                ReferenceTrace::record(&mut vm, steps, cycles_per_frame).to_text()
+            );
+            return Ok(true);
+        }
+    }
+
+    let [trace_path, rest @ ..] = rest else {
+        return Err("Missing reference trace".into());
+    };
+    let text = fs::read_to_string(trace_path)
+        .map_err(|err| format!("Could not read {}: {}", trace_path, err))?;
+    let trace = ReferenceTrace::parse(&text).map_err(|err| err.to_string())?;
+    let cycles_per_frame = number(rest.first(), DEFAULT_CYCLES_PER_FRAME)?;
+
+    match trace.compare(&mut vm, cycles_per_frame) {
+        Some(divergence) => {
+            println!("{}", divergence);
+            Ok(false)
+        }
+        None => {
+            println!("{} instructions match", trace.steps().len());
+            Ok(true)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
+}
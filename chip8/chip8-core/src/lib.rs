@@ -0,0 +1,18 @@
+#![no_std]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+mod vm;
+
+pub use vm::{
+    Beeper, BlendMode, Breakpoint, Cdp1802, ColorAttributes, Coverage, Difference, DigitalSound,
+    Display, Divergence, Expression, ExpressionError, Font, FontError, FontLayout, FontSet,
+    Framebuffer, FunctionProfile, Keypad, MegaChip, Opcode, PixelFormat, Platform, Profiler,
+    Quirks, RandomSource, Rect, ReferenceError, ReferenceStep, ReferenceTrace, Result, RomError,
+    RomWarning, SoundEvent, SymbolError, Symbols, TraceEntry, Tracer, VipMemoryMap, Vm, VmError,
+    XoChip, BACKGROUND_COLORS, COVERAGE_LEN, DEFAULT_PITCH, DISPLAY_HEIGHT, DISPLAY_LEN,
+    DISPLAY_WIDTH, MAX_DISPLAY_HEIGHT, MAX_DISPLAY_WIDTH, MEGA_HEIGHT, MEGA_WIDTH, PATTERN_LEN,
+    SMALL_FONT_LEN,
+};
@@ -0,0 +1,2731 @@
+mod breakpoint;
+mod cdp1802;
+mod colors;
+mod coverage;
+mod error;
+mod expression;
+mod font;
+mod framebuffer;
+mod host;
+mod megachip;
+mod opcode;
+mod platform;
+mod profile;
+mod quirks;
+mod rect;
+mod reference;
+mod symbols;
+mod trace;
+mod xochip;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub use breakpoint::Breakpoint;
+use breakpoint::Breakpoints;
+pub use cdp1802::Cdp1802;
+pub use colors::{ColorAttributes, BACKGROUND_COLORS};
+pub use coverage::{Coverage, COVERAGE_LEN};
+pub use error::VmError;
+pub use expression::{Expression, ExpressionError};
+pub use font::{Font, FontError, FontLayout, FontSet, SMALL_FONT_LEN};
+pub use framebuffer::{Framebuffer, PixelFormat, MAX_DISPLAY_HEIGHT, MAX_DISPLAY_WIDTH};
+pub use host::{Beeper, Display, Keypad, RandomSource};
+use megachip::SOUND_HEADER_LEN;
+pub use megachip::{BlendMode, DigitalSound, MegaChip, SoundEvent, MEGA_HEIGHT, MEGA_WIDTH};
+pub use opcode::Opcode;
+pub use platform::{Platform, RomError, RomWarning, VipMemoryMap};
+pub use profile::{FunctionProfile, Profiler};
+pub use quirks::Quirks;
+pub use rect::Rect;
+pub use reference::{Difference, Divergence, ReferenceError, ReferenceStep, ReferenceTrace};
+pub use symbols::{SymbolError, Symbols};
+pub use trace::{TraceEntry, Tracer};
+pub use xochip::{XoChip, DEFAULT_PITCH, PATTERN_LEN};
+
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+pub const DISPLAY_LEN: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+
+pub type Result<T> = core::result::Result<T, VmError>;
+
+/// Instructions a machine code subroutine may run before it is considered
+/// stuck.
+const MAX_MACHINE_CODE_STEPS: usize = 1_000_000;
+
+/// Memory is allocated in steps of this size as the program touches it, so
+/// the 32 MiB of MegaChip are only there if a ROM uses them.
+const MEMORY_PAGE: usize = 0x10000;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Vm<R, D = (), K = (), B = ()>
+where
+    R: RandomSource,
+    D: Display,
+    K: Keypad,
+    B: Beeper,
+{
+    platform: Platform,
+    ram: Vec<u8>,
+    pc: u16,
+    i_register: u32,
+    delay: u8,
+    sound: u8,
+    v_registers: [u8; 16],
+    rpl_flags: [u8; 16],
+    stack: Vec<u16>,
+    randomize: R,
+    quirks: Quirks,
+    font: Font,
+    cycles: u64,
+    tracer: Option<Tracer>,
+    coverage: Option<Coverage>,
+    profiler: Option<Profiler>,
+    symbols: Symbols,
+    breakpoints: Breakpoints,
+
+    is_waiting: bool,
+    vx_after_wait: u8,
+    is_waiting_vblank: bool,
+    is_halted: bool,
+
+    pub display: Framebuffer,
+    colors: Option<ColorAttributes>,
+    mega: Option<MegaChip>,
+    xo: Option<XoChip>,
+    dirty: Option<Rect>,
+    keys: [bool; 16],
+    second_keys: [bool; 16],
+    tone: Option<u8>,
+
+    screen: D,
+    keypad: K,
+    beeper: B,
+}
+
+#[cfg(test)]
+impl<R, D, K, B> core::fmt::Display for Vm<R, D, K, B>
+where
+    R: RandomSource,
+    D: Display,
+    K: Keypad,
+    B: Beeper,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for y in 0..self.display.height() {
+            for x in 0..self.display.width() {
+                write!(f, "{}", if self.display.get(x, y) { "*" } else { " " })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R> Vm<R>
+where
+    R: RandomSource,
+{
+    /// Creates a CHIP-8 VM with `rom` loaded.
+    ///
+    /// Panics if the ROM does not fit in memory; check it first with
+    /// `Platform::validate_rom`.
+    pub fn new(rom: &[u8], randomize: R) -> Self {
+        Self::for_platform(Platform::Chip8, rom, randomize)
+    }
+
+    /// Creates a VM with the memory layout and display of `platform`, and
+    /// `rom` loaded at the platform's load address.
+    ///
+    /// Panics if the ROM does not fit in memory; check it first with
+    /// `Platform::validate_rom`.
+    pub fn for_platform(platform: Platform, rom: &[u8], randomize: R) -> Self {
+        let load_address = platform.load_address();
+        let end = load_address as usize + rom.len();
+        let mut memory = vec![0; memory_len(platform, end)];
+        memory[load_address as usize..load_address as usize + rom.len()].copy_from_slice(rom);
+        let (width, height) = platform.display_size();
+
+        let mut res = Self {
+            platform,
+            ram: memory,
+            pc: load_address,
+            i_register: 0,
+            delay: 0,
+            sound: 0,
+            v_registers: [0; 16],
+            rpl_flags: [0; 16],
+            stack: Vec::with_capacity(16),
+            display: Framebuffer::new(width, height),
+            colors: (platform == Platform::Chip8X).then(ColorAttributes::default),
+            mega: (platform == Platform::MegaChip).then(MegaChip::default),
+            xo: (platform == Platform::XoChip).then(XoChip::default),
+            dirty: Some(Rect::new(0, 0, width, height)),
+            keys: [false; 16],
+            second_keys: [false; 16],
+            tone: None,
+            randomize,
+            quirks: Quirks::default(),
+            font: Font::builtin(platform.font_set()),
+            cycles: 0,
+            tracer: None,
+            coverage: None,
+            profiler: None,
+            symbols: Symbols::new(),
+            breakpoints: Breakpoints::default(),
+            is_waiting: false,
+            vx_after_wait: 0x0,
+            is_waiting_vblank: false,
+            is_halted: false,
+            screen: (),
+            keypad: (),
+            beeper: (),
+        };
+
+        res.load_fonts();
+        res
+    }
+}
+
+impl<R, D, K, B> Vm<R, D, K, B>
+where
+    R: RandomSource,
+    D: Display,
+    K: Keypad,
+    B: Beeper,
+{
+    pub fn with_display<T: Display>(self, screen: T) -> Vm<R, T, K, B> {
+        self.with_host(|_, keypad, beeper| (screen, keypad, beeper))
+    }
+
+    pub fn with_keypad<T: Keypad>(self, keypad: T) -> Vm<R, D, T, B> {
+        self.with_host(|screen, _, beeper| (screen, keypad, beeper))
+    }
+
+    pub fn with_beeper<T: Beeper>(self, beeper: T) -> Vm<R, D, K, T> {
+        self.with_host(|screen, keypad, _| (screen, keypad, beeper))
+    }
+
+    fn with_host<D2, K2, B2, F>(self, replace: F) -> Vm<R, D2, K2, B2>
+    where
+        D2: Display,
+        K2: Keypad,
+        B2: Beeper,
+        F: FnOnce(D, K, B) -> (D2, K2, B2),
+    {
+        let (screen, keypad, beeper) = replace(self.screen, self.keypad, self.beeper);
+
+        Vm {
+            platform: self.platform,
+            ram: self.ram,
+            pc: self.pc,
+            i_register: self.i_register,
+            delay: self.delay,
+            sound: self.sound,
+            v_registers: self.v_registers,
+            rpl_flags: self.rpl_flags,
+            stack: self.stack,
+            randomize: self.randomize,
+            quirks: self.quirks,
+            font: self.font,
+            cycles: self.cycles,
+            tracer: self.tracer,
+            coverage: self.coverage,
+            profiler: self.profiler,
+            symbols: self.symbols,
+            breakpoints: self.breakpoints,
+            is_waiting: self.is_waiting,
+            vx_after_wait: self.vx_after_wait,
+            is_waiting_vblank: self.is_waiting_vblank,
+            is_halted: self.is_halted,
+            display: self.display,
+            colors: self.colors,
+            mega: self.mega,
+            xo: self.xo,
+            dirty: self.dirty,
+            keys: self.keys,
+            second_keys: self.second_keys,
+            tone: self.tone,
+            screen,
+            keypad,
+            beeper,
+        }
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Number of instructions run so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Starts recording the last `capacity` instructions.
+    pub fn enable_tracing(&mut self, capacity: usize) {
+        self.tracer = Some(Tracer::new(capacity));
+    }
+
+    pub fn disable_tracing(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    /// Starts counting executions, reads and writes per address.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.ram.len()));
+    }
+
+    pub fn disable_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Starts attributing instructions to subroutines. Code outside of any
+    /// subroutine is attributed to the current PC.
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::new(self.pc));
+    }
+
+    pub fn disable_profiling(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Names for addresses, used by `format_address`, `format_stack` and
+    /// `describe_error`.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// `addr` in hex, with the name of its symbol if there is one.
+    pub fn format_address(&self, addr: u32) -> String {
+        self.symbols.format_address(addr)
+    }
+
+    /// The message of an error returned by `tick`, naming the addresses in
+    /// it.
+    pub fn describe_error(&self, error: &VmError) -> String {
+        self.symbols.describe_error(error, self.pc)
+    }
+
+    /// Return addresses of the subroutines being run, the innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    /// The PC and the calls that led to it, innermost first, one per line.
+    pub fn format_stack(&self) -> String {
+        let mut text = format!("at {}\n", self.format_address(self.pc as u32));
+        for addr in self.stack.iter().rev() {
+            let call = addr.wrapping_sub(2) as u32;
+            text.push_str(&format!("called from {}\n", self.format_address(call)));
+        }
+        text
+    }
+
+    /// Adds a breakpoint or tracepoint, checked before every instruction,
+    /// and returns its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.add(breakpoint)
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+        self.breakpoints.remove(id)
+    }
+
+    pub fn breakpoint(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(id)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints.iter()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// The id of the breakpoint that stopped the last `tick` before the
+    /// instruction at the PC. The next `tick` runs it.
+    pub fn breakpoint_hit(&self) -> Option<usize> {
+        self.breakpoints.hit()
+    }
+
+    /// Messages logged by tracepoints since the last call.
+    pub fn take_tracepoint_log(&mut self) -> Vec<String> {
+        self.breakpoints.take_log()
+    }
+
+    /// The memory allocated so far. Bytes past it, up to the platform's
+    /// memory size, have not been written and are 0.
+    pub fn memory(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Replaces the font in memory; FX29 and FX30 point into it from now on.
+    pub fn set_font(&mut self, font: Font) {
+        let layout = self.font.layout();
+        let small = layout.small as usize;
+        self.ram[small..small + self.font.small().len()].fill(0);
+        if let (Some(big), Some(glyphs)) = (layout.big, self.font.big()) {
+            self.ram[big as usize..big as usize + glyphs.len()].fill(0);
+        }
+
+        self.font = font;
+        self.load_fonts();
+    }
+
+    /// The SUPER-CHIP "RPL user flags" saved by FX75, which HP 48 games
+    /// use to keep high scores between runs. Only the first
+    /// `Platform::rpl_flag_count` are used.
+    pub fn rpl_flags(&self) -> [u8; 16] {
+        self.rpl_flags
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; 16]) {
+        self.rpl_flags = flags;
+    }
+
+    pub fn set_key(&mut self, key: u8, value: bool) -> Result<()> {
+        if let Some(k) = self.keys.get_mut(key as usize) {
+            *k = value;
+            if value {
+                self.on_key_down(key);
+            }
+            Ok(())
+        } else {
+            Err(VmError::InvalidKey(key))
+        }
+    }
+
+    /// Sets a key of the second keypad of CHIP-8X.
+    pub fn set_second_key(&mut self, key: u8, value: bool) -> Result<()> {
+        let k = self
+            .second_keys
+            .get_mut(key as usize)
+            .ok_or(VmError::InvalidKey(key))?;
+        *k = value;
+        Ok(())
+    }
+
+    /// Colours of the display on CHIP-8X, `None` on monochrome platforms.
+    pub fn colors(&self) -> Option<&ColorAttributes> {
+        self.colors.as_ref()
+    }
+
+    /// Value last written to the CHIP-8X tone generator by FXF8, which sets
+    /// the pitch of the buzzer.
+    pub fn tone(&self) -> Option<u8> {
+        self.tone
+    }
+
+    /// MegaChip state, `None` on other platforms.
+    pub fn megachip(&self) -> Option<&MegaChip> {
+        self.mega.as_ref()
+    }
+
+    /// Whether the display shows the MegaChip frame instead of `display`.
+    pub fn is_mega_mode(&self) -> bool {
+        self.mega.as_ref().is_some_and(MegaChip::is_enabled)
+    }
+
+    /// XO-CHIP state, `None` on other platforms.
+    pub fn xochip(&self) -> Option<&XoChip> {
+        self.xo.as_ref()
+    }
+
+    /// The planes the pixel at `x`, `y` is lit on: bit 0 for `display`, bit 1
+    /// for the second XO-CHIP plane.
+    pub fn plane_bits(&self, x: usize, y: usize) -> u8 {
+        let second = self.xo.as_ref().is_some_and(|xo| xo.plane().get(x, y));
+        self.display.get(x, y) as u8 | (second as u8) << 1
+    }
+
+    /// The last change to the MegaChip digitised sound since this was
+    /// called.
+    pub fn take_sound_event(&mut self) -> Option<SoundEvent> {
+        self.mega.as_mut().and_then(MegaChip::take_sound_event)
+    }
+
+    /// Whether the program stopped itself with 00FD.
+    pub fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.is_waiting_vblank = false;
+        self.delay = self.delay.saturating_sub(1);
+        self.set_sound(self.sound.saturating_sub(1));
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_frame();
+        }
+    }
+
+    pub fn sound(&self) -> u8 {
+        self.sound
+    }
+
+    /// Area of the display that changed since the last call to
+    /// `take_dirty_rect`, or `None` if nothing did.
+    pub fn dirty_rect(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    pub fn take_dirty_rect(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    /// Marks the whole display as dirty, so frontends redraw it (for instance,
+    /// after switching colours).
+    pub fn invalidate_display(&mut self) {
+        let (width, height) = (self.display.width(), self.display.height());
+        self.mark_dirty(Rect::new(0, 0, width, height));
+    }
+
+    pub fn tick(&mut self) -> Result<()> {
+        self.poll_keypad();
+
+        if self.is_waiting || self.is_waiting_vblank || self.is_halted {
+            return Ok(());
+        }
+
+        if !self.breakpoints.is_empty() && self.stop_at_breakpoint() {
+            return Ok(());
+        }
+
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_execution(self.pc as u32);
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_instruction();
+        }
+
+        let before = self.tracer.is_some().then_some((self.pc, self.v_registers));
+        let res = self.execute();
+        if let Some((pc, v_registers)) = before {
+            self.record_trace(pc, v_registers);
+        }
+        self.cycles += 1;
+
+        res
+    }
+
+    fn stop_at_breakpoint(&mut self) -> bool {
+        let mut breakpoints = core::mem::take(&mut self.breakpoints);
+        let stop = breakpoints.check(self);
+        self.breakpoints = breakpoints;
+        stop
+    }
+
+    fn execute(&mut self) -> Result<()> {
+        let raw_opcode = self.next_opcode()?;
+        let opcode = Opcode::decode(raw_opcode, self.platform)?;
+
+        match opcode {
+            Opcode::ClearScreen => self.exec_clear_screen()?,
+            Opcode::Ret => self.exec_return()?,
+            Opcode::Jump(addr) => self.exec_jump_absolute(addr)?,
+            Opcode::Call(addr) => self.exec_call(addr)?,
+            Opcode::LoadVx(x, value) => self.exec_load_vx(x, value)?,
+            Opcode::SkipIfEq(x, value) => self.exec_skip_if_equal(x, value)?,
+            Opcode::SkipIfNeq(x, value) => self.exec_skip_if_not_equal(x, value)?,
+            Opcode::SkipEqVxVy(x, y) => self.exec_skip_if_equal_vx_vy(x, y)?,
+            Opcode::AddVx(x, value) => self.exec_add_vx(x, value)?,
+            Opcode::LoadVxVy(x, y) => self.exec_load_vx_vy(x, y)?,
+            Opcode::Or(x, y) => self.exec_or_vx_vy(x, y)?,
+            Opcode::And(x, y) => self.exec_and_vx_vy(x, y)?,
+            Opcode::Xor(x, y) => self.exec_xor_vx_vy(x, y)?,
+            Opcode::Add(x, y) => self.exec_add_vx_vy(x, y)?,
+            Opcode::Sub(x, y) => self.exec_sub_vx_vy(x, y)?,
+            Opcode::ShiftR(x, y) => self.exec_shift_right(x, y)?,
+            Opcode::SubN(x, y) => self.exec_subn_vy_vx(x, y)?,
+            Opcode::ShiftL(x, y) => self.exec_shift_left(x, y)?,
+            Opcode::SkipNeqVxVy(x, y) => self.exec_skip_if_not_equal_vx_vy(x, y)?,
+            Opcode::LoadI(addr) => self.exec_load_i(addr)?,
+            Opcode::JumpOffset(addr) => self.exec_jump_offset(addr)?,
+            Opcode::Rand(x, value) => self.exec_rand(x, value)?,
+            Opcode::Display(x, y, rows) => self.exec_display(x, y, rows)?,
+            Opcode::SkipIfKey(x) => self.exec_skip_if_key(x)?,
+            Opcode::SkipIfNotKey(x) => self.exec_skip_if_not_key(x)?,
+            Opcode::LoadDelay(x) => self.exec_load_delay(x)?,
+            Opcode::WaitForKey(x) => self.exec_wait_for_key(x)?,
+            Opcode::StoreDelay(x) => self.exec_store_delay(x)?,
+            Opcode::StoreSound(x) => self.exec_store_sound(x)?,
+            Opcode::AddI(x) => self.exec_add_i(x)?,
+            Opcode::LoadDigit(x) => self.exec_load_digit(x)?,
+            Opcode::Bcd(x) => self.exec_bcd(x)?,
+            Opcode::StoreRegisters(x) => self.exec_store_registers(x)?,
+            Opcode::LoadRegisters(x) => self.exec_load_registers(x)?,
+            Opcode::StoreFlags(x) => self.exec_store_flags(x)?,
+            Opcode::LoadFlags(x) => self.exec_load_flags(x)?,
+            Opcode::Sys(addr) => self.exec_machine_code(addr)?,
+            Opcode::ScrollDown(n) => self.exec_scroll(0, n as isize)?,
+            Opcode::ScrollUp(n) => self.exec_scroll(0, -(n as isize))?,
+            Opcode::ScrollRight => self.exec_scroll(4, 0)?,
+            Opcode::ScrollLeft => self.exec_scroll(-4, 0)?,
+            Opcode::Exit => self.is_halted = true,
+            Opcode::LowRes => self.exec_set_resolution(DISPLAY_WIDTH, DISPLAY_HEIGHT)?,
+            Opcode::HighRes => self.exec_set_resolution(MAX_DISPLAY_WIDTH, MAX_DISPLAY_HEIGHT)?,
+            Opcode::LoadBigDigit(x) => self.exec_load_big_digit(x)?,
+            Opcode::MegaMode(enabled) => self.exec_mega_mode(enabled)?,
+            Opcode::LoadLongI(hi) => self.exec_load_long_i(hi)?,
+            Opcode::LoadPalette(count) => self.exec_load_palette(count)?,
+            Opcode::SpriteWidth(width) => self.with_mega(|mega| mega.set_sprite_width(width)),
+            Opcode::SpriteHeight(height) => self.with_mega(|mega| mega.set_sprite_height(height)),
+            Opcode::ScreenAlpha(alpha) => {
+                self.with_mega(|mega| mega.set_alpha(alpha));
+                self.invalidate_display();
+            }
+            Opcode::PlaySound(n) => self.exec_play_sound(n)?,
+            Opcode::StopSound => self.with_mega(|mega| mega.set_sound_event(SoundEvent::Stop)),
+            Opcode::BlendMode(n) => {
+                let mode = BlendMode::from_number(n).ok_or(VmError::InvalidOpcode(raw_opcode))?;
+                self.with_mega(|mega| mega.set_blend_mode(mode));
+            }
+            Opcode::CollisionColor(color) => self.with_mega(|mega| mega.set_collision_color(color)),
+            Opcode::CycleBackground => self.exec_cycle_background()?,
+            Opcode::SetColorZones(x, y) => self.exec_set_color_zones(x, y)?,
+            Opcode::SetColorRows(x, y, rows) => self.exec_set_color_rows(x, y, rows)?,
+            Opcode::SkipIfKey2(x) => self.exec_skip_if_key2(x)?,
+            Opcode::SkipIfNotKey2(x) => self.exec_skip_if_not_key2(x)?,
+            Opcode::SetTone(x) => self.exec_set_tone(x)?,
+            Opcode::SaveRange(x, y) => self.exec_save_range(x, y)?,
+            Opcode::LoadRange(x, y) => self.exec_load_range(x, y)?,
+            Opcode::SelectPlanes(planes) => self.exec_select_planes(planes)?,
+            Opcode::LoadPattern => self.exec_load_pattern()?,
+            Opcode::SetPitch(x) => self.exec_set_pitch(x)?,
+        };
+
+        Ok(())
+    }
+
+    fn record_trace(&mut self, pc: u16, v_registers: [u8; 16]) {
+        let raw_opcode = match self.ram.get(pc as usize..pc as usize + 2) {
+            Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+            None => 0,
+        };
+        let changed_registers = (0..16)
+            .filter(|&n| self.v_registers[n] != v_registers[n])
+            .fold(0, |mask, n| mask | 1 << n);
+
+        let entry = TraceEntry {
+            cycle: self.cycles,
+            pc,
+            raw_opcode,
+            opcode: Opcode::decode(raw_opcode, self.platform).ok(),
+            v_registers: self.v_registers,
+            changed_registers,
+            i: self.i_register,
+            writes: Vec::new(),
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(entry);
+        }
+    }
+
+    fn poll_keypad(&mut self) {
+        let previous = self.keys;
+        self.keypad.poll(&mut self.keys);
+
+        let keys = self.keys;
+        for (key, (now, before)) in keys.iter().zip(previous.iter()).enumerate() {
+            if *now && !*before {
+                self.on_key_down(key as u8);
+            }
+        }
+    }
+
+    fn on_key_down(&mut self, key: u8) {
+        if self.is_waiting {
+            self.is_waiting = false;
+            self.v_registers[self.vx_after_wait as usize] = key;
+        }
+    }
+
+    fn set_sound(&mut self, value: u8) {
+        let was_buzzing = self.sound > 0;
+        self.sound = value;
+        if was_buzzing != (value > 0) {
+            self.beeper.set_buzzing(value > 0);
+        }
+    }
+
+    fn load_fonts(&mut self) {
+        let layout = self.font.layout();
+        let small = layout.small as usize;
+        self.ram[small..small + self.font.small().len()].copy_from_slice(self.font.small());
+
+        if let (Some(big), Some(glyphs)) = (layout.big, self.font.big()) {
+            let big = big as usize;
+            self.ram[big..big + glyphs.len()].copy_from_slice(glyphs);
+        }
+    }
+
+    fn next_opcode(&mut self) -> Result<u16> {
+        let hi = self.read_byte()?;
+        let lo = self.read_byte()?;
+        let raw_opcode = u16::from_be_bytes([hi, lo]);
+
+        Ok(raw_opcode)
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        let res = self
+            .ram
+            .get(self.pc as usize)
+            .copied()
+            .ok_or(VmError::InvalidAddress(self.pc as u32));
+        self.advance_pc(1)?;
+        res
+    }
+
+    /// Moves the PC forward, unless it would run past the 64 KiB it can
+    /// address.
+    fn advance_pc(&mut self, by: u16) -> Result<()> {
+        self.pc = self
+            .pc
+            .checked_add(by)
+            .ok_or(VmError::InvalidAddress(self.pc as u32 + by as u32))?;
+        Ok(())
+    }
+
+    #[inline]
+    fn write_byte_at(&mut self, addr: u32, value: u8) -> Result<()> {
+        self.reserve_memory(addr, 1)?;
+        let slot = self
+            .ram
+            .get_mut(addr as usize)
+            .ok_or(VmError::InvalidAddress(addr))?;
+        *slot = value;
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record_write(addr, value);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_write(addr);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn read_byte_at(&mut self, addr: u32) -> Result<u8> {
+        Ok(self.read_bytes_at(addr, 1)?[0])
+    }
+
+    fn with_mega<F: FnOnce(&mut MegaChip)>(&mut self, f: F) {
+        if let Some(mega) = self.mega.as_mut() {
+            f(mega);
+        }
+    }
+
+    fn exec_scroll(&mut self, dx: isize, dy: isize) -> Result<()> {
+        match self.mega.as_mut() {
+            Some(mega) if mega.is_enabled() => mega.scroll(dx, dy),
+            _ => {
+                for plane in self.planes() {
+                    let display = self.plane_mut(plane);
+                    match (dx.signum(), dy.signum()) {
+                        (1, _) => display.scroll_right(dx as usize),
+                        (-1, _) => display.scroll_left(-dx as usize),
+                        (_, 1) => display.scroll_down(dy as usize),
+                        _ => display.scroll_up(-dy as usize),
+                    }
+                }
+                self.redraw_screen();
+            }
+        }
+        self.invalidate_display();
+        Ok(())
+    }
+
+    /// Sends the whole framebuffer to the display sink.
+    fn redraw_screen(&mut self) {
+        self.screen.clear();
+        for y in 0..self.display.height() {
+            for x in 0..self.display.width() {
+                if self.plane_bits(x, y) != 0 {
+                    self.screen.set_pixel(x, y, true);
+                }
+            }
+        }
+    }
+
+    /// The planes selected with the XO-CHIP FN01, from 0 for `display`. Only
+    /// `display` on other platforms.
+    fn planes(&self) -> impl Iterator<Item = usize> {
+        let selected = self.xo.as_ref().map_or(1, XoChip::selected_planes);
+        (0..2).filter(move |plane| selected & 1 << plane != 0)
+    }
+
+    fn plane_mut(&mut self, plane: usize) -> &mut Framebuffer {
+        match (plane, self.xo.as_mut()) {
+            (1, Some(xo)) => xo.plane_mut(),
+            _ => &mut self.display,
+        }
+    }
+
+    fn exec_set_resolution(&mut self, width: usize, height: usize) -> Result<()> {
+        self.display = Framebuffer::new(width, height);
+        if let Some(xo) = self.xo.as_mut() {
+            xo.set_resolution(width, height);
+        }
+        self.screen.clear();
+        self.invalidate_display();
+        Ok(())
+    }
+
+    fn exec_load_big_digit(&mut self, vx: u8) -> Result<()> {
+        let big = self.font.layout().big.unwrap_or_default();
+        let nibble = self.v_registers[vx as usize] & 0x0f;
+        self.i_register = big as u32 + nibble as u32 * 10;
+        Ok(())
+    }
+
+    fn exec_mega_mode(&mut self, enabled: bool) -> Result<()> {
+        self.with_mega(|mega| mega.set_enabled(enabled));
+        self.invalidate_display();
+        Ok(())
+    }
+
+    fn exec_load_long_i(&mut self, hi: u8) -> Result<()> {
+        let lo = self.next_opcode()?;
+        self.i_register = (hi as u32) << 16 | lo as u32;
+        Ok(())
+    }
+
+    fn exec_load_palette(&mut self, count: u8) -> Result<()> {
+        let data = self
+            .read_bytes_at(self.i_register, count as usize * 4)?
+            .to_vec();
+        self.with_mega(|mega| mega.load_palette(&data));
+        Ok(())
+    }
+
+    fn exec_play_sound(&mut self, n: u8) -> Result<()> {
+        self.reserve_memory(self.i_register, SOUND_HEADER_LEN)?;
+        let start = self.i_register as usize;
+        let size = DigitalSound::size(&self.ram[start..]).unwrap_or_default();
+        self.reserve_memory(self.i_register, size)?;
+        let memory = &self.ram[start..];
+        let sound =
+            DigitalSound::parse(memory, n == 0).ok_or(VmError::InvalidAddress(self.i_register))?;
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_reads(self.i_register, 6 + sound.samples.len());
+        }
+        self.with_mega(|mega| mega.set_sound_event(SoundEvent::Play(sound)));
+        Ok(())
+    }
+
+    /// `len` bytes of memory from `addr`.
+    fn read_bytes_at(&mut self, addr: u32, len: usize) -> Result<&[u8]> {
+        self.reserve_memory(addr, len)?;
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_reads(addr, len);
+        }
+        self.ram
+            .get(addr as usize..addr as usize + len)
+            .ok_or(VmError::InvalidAddress(addr))
+    }
+
+    /// Allocates memory up to `addr + len`, unless it is past the end of
+    /// the platform's memory.
+    fn reserve_memory(&mut self, addr: u32, len: usize) -> Result<()> {
+        let end = addr as usize + len;
+        if end > self.platform.memory_size() {
+            return Err(VmError::InvalidAddress(addr));
+        }
+        if end > self.ram.len() {
+            self.ram.resize(memory_len(self.platform, end), 0);
+        }
+        Ok(())
+    }
+
+    /// Runs the 1802 subroutine at `addr` until it returns to the
+    /// interpreter with `SEP R4` (D4), like the VIP does.
+    fn exec_machine_code(&mut self, addr: u16) -> Result<()> {
+        let map = match self.platform.vip_memory_map() {
+            Some(map) => map,
+            None => return Ok(()),
+        };
+        self.store_vip_state(map);
+
+        // the registers the VIP interpreter keeps its state in
+        let mut cpu = Cdp1802::new();
+        cpu.set_r(2, map.stack);
+        cpu.set_x(2);
+        cpu.set_r(3, addr);
+        cpu.set_p(3);
+        cpu.set_r(5, self.pc);
+        cpu.set_r(8, u16::from_be_bytes([self.delay, self.sound]));
+        cpu.set_r(0xa, self.i_register as u16);
+
+        let mut steps = 0;
+        while cpu.p() != 4 {
+            if steps == MAX_MACHINE_CODE_STEPS {
+                return Err(VmError::MachineCodeTimeout(addr));
+            }
+            // the keypad latches the key written with OUT 2, and reports
+            // whether it is pressed on EF3
+            cpu.set_ef(3, self.keys[cpu.output(2) as usize & 0xf]);
+            cpu.step(&mut self.ram)?;
+            steps += 1;
+        }
+
+        self.load_vip_state(map, &cpu);
+        Ok(())
+    }
+
+    /// Copies the registers and the display to where the VIP keeps them.
+    fn store_vip_state(&mut self, map: VipMemoryMap) {
+        let registers = map.registers as usize;
+        self.ram[registers..registers + 16].copy_from_slice(&self.v_registers);
+
+        let (width, height) = (self.display.width(), self.display.height());
+        // the part of the display that is both on screen and in memory
+        let (columns, rows) = (
+            map.display_size.0.min(width) / 8,
+            map.display_size.1.min(height),
+        );
+        let display = map.display as usize;
+        for y in 0..rows {
+            let row = display + y * map.display_size.0 / 8;
+            for (i, byte) in self.ram[row..row + columns].iter_mut().enumerate() {
+                *byte = (self.display.row(y) >> (width - 8 * (i + 1))) as u8;
+            }
+        }
+    }
+
+    /// Reads back what machine code changed of the VIP state.
+    fn load_vip_state(&mut self, map: VipMemoryMap, cpu: &Cdp1802) {
+        let registers = map.registers as usize;
+        self.v_registers
+            .copy_from_slice(&self.ram[registers..registers + 16]);
+        self.pc = cpu.r(5);
+        self.i_register = cpu.r(0xa) as u32;
+        let [delay, sound] = cpu.r(8).to_be_bytes();
+        self.delay = delay;
+        self.set_sound(sound);
+
+        let (width, height) = (self.display.width(), self.display.height());
+        let (columns, rows) = (
+            map.display_size.0.min(width) / 8,
+            map.display_size.1.min(height),
+        );
+        let display = map.display as usize;
+        for y in 0..rows {
+            let row = display + y * map.display_size.0 / 8;
+            for x in 0..columns * 8 {
+                let lit = self.ram[row + x / 8] & (0x80 >> (x % 8)) != 0;
+                if self.display.get(x, y) != lit {
+                    self.display.set(x, y, lit);
+                    self.screen.set_pixel(x, y, lit);
+                    self.mark_dirty(Rect::new(x, y, 1, 1));
+                }
+            }
+        }
+    }
+
+    fn exec_cycle_background(&mut self) -> Result<()> {
+        if let Some(colors) = self.colors.as_mut() {
+            colors.cycle_background();
+        }
+        self.invalidate_display();
+        Ok(())
+    }
+
+    fn exec_set_color_zones(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let horizontal = self.v_registers[vx as usize];
+        let vertical = self.v_registers[(vx as usize + 1) & 0xf];
+        let color = self.v_registers[vy as usize];
+        if let Some(colors) = self.colors.as_mut() {
+            colors.set_zones(
+                (horizontal & 0xf, horizontal >> 4),
+                (vertical & 0xf, vertical >> 4),
+                color,
+            );
+        }
+        self.invalidate_display();
+        Ok(())
+    }
+
+    fn exec_set_color_rows(&mut self, vx: u8, vy: u8, rows: u8) -> Result<()> {
+        let x = self.v_registers[vx as usize];
+        let y = self.v_registers[(vx as usize + 1) & 0xf];
+        let color = self.v_registers[vy as usize];
+        if let Some(colors) = self.colors.as_mut() {
+            colors.set_rows(x, y, rows, color);
+        }
+        self.invalidate_display();
+        Ok(())
+    }
+
+    fn exec_skip_if_key2(&mut self, vx: u8) -> Result<()> {
+        if self.get_vx_second_key(vx)? {
+            self.skip_instruction()?;
+        }
+        Ok(())
+    }
+
+    fn exec_skip_if_not_key2(&mut self, vx: u8) -> Result<()> {
+        if !self.get_vx_second_key(vx)? {
+            self.skip_instruction()?;
+        }
+        Ok(())
+    }
+
+    fn get_vx_second_key(&self, vx: u8) -> Result<bool> {
+        let key = self.v_registers[vx as usize];
+        self.second_keys
+            .get(key as usize)
+            .copied()
+            .ok_or(VmError::InvalidKey(key))
+    }
+
+    fn exec_set_tone(&mut self, vx: u8) -> Result<()> {
+        self.tone = Some(self.v_registers[vx as usize]);
+        Ok(())
+    }
+
+    fn exec_clear_screen(&mut self) -> Result<()> {
+        if let Some(mega) = self.mega.as_mut().filter(|mega| mega.is_enabled()) {
+            mega.present();
+            self.invalidate_display();
+            return Ok(());
+        }
+
+        for plane in self.planes() {
+            self.plane_mut(plane).clear();
+        }
+        self.invalidate_display();
+        if self.xo.is_some() {
+            // the planes that were not selected keep their pixels
+            self.redraw_screen();
+        } else {
+            self.screen.clear();
+        }
+        Ok(())
+    }
+
+    fn exec_jump_absolute(&mut self, addr: u16) -> Result<()> {
+        self.pc = addr;
+        Ok(())
+    }
+
+    fn exec_load_vx(&mut self, x: u8, value: u8) -> Result<()> {
+        self.v_registers[x as usize] = value;
+        Ok(())
+    }
+
+    fn exec_add_vx(&mut self, x: u8, value: u8) -> Result<()> {
+        self.v_registers[x as usize] = self.v_registers[x as usize].wrapping_add(value);
+        Ok(())
+    }
+
+    fn exec_load_i(&mut self, addr: u16) -> Result<()> {
+        self.i_register = addr as u32;
+        Ok(())
+    }
+
+    fn exec_display(&mut self, vx: u8, vy: u8, rows: u8) -> Result<()> {
+        self.v_registers[0xf] = 0x00;
+
+        let sprite_x = self.v_registers[vx as usize] as usize;
+        let sprite_y = self.v_registers[vy as usize] as usize;
+
+        if self.is_mega_mode() {
+            return self.exec_mega_display(sprite_x, sprite_y);
+        }
+
+        // SUPER-CHIP draws 16x16 sprites, two bytes per row, for DXY0
+        let (sprite_width, rows) = if rows == 0 && self.platform.supports_superchip() {
+            (16, 16)
+        } else {
+            (8, rows as usize)
+        };
+        // XO-CHIP draws on every selected plane, with the sprite of the first
+        // one first
+        let planes: Vec<_> = self.planes().collect();
+        let size = rows * sprite_width / 8;
+        let sprite = self
+            .read_bytes_at(self.i_register, size * planes.len())?
+            .to_vec();
+
+        let (width, height) = (self.display.width(), self.display.height());
+        let (sprite_x, sprite_y) = (sprite_x % width, sprite_y % height);
+        // without wrapping, the columns past the right edge are cut off
+        let full = u16::MAX >> (16 - sprite_width);
+        let clip = if self.quirks.wrap || sprite_x + sprite_width <= width {
+            full
+        } else {
+            full << (sprite_x + sprite_width - width) & full
+        };
+
+        for (plane, sprite) in planes.into_iter().zip(sprite.chunks(size.max(1))) {
+            for (row, bytes) in sprite.chunks(sprite_width / 8).enumerate() {
+                if !self.quirks.wrap && sprite_y + row >= height {
+                    break;
+                }
+
+                let y = (sprite_y + row) % height;
+                let bits = bytes.iter().fold(0, |bits, byte| bits << 8 | *byte as u16) & clip;
+                let (mask, did_erase) =
+                    self.plane_mut(plane)
+                        .xor_sprite_row(sprite_x, y, bits, sprite_width);
+                if did_erase {
+                    self.v_registers[0xf] = 0x01;
+                }
+
+                self.notify_row_change(y, mask);
+            }
+        }
+
+        self.is_waiting_vblank = self.quirks.vblank;
+        Ok(())
+    }
+
+    fn exec_mega_display(&mut self, x: usize, y: usize) -> Result<()> {
+        let (width, height) = self.mega.as_ref().map_or((0, 0), MegaChip::sprite_size);
+        let sprite = self
+            .read_bytes_at(self.i_register, width * height)?
+            .to_vec();
+        let collision = self
+            .mega
+            .as_mut()
+            .is_some_and(|mega| mega.draw(x, y, &sprite));
+        self.v_registers[0xf] = collision as u8;
+
+        self.is_waiting_vblank = self.quirks.vblank;
+        Ok(())
+    }
+
+    fn notify_row_change(&mut self, y: usize, mask: u128) {
+        if let Some(rect) = self.display.mask_bounds(y, mask) {
+            self.mark_dirty(rect);
+        }
+
+        for x in self.display.mask_columns(mask) {
+            self.screen.set_pixel(x, y, self.plane_bits(x, y) != 0);
+        }
+    }
+
+    #[inline]
+    fn mark_dirty(&mut self, rect: Rect) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(&rect),
+            None => rect,
+        });
+    }
+
+    /// Skips the next instruction, which is 4 bytes long if it is the
+    /// XO-CHIP `F000 NNNN`.
+    fn skip_instruction(&mut self) -> Result<()> {
+        let pc = self.pc as usize;
+        let is_long = self.xo.is_some() && self.ram.get(pc..pc + 2) == Some(&[0xf0, 0x00]);
+        self.advance_pc(if is_long { 4 } else { 2 })
+    }
+
+    fn exec_skip_if_equal(&mut self, vx: u8, value: u8) -> Result<()> {
+        if self.v_registers[vx as usize] == value {
+            self.skip_instruction()?;
+        }
+
+        Ok(())
+    }
+
+    fn exec_skip_if_not_equal(&mut self, vx: u8, value: u8) -> Result<()> {
+        if self.v_registers[vx as usize] != value {
+            self.skip_instruction()?;
+        }
+
+        Ok(())
+    }
+
+    fn exec_skip_if_equal_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        if self.v_registers[vx as usize] == self.v_registers[vy as usize] {
+            self.skip_instruction()?;
+        }
+
+        Ok(())
+    }
+
+    fn exec_skip_if_not_equal_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        if self.v_registers[vx as usize] != self.v_registers[vy as usize] {
+            self.skip_instruction()?;
+        }
+
+        Ok(())
+    }
+
+    fn exec_load_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        self.v_registers[vx as usize] = self.v_registers[vy as usize];
+        Ok(())
+    }
+
+    fn exec_or_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        self.v_registers[vx as usize] |= self.v_registers[vy as usize];
+        self.reset_vf_after_logic();
+        Ok(())
+    }
+
+    fn exec_and_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        self.v_registers[vx as usize] &= self.v_registers[vy as usize];
+        self.reset_vf_after_logic();
+        Ok(())
+    }
+
+    fn exec_xor_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        self.v_registers[vx as usize] ^= self.v_registers[vy as usize];
+        self.reset_vf_after_logic();
+        Ok(())
+    }
+
+    #[inline]
+    fn reset_vf_after_logic(&mut self) {
+        if self.quirks.logic {
+            self.v_registers[0xf] = 0x00;
+        }
+    }
+
+    /// The register shifted by 8XY6 and 8XYE.
+    #[inline]
+    fn shift_source(&self, vx: u8, vy: u8) -> u8 {
+        if self.quirks.shift {
+            self.v_registers[vx as usize]
+        } else {
+            self.v_registers[vy as usize]
+        }
+    }
+
+    fn exec_add_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let (value, carry) =
+            self.v_registers[vx as usize].overflowing_add(self.v_registers[vy as usize]);
+        self.v_registers[vx as usize] = value;
+        self.v_registers[0xf] = if carry { 0x01 } else { 0x00 };
+
+        Ok(())
+    }
+
+    fn exec_sub_vx_vy(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let (value, overflow) =
+            self.v_registers[vx as usize].overflowing_sub(self.v_registers[vy as usize]);
+        self.v_registers[vx as usize] = value;
+        self.v_registers[0xf] = if overflow { 0x00 } else { 0x01 };
+
+        Ok(())
+    }
+
+    fn exec_shift_right(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let y = self.shift_source(vx, vy);
+        let shifted_out = y & 0b0000_0001;
+        self.v_registers[vx as usize] = y >> 1;
+        self.v_registers[0xf] = shifted_out;
+
+        Ok(())
+    }
+
+    fn exec_subn_vy_vx(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let (value, overflow) =
+            self.v_registers[vy as usize].overflowing_sub(self.v_registers[vx as usize]);
+        self.v_registers[vx as usize] = value;
+        self.v_registers[0xf] = if overflow { 0x00 } else { 0x01 };
+
+        Ok(())
+    }
+
+    fn exec_shift_left(&mut self, vx: u8, vy: u8) -> Result<()> {
+        let y = self.shift_source(vx, vy);
+        let shifted_out = (y & 0b1000_0000) >> 7;
+        self.v_registers[vx as usize] = y << 1;
+        self.v_registers[0xf] = shifted_out;
+
+        Ok(())
+    }
+
+    fn exec_jump_offset(&mut self, addr: u16) -> Result<()> {
+        let offset_register = if self.quirks.jump {
+            (addr >> 8) & 0xf
+        } else {
+            0x0
+        };
+        self.pc = addr + self.v_registers[offset_register as usize] as u16;
+        Ok(())
+    }
+
+    fn exec_rand(&mut self, vx: u8, value: u8) -> Result<()> {
+        self.v_registers[vx as usize] = self.randomize.random_byte() & value;
+        Ok(())
+    }
+
+    fn exec_skip_if_key(&mut self, vx: u8) -> Result<()> {
+        let state = self.get_vx_key(vx)?;
+        if state {
+            self.skip_instruction()?;
+        }
+        Ok(())
+    }
+
+    fn exec_skip_if_not_key(&mut self, vx: u8) -> Result<()> {
+        let state = self.get_vx_key(vx)?;
+        if !state {
+            self.skip_instruction()?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn get_vx_key(&self, vx: u8) -> Result<bool> {
+        let key = self.v_registers[vx as usize];
+        self.keys
+            .get(key as usize)
+            .copied()
+            .ok_or(VmError::InvalidKey(key))
+    }
+
+    fn exec_wait_for_key(&mut self, vx: u8) -> Result<()> {
+        self.vx_after_wait = vx;
+        self.is_waiting = true;
+
+        Ok(())
+    }
+
+    fn exec_load_delay(&mut self, vx: u8) -> Result<()> {
+        self.v_registers[vx as usize] = self.delay;
+        Ok(())
+    }
+
+    fn exec_store_delay(&mut self, vx: u8) -> Result<()> {
+        self.delay = self.v_registers[vx as usize];
+        Ok(())
+    }
+
+    fn exec_store_sound(&mut self, vx: u8) -> Result<()> {
+        self.set_sound(self.v_registers[vx as usize]);
+        Ok(())
+    }
+
+    fn exec_store_registers(&mut self, vx: u8) -> Result<()> {
+        for i in 0..=vx as usize {
+            self.write_byte_at(self.i_register + i as u32, self.v_registers[i])?;
+        }
+        self.i_register += self.quirks.memory_increment(vx) as u32;
+
+        Ok(())
+    }
+
+    fn exec_load_registers(&mut self, vx: u8) -> Result<()> {
+        for i in 0..=vx as usize {
+            self.v_registers[i] = self.read_byte_at(self.i_register + i as u32)?;
+        }
+        self.i_register += self.quirks.memory_increment(vx) as u32;
+
+        Ok(())
+    }
+
+    fn exec_save_range(&mut self, vx: u8, vy: u8) -> Result<()> {
+        for (offset, n) in register_range(vx, vy).enumerate() {
+            self.write_byte_at(self.i_register + offset as u32, self.v_registers[n])?;
+        }
+        Ok(())
+    }
+
+    fn exec_load_range(&mut self, vx: u8, vy: u8) -> Result<()> {
+        for (offset, n) in register_range(vx, vy).enumerate() {
+            self.v_registers[n] = self.read_byte_at(self.i_register + offset as u32)?;
+        }
+        Ok(())
+    }
+
+    fn exec_select_planes(&mut self, planes: u8) -> Result<()> {
+        if let Some(xo) = self.xo.as_mut() {
+            xo.select_planes(planes);
+        }
+        Ok(())
+    }
+
+    fn exec_load_pattern(&mut self) -> Result<()> {
+        let mut pattern = [0; PATTERN_LEN];
+        pattern.copy_from_slice(self.read_bytes_at(self.i_register, PATTERN_LEN)?);
+        if let Some(xo) = self.xo.as_mut() {
+            xo.set_pattern(pattern);
+        }
+        Ok(())
+    }
+
+    fn exec_set_pitch(&mut self, vx: u8) -> Result<()> {
+        let pitch = self.v_registers[vx as usize];
+        if let Some(xo) = self.xo.as_mut() {
+            xo.set_pitch(pitch);
+        }
+        Ok(())
+    }
+
+    fn exec_store_flags(&mut self, vx: u8) -> Result<()> {
+        let count = self.flags_to_copy(vx, 0xf075)?;
+        self.rpl_flags[..count].copy_from_slice(&self.v_registers[..count]);
+        Ok(())
+    }
+
+    fn exec_load_flags(&mut self, vx: u8) -> Result<()> {
+        let count = self.flags_to_copy(vx, 0xf085)?;
+        self.v_registers[..count].copy_from_slice(&self.rpl_flags[..count]);
+        Ok(())
+    }
+
+    /// Number of flags FX75 or FX85 copies, if the platform has that many.
+    fn flags_to_copy(&self, vx: u8, opcode: u16) -> Result<usize> {
+        let count = vx as usize + 1;
+        if count > self.platform.rpl_flag_count() {
+            return Err(VmError::InvalidOpcode(opcode | (vx as u16) << 8));
+        }
+        Ok(count)
+    }
+
+    fn exec_call(&mut self, addr: u16) -> Result<()> {
+        self.stack.push(self.pc);
+        self.pc = addr;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_call(addr);
+        }
+        Ok(())
+    }
+
+    fn exec_return(&mut self) -> Result<()> {
+        self.pc = self.stack.pop().ok_or(VmError::EmptyStack)?;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record_return();
+        }
+        Ok(())
+    }
+
+    fn exec_add_i(&mut self, vx: u8) -> Result<()> {
+        self.i_register += self.v_registers[vx as usize] as u32;
+        Ok(())
+    }
+
+    fn exec_bcd(&mut self, vx: u8) -> Result<()> {
+        let mut value = self.v_registers[vx as usize];
+        let hundreds = value / 100;
+        value -= hundreds * 100;
+        let tens = value / 10;
+        value -= tens * 10;
+
+        self.write_byte_at(self.i_register, hundreds)?;
+        self.write_byte_at(self.i_register + 1, tens)?;
+        self.write_byte_at(self.i_register + 2, value)?;
+
+        Ok(())
+    }
+
+    fn exec_load_digit(&mut self, vx: u8) -> Result<()> {
+        let nibble = self.v_registers[vx as usize] & 0x0f;
+        let addr = self.font.layout().small + nibble as u16 * 5;
+        self.i_register = addr as u32;
+
+        Ok(())
+    }
+}
+
+/// The registers from VX to VY, counting down if X is greater than Y.
+fn register_range(vx: u8, vy: u8) -> impl Iterator<Item = usize> {
+    let (x, y) = (vx as usize, vy as usize);
+    (0..=x.abs_diff(y)).map(move |i| if x <= y { x + i } else { x - i })
+}
+
+/// Memory to allocate for the first `end` bytes: all of it on the small
+/// platforms, whole pages up to the platform's size on the others.
+fn memory_len(platform: Platform, end: usize) -> usize {
+    let pages = end.max(1).div_ceil(MEMORY_PAGE);
+    platform.memory_size().min(pages * MEMORY_PAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[allow(clippy::needless_borrow)]
+    fn any_vm(rom: &[u8]) -> Vm<fn() -> u8> {
+        Vm::new(&rom, || 0x00)
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn set_key_updates_value() {
+        let mut vm = any_vm(&[]);
+        let res = vm.set_key(0xf, true);
+
+        assert!(res.is_ok());
+        assert_eq!(vm.keys[0xf], true);
+    }
+
+    #[test]
+    fn does_not_tick_when_waiting() {
+        let mut vm = any_vm(&[0x00, 0xe0]);
+        vm.is_waiting = true;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x200);
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn stops_wait_and_loads_vx_after_key_down() {
+        let mut vm = any_vm(&[]);
+        vm.is_waiting = true;
+        vm.vx_after_wait = 0xb;
+
+        let _ = vm.set_key(0xa, false);
+        assert_eq!(vm.is_waiting, true);
+
+        let _ = vm.set_key(0xa, true);
+        assert_eq!(vm.is_waiting, false);
+        assert_eq!(vm.v_registers[0xb], 0xa);
+    }
+
+    #[test]
+    fn ticks_timers() {
+        let mut vm = any_vm(&[]);
+        vm.delay = 0x01;
+        vm.sound = 0x02;
+
+        vm.tick_timers();
+        assert_eq!(vm.delay, 0x00);
+        assert_eq!(vm.sound, 0x01);
+
+        vm.tick_timers();
+        assert_eq!(vm.delay, 0x00);
+        assert_eq!(vm.sound, 0x00);
+    }
+
+    #[derive(Debug, Default, PartialEq, Clone)]
+    struct RecordingHost {
+        pixels: Vec<(usize, usize, bool)>,
+        clears: usize,
+        buzzing: Vec<bool>,
+        pressed_key: Option<usize>,
+    }
+
+    impl Display for RecordingHost {
+        fn clear(&mut self) {
+            self.clears += 1;
+        }
+
+        fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
+            self.pixels.push((x, y, value));
+        }
+    }
+
+    impl Keypad for RecordingHost {
+        fn poll(&mut self, keys: &mut [bool; 16]) {
+            if let Some(key) = self.pressed_key {
+                keys[key] = true;
+            }
+        }
+    }
+
+    impl Beeper for RecordingHost {
+        fn set_buzzing(&mut self, buzzing: bool) {
+            self.buzzing.push(buzzing);
+        }
+    }
+
+    #[test]
+    fn notifies_display_sink() {
+        let rom = [0xd0, 0x11, 0x00, 0xe0, 0xc0];
+        let mut vm = any_vm(&rom).with_display(RecordingHost::default());
+        vm.i_register = 0x204;
+
+        let _ = vm.tick();
+        assert_eq!(vm.screen.pixels, vec![(0, 0, true), (1, 0, true)]);
+
+        let _ = vm.tick();
+        assert_eq!(vm.screen.clears, 1);
+    }
+
+    #[test]
+    fn polls_keypad_before_each_instruction() {
+        let rom = [0xf3, 0x0a, 0x00, 0x00];
+        let mut vm = any_vm(&rom).with_keypad(RecordingHost::default());
+
+        let _ = vm.tick();
+        assert!(vm.is_waiting);
+
+        vm.keypad.pressed_key = Some(0x7);
+        let _ = vm.tick();
+        assert!(!vm.is_waiting);
+        assert!(vm.keys[0x7]);
+        assert_eq!(vm.v_registers[0x3], 0x7);
+    }
+
+    #[test]
+    fn notifies_beeper_when_buzzing_starts_and_stops() {
+        let rom = [0xf0, 0x18];
+        let mut vm = any_vm(&rom).with_beeper(RecordingHost::default());
+        vm.v_registers[0x0] = 0x02;
+
+        let _ = vm.tick();
+        vm.tick_timers();
+        vm.tick_timers();
+
+        assert_eq!(vm.beeper.buzzing, vec![true, false]);
+    }
+
+    #[test]
+    fn loads_rom_at_platform_address() {
+        let vm = Vm::for_platform(Platform::Eti660, &[0xab, 0xcd], || 0x00);
+
+        assert_eq!(vm.pc, 0x600);
+        assert_eq!(vm.ram[0x600..0x602], [0xab, 0xcd]);
+        assert_eq!(vm.ram.len(), 0x1000);
+        assert_eq!((vm.display.width(), vm.display.height()), (64, 48));
+    }
+
+    #[test]
+    fn traces_instructions() {
+        let rom = [0x6a, 0x02, 0xa3, 0x00, 0xfa, 0x33, 0x00, 0xe0];
+        let mut vm = any_vm(&rom);
+        vm.tick().unwrap();
+        vm.enable_tracing(2);
+        for _ in 0..3 {
+            vm.tick().unwrap();
+        }
+
+        let entries: Vec<_> = vm.tracer().unwrap().entries().cloned().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].cycle, 2);
+        assert_eq!(entries[0].opcode, Some(Opcode::Bcd(0xa)));
+        assert_eq!(entries[0].writes, [(0x300, 0), (0x301, 0), (0x302, 2)]);
+        assert_eq!(entries[1].pc, 0x206);
+        assert!(entries[1].writes.is_empty());
+        assert_eq!(vm.cycles(), 4);
+    }
+
+    #[test]
+    fn counts_coverage() {
+        let rom = [0xa2, 0x08, 0xf1, 0x65, 0xf1, 0x55, 0x12, 0x06];
+        let mut vm = any_vm(&rom);
+        vm.enable_coverage();
+        for _ in 0..5 {
+            vm.tick().unwrap();
+        }
+
+        let coverage = vm.coverage().unwrap();
+        assert_eq!(
+            coverage.executions()[0x200..0x208],
+            [1, 0, 1, 0, 1, 0, 2, 0]
+        );
+        assert_eq!(coverage.reads()[0x208..0x20c], [1, 1, 0, 0]);
+        assert_eq!(coverage.writes()[0x208..0x20c], [0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn names_addresses_with_symbols() {
+        // 200: call 204, 202: jump 202, 204: call 208, 206: return, 208: invalid
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x22, 0x08, 0x00, 0xee, 0xff, 0xff];
+        let mut vm = any_vm(&rom);
+        vm.set_symbols(Symbols::parse("200 main\n204 update\n208 draw").unwrap());
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+
+        assert_eq!(vm.stack(), [0x202, 0x206]);
+        assert_eq!(
+            vm.format_stack(),
+            "at 0x0208 (draw)\n\
+             called from 0x0204 (update)\n\
+             called from 0x0200 (main)\n"
+        );
+        let err = vm.tick().unwrap_err();
+        assert_eq!(
+            vm.describe_error(&err),
+            "Invalid opcode: 0xffff at 0x0208 (draw)"
+        );
+    }
+
+    #[test]
+    fn stops_at_conditional_breakpoints() {
+        // 200: V0 += 1, 202: jump 200
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut vm = any_vm(&rom);
+        let id = vm.add_breakpoint(Breakpoint::parse("pc == 0x202 && v0 >= 2").unwrap());
+        vm.add_breakpoint(Breakpoint::parse("pc == 0x200 => v0 = {v0}").unwrap());
+
+        let mut ticks = 0;
+        while vm.breakpoint_hit().is_none() {
+            vm.tick().unwrap();
+            ticks += 1;
+        }
+        assert_eq!(ticks, 4);
+        assert_eq!(vm.breakpoint_hit(), Some(id));
+        assert_eq!((vm.pc, vm.v_registers[0]), (0x202, 2));
+        assert_eq!(vm.take_tracepoint_log(), ["v0 = 0", "v0 = 1"]);
+
+        vm.tick().unwrap();
+        assert_eq!((vm.pc, vm.breakpoint_hit()), (0x200, None));
+        assert_eq!(vm.breakpoint(id).unwrap().hits(), 1);
+        vm.clear_breakpoints();
+        vm.tick().unwrap();
+        assert_eq!(vm.v_registers[0], 3);
+    }
+
+    #[test]
+    fn opcode_clear_screen() {
+        let rom = [0x00, 0xe0];
+        let mut vm = any_vm(&rom);
+        vm.display.set(0, 0, true);
+        vm.display.set(63, 31, true);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert!(vm.display.is_blank());
+    }
+
+    #[test]
+    fn runs_machine_code_subroutines() {
+        let rom = [
+            0x60, 0x07, // V0 = 7
+            0x02, 0x06, // call machine code at 0x206
+            0x12, 0x04, // loop
+            // R7 = 0xEF0 (V0), D = M(R7) + 1, store it in V0 and, through
+            // R9, the first byte of the display, then return with SEP R4
+            0xf8, 0x0e, 0xb7, 0xf8, 0xf0, 0xa7, //
+            0x07, 0xfc, 0x01, 0x57, 0xa9, //
+            0xf8, 0x0f, 0xb7, 0xf8, 0x00, 0xa7, 0x89, 0x57, //
+            0xd4,
+        ];
+        let mut vm = Vm::for_platform(Platform::HybridVip, &rom, || 0x00);
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+
+        assert_eq!(vm.v_registers[0], 0x08);
+        assert_eq!(vm.pc, 0x204);
+        assert_eq!(vm.display.row(0) >> 56, 0x08);
+        assert_eq!(vm.take_dirty_rect(), Some(Rect::new(0, 0, 64, 32)));
+    }
+
+    #[test]
+    fn runs_machine_code_with_platform_memory_map() {
+        for platform in [Platform::Chip8HiRes, Platform::Chip8X] {
+            let map = platform.vip_memory_map().unwrap();
+            let start = platform.load_address();
+            let [call_hi, call_lo] = (start + 6).to_be_bytes();
+            let [jump_hi, jump_lo] = (0x1000 | (start + 4)).to_be_bytes();
+            let [reg_hi, reg_lo] = map.registers.to_be_bytes();
+            // the first byte of the last row
+            let last_row = map.display + (map.display_size.1 as u16 - 1) * 8;
+            let [row_hi, row_lo] = last_row.to_be_bytes();
+            let rom = [
+                0x60, 0x07, // V0 = 7
+                call_hi, call_lo, // call machine code after the loop
+                jump_hi, jump_lo, // loop
+                // V0 += 1 and copy it to the last row, like above
+                0xf8, reg_hi, 0xb7, 0xf8, reg_lo, 0xa7, //
+                0x07, 0xfc, 0x01, 0x57, 0xa9, //
+                0xf8, row_hi, 0xb7, 0xf8, row_lo, 0xa7, 0x89, 0x57, //
+                0xd4,
+            ];
+            let mut vm = Vm::for_platform(platform, &rom, || 0x00);
+            vm.tick().unwrap();
+            vm.tick().unwrap();
+
+            let (width, height) = platform.display_size();
+            assert_eq!(vm.v_registers[0], 0x08, "{:?}", platform);
+            assert_eq!(vm.display.row(height - 1) >> (width - 8), 0x08);
+        }
+    }
+
+    #[test]
+    fn ignores_machine_code_without_vip() {
+        let mut vm = Vm::for_platform(Platform::SuperChip, &[0x02, 0x06], || 0x00);
+        vm.tick().unwrap();
+        assert_eq!(vm.pc, 0x202);
+
+        let mut vm = any_vm(&[0x00, 0x00, 0x00, 0x00]);
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+        assert_eq!(vm.pc, 0x204);
+    }
+
+    #[test]
+    fn stops_machine_code_that_does_not_return() {
+        // BR 06: loops forever
+        let mut vm = Vm::for_platform(Platform::HybridVip, &[0x02, 0x02, 0x30, 0x02], || 0x00);
+        assert_eq!(vm.tick(), Err(VmError::MachineCodeTimeout(0x202)));
+    }
+
+    #[test]
+    fn opcode_jump_absolute() {
+        let rom = [0x1a, 0xbc];
+        let mut vm = any_vm(&rom);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0xabc);
+    }
+
+    #[test]
+    fn opcode_load_vx() {
+        let rom = [0x6a, 0xbc];
+        let mut vm = any_vm(&rom);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0xa], 0xbc);
+    }
+
+    #[test]
+    fn opcode_add_vx() {
+        let rom = [0x7a, 0xbc];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0xa] = 0x11;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0xa], 0x11 + 0xbc);
+    }
+
+    #[test]
+    fn opcode_skip_if_not_equal_vx_vy() {
+        let rom = [0x90, 0x10];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+        vm.v_registers[0x1] = 0x01;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.v_registers[0x0] = 0x01;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn opcode_load_i() {
+        let rom = [0xaa, 0xbc];
+        let mut vm = any_vm(&rom);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.i_register, 0x0abc);
+    }
+
+    #[test]
+    fn opcode_display() {
+        let rom = [0xd0, 0x14, 0xd0, 0x14, 0xff, 0x81, 0x81, 0xff];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x204;
+        vm.v_registers[0x0] = 0x01;
+        vm.v_registers[0x1] = 0x00;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0xf], 0x00);
+        let pixels: Vec<bool> = vm.display.pixels().collect();
+        assert_eq!(pixels[1..9], [true; 8]);
+        assert_eq!(
+            pixels[(64 + 1)..(64 + 9)],
+            [true, false, false, false, false, false, false, true]
+        );
+        assert_eq!(
+            pixels[(64 * 2 + 1)..(64 * 2 + 9)],
+            [true, false, false, false, false, false, false, true]
+        );
+        assert_eq!(pixels[(64 * 3 + 1)..(64 * 3 + 9)], [true; 8]);
+
+        res = vm.tick();
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+        assert!(vm.display.is_blank());
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn tracks_dirty_rect_of_drawn_sprites() {
+        let rom = [0xd0, 0x12, 0x00, 0xe0, 0x81, 0xff];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x204;
+        vm.v_registers[0x0] = 10;
+        vm.v_registers[0x1] = 20;
+
+        assert_eq!(
+            vm.take_dirty_rect(),
+            Some(Rect::new(0, 0, DISPLAY_WIDTH, DISPLAY_HEIGHT))
+        );
+        assert_eq!(vm.dirty_rect(), None);
+
+        let _ = vm.tick();
+        assert_eq!(vm.take_dirty_rect(), Some(Rect::new(10, 20, 8, 2)));
+
+        let _ = vm.tick();
+        assert_eq!(
+            vm.take_dirty_rect(),
+            Some(Rect::new(0, 0, DISPLAY_WIDTH, DISPLAY_HEIGHT))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::identity_op)]
+    fn opcode_display_with_wrap() {
+        let rom = [0xd0, 0x12, 0xff, 0xff];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x202;
+        vm.v_registers[0x0] = 60;
+        vm.v_registers[0x1] = 31;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        let pixels: Vec<bool> = vm.display.pixels().collect();
+        assert_eq!(pixels[0..4], [true; 4]);
+        assert_eq!(pixels[60..64], [true; 4]);
+        assert_eq!(
+            pixels[0 + 31 * DISPLAY_WIDTH..(4 + 31 * DISPLAY_WIDTH)],
+            [true; 4]
+        );
+        assert_eq!(
+            pixels[60 + 31 * DISPLAY_WIDTH..(64 + 31 * DISPLAY_WIDTH)],
+            [true; 4]
+        );
+    }
+
+    #[test]
+    fn opcode_display_clips_without_wrap_quirk() {
+        let rom = [0xd0, 0x12, 0xff, 0xff];
+        let mut vm = any_vm(&rom);
+        vm.set_quirks(Quirks::cosmac_vip());
+        vm.i_register = 0x202;
+        vm.v_registers[0x0] = 60;
+        vm.v_registers[0x1] = 31;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        let pixels: Vec<bool> = vm.display.pixels().collect();
+        assert_eq!(pixels.iter().filter(|on| **on).count(), 4);
+        assert_eq!(
+            pixels[60 + 31 * DISPLAY_WIDTH..(64 + 31 * DISPLAY_WIDTH)],
+            [true; 4]
+        );
+    }
+
+    #[test]
+    fn opcode_display_waits_for_vblank_quirk() {
+        let rom = [0xd0, 0x11, 0x60, 0x01];
+        let mut vm = any_vm(&rom);
+        vm.set_quirks(Quirks::cosmac_vip());
+
+        let _ = vm.tick();
+        let _ = vm.tick();
+        assert_eq!(vm.pc, 0x202);
+
+        vm.tick_timers();
+        let _ = vm.tick();
+        assert_eq!(vm.pc, 0x204);
+    }
+
+    #[test]
+    fn opcode_skip_if_equal() {
+        let rom = [0x30, 0xab];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xab;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.v_registers[0x0] = 0x0;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn opcode_skip_if_not_equal() {
+        let rom = [0x40, 0xab];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.v_registers[0x0] = 0xab;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn stops_pc_at_end_of_address_space() {
+        let mut vm = Vm::for_platform(Platform::MegaChip, &[0x00, 0xe0], || 0x00);
+        // V0 == 0: skips past 0xffff
+        vm.ram[0xfffc..0x10000].copy_from_slice(&[0x30, 0x00, 0x00, 0xe0]);
+        vm.pc = 0xfffc;
+        assert_eq!(vm.tick(), Err(VmError::InvalidAddress(0x10000)));
+
+        vm.pc = 0xfffe;
+        assert_eq!(vm.tick(), Err(VmError::InvalidAddress(0x10000)));
+    }
+
+    #[test]
+    fn opcode_skip_if_equal_vx_vy() {
+        let rom = [0x50, 0x10];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+        vm.v_registers[0x1] = 0x00;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.v_registers[0x0] = 0xab;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn opcode_load_vx_vy() {
+        let rom = [0x80, 0x10];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x1] = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0xab);
+    }
+
+    #[test]
+    fn opcode_or_vx_vy() {
+        let rom = [0x80, 0x11];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x0f;
+        vm.v_registers[0x1] = 0b_0101_0101;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0b_0101_1111);
+    }
+
+    #[test]
+    fn opcode_or_vx_vy_resets_vf_with_logic_quirk() {
+        let rom = [0x80, 0x11];
+        let mut vm = any_vm(&rom);
+        vm.set_quirks(Quirks::cosmac_vip());
+        vm.v_registers[0xf] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.v_registers[0xf], 0x00);
+    }
+
+    #[test]
+    fn opcode_and_vx_vy() {
+        let rom = [0x80, 0x12];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x0f;
+        vm.v_registers[0x1] = 0b_0101_0101;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0b_0000_0101);
+    }
+
+    #[test]
+    fn opcode_xor_vx_vy() {
+        let rom = [0x80, 0x13];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x0f;
+        vm.v_registers[0x1] = 0b_0101_0101;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0b_0101_1010);
+    }
+
+    #[test]
+    fn opcode_add_vx_vy() {
+        let rom = [0x80, 0x14];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x02;
+        vm.v_registers[0x1] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x03);
+        assert_eq!(vm.v_registers[0xf], 0x00);
+    }
+
+    #[test]
+    fn opcode_add_vx_vy_with_carry() {
+        let rom = [0x80, 0x14];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xff;
+        vm.v_registers[0x1] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x00);
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn opcode_sub_vx_vy() {
+        let rom = [0x80, 0x15];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x03;
+        vm.v_registers[0x1] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x02);
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn opcode_sub_vx_vy_with_borrow() {
+        let rom = [0x80, 0x15];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+        vm.v_registers[0x1] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0xff);
+        assert_eq!(vm.v_registers[0xf], 0x00);
+    }
+
+    #[test]
+    fn opcode_shift_right() {
+        let rom = [0x80, 0x16];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+        vm.v_registers[0x1] = 0b_0000_0011;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x01);
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn opcode_shift_right_in_place_with_shift_quirk() {
+        let rom = [0x80, 0x16];
+        let mut vm = any_vm(&rom);
+        vm.set_quirks(Quirks::superchip());
+        vm.v_registers[0x0] = 0b_0000_0100;
+        vm.v_registers[0x1] = 0b_0000_0011;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.v_registers[0x0], 0b_0000_0010);
+        assert_eq!(vm.v_registers[0xf], 0x00);
+    }
+
+    #[test]
+    fn opcode_subn_vy_vx() {
+        let rom = [0x80, 0x17];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x01;
+        vm.v_registers[0x1] = 0x03;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x02);
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn opcode_shift_left() {
+        let rom = [0x80, 0x1e];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0x00;
+        vm.v_registers[0x1] = 0b_1000_0001;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0x02);
+        assert_eq!(vm.v_registers[0xf], 0x01);
+    }
+
+    #[test]
+    fn opcode_jump_offset() {
+        let rom = [0xb2, 0x00];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x2ab);
+    }
+
+    #[test]
+    fn opcode_jump_offset_with_jump_quirk() {
+        let rom = [0xb2, 0x10];
+        let mut vm = any_vm(&rom);
+        vm.set_quirks(Quirks::superchip());
+        vm.v_registers[0x2] = 0x01;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x211);
+    }
+
+    #[test]
+    fn opcode_rand() {
+        let rom = [0xc0, 0x0f];
+        let mut vm = Vm::new(&rom, || 0b1010_1010);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0b0000_1010);
+    }
+
+    #[test]
+    fn opcode_skip_if_key() {
+        let rom = [0xe0, 0x9e];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xa;
+        vm.keys[0xa] = true;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.keys[0xa] = false;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn opcode_skip_if_not_key() {
+        let rom = [0xe0, 0xa1];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xa;
+        vm.keys[0xa] = false;
+
+        let mut res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x204);
+
+        vm.pc = 0x200;
+        vm.keys[0xa] = true;
+        res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn opcode_wait_for_key() {
+        let rom = [0xf0, 0x0a];
+        let mut vm = any_vm(&rom);
+
+        let res = vm.tick();
+        assert!(res.is_ok());
+        assert_eq!(vm.is_waiting, true);
+        assert_eq!(vm.pc, 0x202);
+
+        let _ = vm.set_key(0xa, true);
+        assert_eq!(vm.is_waiting, false);
+        assert_eq!(vm.v_registers[0x0], 0xa);
+    }
+
+    #[test]
+    fn opcode_load_delay() {
+        let rom = [0xf0, 0x07];
+        let mut vm = any_vm(&rom);
+        vm.delay = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.v_registers[0x0], 0xab);
+    }
+
+    #[test]
+    fn opcode_store_delay() {
+        let rom = [0xf0, 0x15];
+        let mut vm = any_vm(&rom);
+        vm.delay = 0x00;
+        vm.v_registers[0x0] = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.delay, 0xab);
+    }
+
+    #[test]
+    fn opcode_store_sound() {
+        let rom = [0xf0, 0x18];
+        let mut vm = any_vm(&rom);
+        vm.sound = 0x00;
+        vm.v_registers[0x0] = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.sound, 0xab);
+    }
+
+    #[test]
+    fn opcode_store_registers() {
+        let rom = [0xf2, 0x55];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x300;
+        vm.v_registers[0x0..0x03].copy_from_slice(&[0xa, 0xb, 0xc]);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.i_register, 0x303);
+        assert_eq!(vm.ram[0x300..0x303], [0xa, 0xb, 0xc]);
+    }
+
+    #[test]
+    fn opcode_store_registers_with_memory_quirks() {
+        let rom = [0xf2, 0x55, 0xf2, 0x55];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x300;
+
+        vm.set_quirks(Quirks::superchip());
+        let _ = vm.tick();
+        assert_eq!(vm.i_register, 0x300);
+
+        vm.set_quirks(Quirks {
+            memory_increment_by_x: true,
+            ..Quirks::default()
+        });
+        let _ = vm.tick();
+        assert_eq!(vm.i_register, 0x302);
+    }
+
+    #[test]
+    fn opcode_load_registers() {
+        let rom = [0xf2, 0x65];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x300;
+        vm.ram[0x300..0x303].copy_from_slice(&[0xa, 0xb, 0xc]);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.i_register, 0x303);
+        assert_eq!(vm.v_registers[0..3], [0xa, 0xb, 0xc]);
+    }
+
+    #[test]
+    fn opcode_store_and_load_flags() {
+        let rom = [0xf2, 0x75, 0xf1, 0x85];
+        let mut vm = Vm::for_platform(Platform::SuperChip, &rom, || 0x00);
+        vm.v_registers[0x0..0x03].copy_from_slice(&[0xa, 0xb, 0xc]);
+
+        let _ = vm.tick();
+        assert_eq!(vm.rpl_flags()[0..4], [0xa, 0xb, 0xc, 0x0]);
+
+        vm.v_registers = [0; 16];
+        vm.set_rpl_flags([0x1; 16]);
+        let _ = vm.tick();
+        assert_eq!(vm.v_registers[0..3], [0x1, 0x1, 0x0]);
+    }
+
+    #[test]
+    fn limits_flags_to_platform() {
+        let rom = [0xf7, 0x75, 0xf8, 0x75];
+        let mut vm = any_vm(&rom);
+        assert_eq!(vm.tick(), Err(VmError::InvalidOpcode(0xf775)));
+
+        let mut vm = Vm::for_platform(Platform::SuperChip, &rom, || 0x00);
+        vm.tick().unwrap();
+        assert_eq!(vm.tick(), Err(VmError::InvalidOpcode(0xf875)));
+
+        let mut vm = Vm::for_platform(Platform::XoChip, &[0xff, 0x85], || 0x00);
+        vm.tick().unwrap();
+    }
+
+    #[test]
+    fn opcode_call() {
+        let rom = [0x23, 0x00];
+        let mut vm = any_vm(&rom);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.stack.len(), 1);
+        assert_eq!(vm.pc, 0x300);
+    }
+
+    #[test]
+    fn opcode_ret() {
+        let rom = [0x00, 0xee];
+        let mut vm = any_vm(&rom);
+        vm.stack.push(0x300);
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert!(vm.stack.is_empty());
+        assert_eq!(vm.pc, 0x300);
+    }
+
+    #[test]
+    fn profiles_subroutines() {
+        // 200: call 206, call 206, jump 204; 206: V0 += 1, return
+        let rom = [0x22, 0x06, 0x22, 0x06, 0x12, 0x04, 0x70, 0x01, 0x00, 0xee];
+        let mut vm = any_vm(&rom);
+        vm.enable_profiling();
+        for _ in 0..8 {
+            vm.tick().unwrap();
+        }
+        vm.tick_timers();
+
+        let profiler = vm.profiler().unwrap();
+        assert_eq!(profiler.instructions(), 8);
+        assert_eq!(profiler.frames(), 1);
+        let functions: Vec<_> = profiler
+            .functions()
+            .map(|(addr, p)| (addr, p.calls, p.exclusive, p.inclusive))
+            .collect();
+        assert_eq!(functions, [(0x200, 0, 4, 8), (0x206, 2, 4, 4)]);
+    }
+
+    #[test]
+    fn opcode_bcd() {
+        let rom = [0xf0, 0x33];
+        let mut vm = any_vm(&rom);
+        vm.i_register = 0x300;
+        vm.v_registers[0x0] = 128;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.ram[0x300..0x303], [1, 2, 8]);
+    }
+
+    #[test]
+    fn opcode_load_digit() {
+        let rom = [0xf0, 0x29];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xab;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.i_register, 0xb * 5);
+    }
+
+    #[test]
+    fn loads_big_font_on_superchip() {
+        let vm = Vm::for_platform(Platform::SuperChip, &[], || 0x00);
+        assert_eq!(
+            vm.ram[0xa0..0xaa],
+            [0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c]
+        );
+
+        let vm = any_vm(&[]);
+        assert_eq!(vm.ram[0x50..0x5a], [0; 10]);
+    }
+
+    #[test]
+    fn points_digits_into_active_font() {
+        let mut vm = Vm::for_platform(Platform::SuperChip, &[0xf0, 0x29, 0xf0, 0x30], || 0x00);
+        vm.v_registers[0] = 0xb;
+        vm.set_font(Font::builtin(FontSet::Octo));
+
+        vm.tick().unwrap();
+        assert_eq!(vm.i_register, 0x37);
+        assert_eq!(vm.ram[0x37..0x3c], [0xe0, 0x90, 0xe0, 0x90, 0xe0]);
+        // what Octo's big digits did not overwrite of SUPER-CHIP's is gone
+        assert_eq!(vm.ram[0xf0..0x104], [0; 20]);
+
+        let big = [0x11; 160];
+        let font = Font::custom(&[0x22; 80], Some(&big)).unwrap();
+        let layout = FontLayout {
+            small: 0x100,
+            big: Some(0x000),
+        };
+        vm.set_font(font.with_layout(layout).unwrap());
+        vm.tick().unwrap();
+        assert_eq!(vm.i_register, 0xb * 10);
+        assert_eq!(vm.ram[0x6e], 0x11);
+        assert_eq!(vm.ram[0x100..0x150], [0x22; 80]);
+        assert_eq!(vm.ram[0xa0..0x100], [0; 96]);
+    }
+
+    #[test]
+    fn opcode_add_i() {
+        let rom = [0xf0, 0x1e];
+        let mut vm = any_vm(&rom);
+        vm.v_registers[0x0] = 0xab;
+        vm.i_register = 0x300;
+
+        let res = vm.tick();
+
+        assert!(res.is_ok());
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.i_register, 0x3ab);
+    }
+
+    #[test]
+    fn runs_chip8x_extensions() {
+        let rom = [
+            0x02, 0xa0, // cycle background
+            0x60, 0x10, // V0 = 0x10: zones 0 and 1
+            0x61, 0x00, // V1 = 0: top zone
+            0x62, 0x05, // V2 = yellow
+            0xb0, 0x20, // colour zones
+            0x63, 0x01, // V3 = 1
+            0xe3, 0xf2, // skip if key 1 on keypad 2
+            0xf2, 0xf8, // skipped: tone = V2
+            0xe3, 0xf5, // skip if not key 1 on keypad 2
+            0x00, 0xe0,
+        ];
+        let mut vm = Vm::for_platform(Platform::Chip8X, &rom, || 0x00);
+        vm.set_second_key(1, true).unwrap();
+        for _ in 0..8 {
+            vm.tick().unwrap();
+        }
+
+        let colors = vm.colors().unwrap();
+        assert_eq!(colors.background(), 1);
+        assert_eq!(colors.foreground(15, 3), 5);
+        assert_eq!(colors.foreground(16, 3), 1);
+        assert_eq!(vm.tone(), None);
+        assert_eq!(vm.pc, 0x312);
+
+        assert_eq!(any_vm(&[]).colors(), None);
+    }
+
+    #[test]
+    fn runs_superchip_extensions() {
+        let rom = [
+            0x00, 0xff, // 128x64
+            0xa2, 0x10, // I = sprite
+            0xd0, 0x00, // 16x16 sprite at 0, 0
+            0x00, 0xc2, // scroll down 2
+            0x00, 0xfb, // scroll right 4
+            0x60, 0x07, // V0 = 7
+            0xf0, 0x30, // big digit 7
+            0x00, 0xfd, // exit
+            0xff, 0xff, // sprite: 16 lit pixels in the first row
+        ];
+        let mut vm = Vm::for_platform(Platform::SuperChip, &rom, || 0x00);
+        for _ in 0..10 {
+            vm.tick().unwrap();
+        }
+
+        assert_eq!((vm.display.width(), vm.display.height()), (128, 64));
+        assert!(vm.display.get(4, 2) && vm.display.get(19, 2));
+        assert!(!vm.display.get(3, 2) && !vm.display.get(20, 2));
+        assert!(!vm.display.get(4, 0));
+        assert_eq!(vm.i_register, 0xa0 + 70);
+        assert!(vm.is_halted());
+        assert_eq!(vm.pc, 0x210);
+    }
+
+    #[test]
+    fn runs_megachip_extensions() {
+        let rom = [
+            0x00, 0x11, // MegaChip mode on
+            0x01, 0x00, 0x02, 0x1c, // I = 0x00021c
+            0x02, 0x01, // load one colour
+            0x03, 0x02, // sprites are 2x1
+            0x04, 0x01, //
+            0x09, 0x01, // collision colour 1
+            0xa2, 0x20, // I = sprite
+            0xd0, 0x00, // draw
+            0xd0, 0x00, // draw again: collides
+            0x00, 0xe0, // show the frame
+            0xa2, 0x22, // I = sound
+            0x06, 0x00, // play it in a loop
+            0x12, 0x1a, // loop
+            0xff, 0x11, 0x22, 0x33, // palette
+            0x01, 0x01, // sprite
+            0x1f, 0x40, 0x00, 0x00, 0x01, 0x00, 0x80, // sound
+        ];
+        let mut vm = Vm::for_platform(Platform::MegaChip, &rom, || 0x00);
+        for _ in 0..12 {
+            vm.tick().unwrap();
+        }
+
+        assert!(vm.is_mega_mode());
+        let mega = vm.megachip().unwrap();
+        let color = [0x11, 0x22, 0x33, 0xff];
+        assert_eq!(mega.frame()[0..3], [color, color, [0, 0, 0, 0xff]]);
+        assert_eq!(vm.v_registers[0xf], 1);
+        assert_eq!(
+            vm.take_sound_event(),
+            Some(SoundEvent::Play(DigitalSound {
+                sample_rate: 8000,
+                looping: true,
+                samples: vec![0x80],
+            }))
+        );
+        assert_eq!(vm.take_sound_event(), None);
+    }
+
+    #[test]
+    fn allocates_megachip_memory_on_demand() {
+        let rom = [
+            0x01, 0x01, 0x23, 0x45, // I = 0x012345
+            0x60, 0x07, // V0 = 7
+            0xf0, 0x55, // store V0 at I
+            0x01, 0xff, 0xff, 0xff, // I = 0xffffff
+            0xf1, 0x55, // store V0 and V1 at I
+            0xf1, 0x55, // again at the end of memory
+        ];
+        let mut vm = Vm::for_platform(Platform::MegaChip, &rom, || 0x00);
+        assert_eq!(vm.memory().len(), 0x10000);
+
+        for _ in 0..3 {
+            vm.tick().unwrap();
+        }
+        assert_eq!(vm.memory().len(), 0x20000);
+        assert_eq!(vm.memory()[0x12345], 7);
+
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+        assert_eq!(vm.memory().len(), 0x1010000);
+
+        vm.i_register = 0x1ffffff;
+        assert_eq!(vm.tick(), Err(VmError::InvalidAddress(0x2000000)));
+    }
+
+    #[test]
+    fn runs_xochip_extensions() {
+        let rom = [
+            0xf3, 0x01, // select both planes
+            0xa2, 0x1e, // I = sprite
+            0xd0, 0x01, // draw it at 0, 0 on both planes
+            0xf1, 0x01, // select the first plane
+            0x00, 0xe0, // clear it
+            0x30, 0x00, // V0 == 0: skips all of
+            0xf0, 0x00, 0x02, 0x1e, // I = 0x021e
+            0xf0, 0x00, 0x02, 0x20, // I = 0x0220
+            0x51, 0x33, // load V1 to V3
+            0x53, 0x12, // store V3 down to V1
+            0xf0, 0x02, // load the audio pattern
+            0xf1, 0x3a, // pitch = V1
+            0x12, 0x1c, // loop
+            0x80, 0xc0, // sprite of each plane
+            0x10, 0x20, 0x30,
+        ];
+        let mut vm = Vm::for_platform(Platform::XoChip, &rom, || 0x00);
+        for _ in 0..11 {
+            vm.tick().unwrap();
+        }
+
+        assert_eq!(
+            [
+                vm.plane_bits(0, 0),
+                vm.plane_bits(1, 0),
+                vm.plane_bits(2, 0)
+            ],
+            [2, 2, 0]
+        );
+        assert_eq!(vm.pc, 0x21c);
+        assert_eq!(vm.v_registers[1..4], [0x10, 0x20, 0x30]);
+        assert_eq!(vm.ram[0x220..0x223], [0x30, 0x20, 0x10]);
+
+        let xo = vm.xochip().unwrap();
+        assert_eq!(xo.selected_planes(), 1);
+        assert_eq!(xo.pattern().unwrap()[..4], [0x30, 0x20, 0x10, 0x00]);
+        assert_eq!(xo.pitch(), 0x10);
+    }
+}
@@ -0,0 +1,296 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::{Beeper, Display, Expression, ExpressionError, Keypad, RandomSource, Vm};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    /// A value, in hex if the flag is set.
+    Value(Expression, bool),
+}
+
+/// Stops the VM before an instruction when its condition is true, or logs a
+/// message instead if it is a tracepoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    condition: Expression,
+    log: Option<Vec<Segment>>,
+    ignore_count: u64,
+    hits: u64,
+}
+
+impl Breakpoint {
+    /// A breakpoint such as `pc == 0x2a4 && v3 == 0x10`.
+    pub fn new(condition: Expression) -> Self {
+        Self {
+            condition,
+            log: None,
+            ignore_count: 0,
+            hits: 0,
+        }
+    }
+
+    /// A breakpoint that logs `format` instead of stopping. The format is
+    /// text with expressions in braces, optionally in hex with `:x`, such as
+    /// `score: {mem[i]} at {pc:x}`. `{{` and `}}` are literal braces.
+    pub fn tracepoint(condition: Expression, format: &str) -> Result<Self, ExpressionError> {
+        Ok(Self {
+            log: Some(parse_format(format)?),
+            ..Self::new(condition)
+        })
+    }
+
+    /// Parses a condition, optionally followed by `=>` and the format of a
+    /// tracepoint: `pc == 0x300 => v0 is {v0}`.
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        match text.split_once("=>") {
+            Some((condition, format)) => {
+                let offset = condition.len() + 2;
+                Self::tracepoint(Expression::parse(condition)?, format.trim()).map_err(|err| {
+                    match err {
+                        ExpressionError::Unexpected(pos) => {
+                            let trimmed = format.len() - format.trim_start().len();
+                            ExpressionError::Unexpected(offset + trimmed + pos)
+                        }
+                        err => err,
+                    }
+                })
+            }
+            None => Ok(Self::new(Expression::parse(text)?)),
+        }
+    }
+
+    /// Ignores the first `count` times the condition is true.
+    pub fn with_ignore_count(self, count: u64) -> Self {
+        Self {
+            ignore_count: count,
+            ..self
+        }
+    }
+
+    pub fn condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    pub fn is_tracepoint(&self) -> bool {
+        self.log.is_some()
+    }
+
+    /// Times the condition was true, including the ignored ones.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Checks the condition, returning the message of a tracepoint or `None`
+    /// for a breakpoint if it triggers.
+    fn check<R, D, K, B>(&mut self, vm: &Vm<R, D, K, B>) -> Option<Option<String>>
+    where
+        R: RandomSource,
+        D: Display,
+        K: Keypad,
+        B: Beeper,
+    {
+        if self.condition.evaluate(vm) == 0 {
+            return None;
+        }
+        self.hits += 1;
+        if self.hits <= self.ignore_count {
+            return None;
+        }
+
+        Some(self.log.as_ref().map(|segments| {
+            let mut message = String::new();
+            for segment in segments {
+                let _ = match segment {
+                    Segment::Text(text) => write!(message, "{}", text),
+                    Segment::Value(value, true) => write!(message, "{:#x}", value.evaluate(vm)),
+                    Segment::Value(value, false) => write!(message, "{}", value.evaluate(vm)),
+                };
+            }
+            message
+        }))
+    }
+}
+
+fn parse_format(format: &str) -> Result<Vec<Segment>, ExpressionError> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        let pos = format.len() - rest.len();
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(c);
+            rest = &rest[2..];
+        } else if c == '{' {
+            let end = rest.find('}').ok_or(ExpressionError::UnexpectedEnd)?;
+            let (value, hex) = match rest[1..end].strip_suffix(":x") {
+                Some(value) => (value, true),
+                None => (&rest[1..end], false),
+            };
+            let value = Expression::parse(value).map_err(|err| match err {
+                ExpressionError::Unexpected(offset) => {
+                    ExpressionError::Unexpected(pos + 1 + offset)
+                }
+                ExpressionError::UnexpectedEnd => ExpressionError::Unexpected(pos + end),
+            })?;
+            if !text.is_empty() {
+                segments.push(Segment::Text(core::mem::take(&mut text)));
+            }
+            segments.push(Segment::Value(value, hex));
+            rest = &rest[end + 1..];
+        } else if c == '}' {
+            return Err(ExpressionError::Unexpected(pos));
+        } else {
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// The breakpoints of a VM, by id.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Breakpoints {
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    next_id: usize,
+    /// Messages of the tracepoints, until they are taken.
+    log: Vec<String>,
+    /// The breakpoint that stopped the VM.
+    hit: Option<usize>,
+    /// Cycle where the VM stopped, to run the instruction when resumed.
+    stopped_at: Option<u64>,
+}
+
+impl Breakpoints {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty() && self.hit.is_none()
+    }
+
+    pub(crate) fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.insert(id, breakpoint);
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) -> Option<Breakpoint> {
+        self.breakpoints.remove(&id)
+    }
+
+    pub(crate) fn get(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(&id)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints
+            .iter()
+            .map(|(id, breakpoint)| (*id, breakpoint))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub(crate) fn hit(&self) -> Option<usize> {
+        self.hit
+    }
+
+    pub(crate) fn take_log(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.log)
+    }
+
+    /// Checks the breakpoints before the next instruction, and whether the
+    /// VM has to stop. The instruction the VM stopped at runs on the next
+    /// check.
+    pub(crate) fn check<R, D, K, B>(&mut self, vm: &Vm<R, D, K, B>) -> bool
+    where
+        R: RandomSource,
+        D: Display,
+        K: Keypad,
+        B: Beeper,
+    {
+        self.hit = None;
+        if self.stopped_at.take() == Some(vm.cycles) {
+            return false;
+        }
+
+        for (id, breakpoint) in self.breakpoints.iter_mut() {
+            match breakpoint.check(vm) {
+                Some(Some(message)) => self.log.push(message),
+                Some(None) => {
+                    self.hit.get_or_insert(*id);
+                }
+                None => {}
+            }
+        }
+        if self.hit.is_some() {
+            self.stopped_at = Some(vm.cycles);
+        }
+        self.hit.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn vm() -> Vm<fn() -> u8> {
+        let mut vm: Vm<fn() -> u8> = Vm::new(&[0x12, 0x00], || 0);
+        vm.v_registers[3] = 0x10;
+        vm.i_register = 0x3f0;
+        vm
+    }
+
+    #[test]
+    fn counts_hits_after_ignored_ones() {
+        let vm = vm();
+        let mut breakpoint = Breakpoint::parse("v3 == 0x10")
+            .unwrap()
+            .with_ignore_count(1);
+
+        assert_eq!(breakpoint.check(&vm), None);
+        assert_eq!(breakpoint.check(&vm), Some(None));
+        assert_eq!(breakpoint.hits(), 2);
+        assert!(!breakpoint.is_tracepoint());
+    }
+
+    #[test]
+    fn formats_tracepoint_messages() {
+        let vm = vm();
+        let mut tracepoint = Breakpoint::parse("pc == 0x200 => {{v3}} = {v3}, I = {i:x}").unwrap();
+        assert_eq!(
+            tracepoint.check(&vm),
+            Some(Some("{v3} = 16, I = 0x3f0".into()))
+        );
+
+        assert_eq!(
+            Breakpoint::parse("pc == 0x200 => v3 = {v3 +}"),
+            Err(ExpressionError::Unexpected(25))
+        );
+        assert_eq!(
+            Breakpoint::parse("pc == 0x200 => {v3"),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn stops_once_per_instruction() {
+        let vm = vm();
+        let mut breakpoints = Breakpoints::default();
+        let id = breakpoints.add(Breakpoint::parse("pc == 0x200").unwrap());
+        breakpoints.add(Breakpoint::parse("1 => at {pc:x}").unwrap());
+
+        assert!(breakpoints.check(&vm));
+        assert_eq!(breakpoints.hit(), Some(id));
+        assert!(!breakpoints.check(&vm));
+        assert_eq!(breakpoints.hit(), None);
+        assert_eq!(breakpoints.take_log(), vec![String::from("at 0x200")]);
+    }
+}
@@ -0,0 +1,403 @@
+use super::{Result, VmError};
+
+/// The RCA CDP1802 CPU of the COSMAC VIP, which ran the original CHIP-8
+/// interpreter. Programs for the VIP call 1802 machine code with `0NNN`.
+///
+/// The CPU works directly on the memory passed to `step`. Addresses wrap
+/// around the end of memory, as the VIP does not decode the upper address
+/// lines of its RAM.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cdp1802 {
+    r: [u16; 16],
+    d: u8,
+    df: bool,
+    p: u8,
+    x: u8,
+    t: u8,
+    ie: bool,
+    q: bool,
+    ef: [bool; 4],
+    outputs: [u8; 8],
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        Self {
+            ie: true,
+            ..Self::default()
+        }
+    }
+
+    /// Scratchpad register `n`.
+    pub fn r(&self, n: u8) -> u16 {
+        self.r[n as usize & 0xf]
+    }
+
+    pub fn set_r(&mut self, n: u8, value: u16) {
+        self.r[n as usize & 0xf] = value;
+    }
+
+    pub fn d(&self) -> u8 {
+        self.d
+    }
+
+    pub fn df(&self) -> bool {
+        self.df
+    }
+
+    /// Number of the register used as program counter.
+    pub fn p(&self) -> u8 {
+        self.p
+    }
+
+    pub fn set_p(&mut self, n: u8) {
+        self.p = n & 0xf;
+    }
+
+    /// Number of the register used as data pointer.
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+
+    pub fn set_x(&mut self, n: u8) {
+        self.x = n & 0xf;
+    }
+
+    /// The Q output, which drives the buzzer on the VIP.
+    pub fn q(&self) -> bool {
+        self.q
+    }
+
+    /// Sets the external flag input `EF1`..`EF4` (`n` is 1 to 4).
+    pub fn set_ef(&mut self, n: u8, value: bool) {
+        if let Some(ef) = self.ef.get_mut((n as usize).wrapping_sub(1)) {
+            *ef = value;
+        }
+    }
+
+    /// Last byte written to output port `n` (1 to 7) by `OUT n`.
+    pub fn output(&self, n: u8) -> u8 {
+        self.outputs[n as usize & 0x7]
+    }
+
+    /// Runs one instruction.
+    pub fn step(&mut self, ram: &mut [u8]) -> Result<()> {
+        let opcode = self.fetch(ram);
+        let (hi, n) = (opcode >> 4, opcode & 0xf);
+
+        match hi {
+            // IDL waits for an interrupt or DMA, which the VIP display
+            // would provide; here it just continues
+            0x0 if n == 0 => {}
+            0x0 => self.d = self.load(ram, self.r(n)),
+            0x1 => self.r[n as usize] = self.r(n).wrapping_add(1),
+            0x2 => self.r[n as usize] = self.r(n).wrapping_sub(1),
+            0x3 => {
+                let taken = n == 0 || (n != 8 && self.condition(n & 0x7) != (n >= 8));
+                self.short_branch(ram, taken);
+            }
+            0x4 => {
+                self.d = self.load(ram, self.r(n));
+                self.r[n as usize] = self.r(n).wrapping_add(1);
+            }
+            0x5 => self.store(ram, self.r(n), self.d),
+            0x6 => self.exec_io(ram, n)?,
+            0x7 => self.exec_misc(ram, n),
+            0x8 => self.d = self.r(n) as u8,
+            0x9 => self.d = (self.r(n) >> 8) as u8,
+            0xa => self.r[n as usize] = self.r(n) & 0xff00 | self.d as u16,
+            0xb => self.r[n as usize] = self.r(n) & 0x00ff | (self.d as u16) << 8,
+            0xc => self.exec_long(ram, n),
+            0xd => self.p = n,
+            0xe => self.x = n,
+            _ => self.exec_alu(ram, n),
+        }
+
+        Ok(())
+    }
+
+    fn fetch(&mut self, ram: &[u8]) -> u8 {
+        let pc = self.r(self.p);
+        self.r[self.p as usize] = pc.wrapping_add(1);
+        self.load(ram, pc)
+    }
+
+    fn load(&self, ram: &[u8], addr: u16) -> u8 {
+        ram[addr as usize % ram.len()]
+    }
+
+    fn store(&self, ram: &mut [u8], addr: u16, value: u8) {
+        ram[addr as usize % ram.len()] = value;
+    }
+
+    /// Conditions of the branches, by the low three bits of the opcode.
+    fn condition(&self, n: u8) -> bool {
+        match n {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            _ => self.ef[n as usize - 4],
+        }
+    }
+
+    fn short_branch(&mut self, ram: &[u8], taken: bool) {
+        let pc = self.r(self.p);
+        if taken {
+            self.r[self.p as usize] = pc & 0xff00 | self.load(ram, pc) as u16;
+        } else {
+            self.r[self.p as usize] = pc.wrapping_add(1);
+        }
+    }
+
+    fn exec_long(&mut self, ram: &[u8], n: u8) {
+        let pc = self.r(self.p);
+        let skip = |cpu: &mut Self, taken: bool| {
+            if taken {
+                cpu.r[cpu.p as usize] = pc.wrapping_add(2);
+            }
+        };
+
+        match n {
+            // NOP
+            0x4 => {}
+            // LSNQ, LSNZ, LSNF
+            0x5 => skip(self, !self.q),
+            0x6 => skip(self, self.d != 0),
+            0x7 => skip(self, !self.df),
+            // LSKP
+            0x8 => skip(self, true),
+            // LSIE, LSQ, LSZ, LSDF
+            0xc => skip(self, self.ie),
+            0xd => skip(self, self.q),
+            0xe => skip(self, self.d == 0),
+            0xf => skip(self, self.df),
+            // LBR, LBQ, LBZ, LBDF and their negations
+            _ => {
+                let condition = match n & 0x3 {
+                    0 => true,
+                    1 => self.q,
+                    2 => self.d == 0,
+                    _ => self.df,
+                };
+                if condition != (n >= 8) {
+                    let hi = self.load(ram, pc);
+                    let lo = self.load(ram, pc.wrapping_add(1));
+                    self.r[self.p as usize] = u16::from_be_bytes([hi, lo]);
+                } else {
+                    self.r[self.p as usize] = pc.wrapping_add(2);
+                }
+            }
+        }
+    }
+
+    fn exec_io(&mut self, ram: &mut [u8], n: u8) -> Result<()> {
+        let rx = self.r(self.x);
+        match n {
+            // IRX
+            0x0 => self.r[self.x as usize] = rx.wrapping_add(1),
+            // OUT 1..7
+            0x1..=0x7 => {
+                self.outputs[n as usize] = self.load(ram, rx);
+                self.r[self.x as usize] = rx.wrapping_add(1);
+            }
+            // INP 1..7, with nothing connected to the bus
+            0x9..=0xf => {
+                self.d = 0;
+                self.store(ram, rx, 0);
+            }
+            _ => return Err(VmError::InvalidMachineCode(0x60 | n)),
+        }
+
+        Ok(())
+    }
+
+    fn exec_misc(&mut self, ram: &mut [u8], n: u8) {
+        let rx = self.r(self.x);
+        match n {
+            // RET, DIS
+            0x0 | 0x1 => {
+                let value = self.load(ram, rx);
+                self.r[self.x as usize] = rx.wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xf;
+                self.ie = n == 0;
+            }
+            // LDXA
+            0x2 => {
+                self.d = self.load(ram, rx);
+                self.r[self.x as usize] = rx.wrapping_add(1);
+            }
+            // STXD
+            0x3 => {
+                self.store(ram, rx, self.d);
+                self.r[self.x as usize] = rx.wrapping_sub(1);
+            }
+            // ADC, SDB, SMB
+            0x4 => self.add(self.load(ram, rx), self.df),
+            0x5 => self.subtract(self.load(ram, rx), self.d, self.df),
+            0x7 => self.subtract(self.d, self.load(ram, rx), self.df),
+            // SHRC
+            0x6 => {
+                let carry = self.d & 1 != 0;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            // SAV
+            0x8 => self.store(ram, rx, self.t),
+            // MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                self.store(ram, self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            // REQ, SEQ
+            0xa => self.q = false,
+            0xb => self.q = true,
+            // ADCI, SDBI, SMBI
+            0xc => {
+                let value = self.fetch(ram);
+                self.add(value, self.df);
+            }
+            0xd => {
+                let value = self.fetch(ram);
+                self.subtract(value, self.d, self.df);
+            }
+            0xf => {
+                let value = self.fetch(ram);
+                self.subtract(self.d, value, self.df);
+            }
+            // SHLC
+            _ => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+        }
+    }
+
+    fn exec_alu(&mut self, ram: &mut [u8], n: u8) {
+        // the immediate forms (F8..FF) take their operand after the opcode
+        let operand = |cpu: &mut Self| {
+            if n >= 8 {
+                cpu.fetch(ram)
+            } else {
+                cpu.load(ram, cpu.r(cpu.x))
+            }
+        };
+
+        match n & 0x7 {
+            // LDX, LDI
+            0x0 => self.d = operand(self),
+            // OR, ORI
+            0x1 => self.d |= operand(self),
+            // AND, ANI
+            0x2 => self.d &= operand(self),
+            // XOR, XRI
+            0x3 => self.d ^= operand(self),
+            // ADD, ADI
+            0x4 => {
+                let value = operand(self);
+                self.add(value, false);
+            }
+            // SD, SDI
+            0x5 => {
+                let value = operand(self);
+                self.subtract(value, self.d, true);
+            }
+            // SHR, SHL
+            0x6 if n < 8 => {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            }
+            0x6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            // SM, SMI
+            _ => {
+                let value = operand(self);
+                self.subtract(self.d, value, true);
+            }
+        }
+    }
+
+    fn add(&mut self, value: u8, carry: bool) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xff;
+    }
+
+    /// `a - b`, borrowing when `no_borrow` is false. DF is set when there
+    /// is no borrow.
+    fn subtract(&mut self, a: u8, b: u8, no_borrow: bool) {
+        let difference = a as i16 - b as i16 - !no_borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Runs `code` from address 0 until it reaches its end.
+    fn run(code: &[u8]) -> (Cdp1802, alloc::vec::Vec<u8>) {
+        let mut ram = vec![0; 0x100];
+        ram[..code.len()].copy_from_slice(code);
+        let mut cpu = Cdp1802::new();
+        while (cpu.r(0) as usize) < code.len() {
+            cpu.step(&mut ram).unwrap();
+        }
+        (cpu, ram)
+    }
+
+    #[test]
+    fn adds_and_subtracts_with_carry() {
+        // LDI F0, ADI 20
+        let (cpu, _) = run(&[0xf8, 0xf0, 0xfc, 0x20]);
+        assert_eq!((cpu.d(), cpu.df()), (0x10, true));
+
+        // LDI 10, SMI 20
+        let (cpu, _) = run(&[0xf8, 0x10, 0xff, 0x20]);
+        assert_eq!((cpu.d(), cpu.df()), (0xf0, false));
+
+        // LDI 10, SMI 20, SMBI 00: borrows from the previous subtraction
+        let (cpu, _) = run(&[0xf8, 0x10, 0xff, 0x20, 0x7f, 0x00]);
+        assert_eq!((cpu.d(), cpu.df()), (0xef, true));
+    }
+
+    #[test]
+    fn loads_and_stores_through_registers() {
+        // LDI 80, PLO 1, LDI 42, STR 1, LDI 00, LDN 1
+        let (cpu, ram) = run(&[0xf8, 0x80, 0xa1, 0xf8, 0x42, 0x51, 0xf8, 0x00, 0x01]);
+        assert_eq!(ram[0x80], 0x42);
+        assert_eq!(cpu.d(), 0x42);
+    }
+
+    #[test]
+    fn branches() {
+        // LDI 00, BZ 06, LDI 55, SEQ
+        let (cpu, _) = run(&[0xf8, 0x00, 0x32, 0x06, 0xf8, 0x55, 0x7b]);
+        assert_eq!(cpu.d(), 0x00);
+        assert!(cpu.q());
+
+        // LBR 0005, SEQ, NOP
+        let (cpu, _) = run(&[0xc0, 0x00, 0x05, 0x7b, 0xc4, 0xc4]);
+        assert!(!cpu.q());
+    }
+
+    #[test]
+    fn mark_and_ret_save_and_restore_x_and_p() {
+        // LDI 80, PLO 2, SEX 2, MARK
+        let (cpu, ram) = run(&[0xf8, 0x80, 0xa2, 0xe2, 0x79]);
+        assert_eq!(ram[0x80], 0x20);
+        assert_eq!((cpu.x(), cpu.p(), cpu.r(2)), (0, 0, 0x7f));
+
+        // ... then SEX 2, IRX, RET
+        let (cpu, _) = run(&[0xf8, 0x80, 0xa2, 0xe2, 0x79, 0xe2, 0x60, 0x70]);
+        assert_eq!((cpu.x(), cpu.p(), cpu.r(2)), (2, 0, 0x81));
+    }
+}
@@ -0,0 +1,102 @@
+use super::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Number of background colours the VP-590 colour board cycles through.
+pub const BACKGROUND_COLORS: u8 = 4;
+
+/// Width in pixels of a colour zone.
+const ZONE_WIDTH: usize = 8;
+/// Height in pixels of the zones coloured by BXY0.
+const ZONE_HEIGHT: usize = 4;
+const COLUMNS: usize = DISPLAY_WIDTH / ZONE_WIDTH;
+
+/// Colours of the CHIP-8X display, as set by the VP-590 colour board.
+///
+/// Lit pixels take the foreground colour of their zone: 0 black, 1 red,
+/// 2 blue, 3 violet, 4 green, 5 yellow, 6 aqua or 7 white. Unlit pixels show
+/// the background colour: 0 blue, 1 black, 2 green or 3 red.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorAttributes {
+    background: u8,
+    /// Foreground colour of every 8x1 area of the display.
+    zones: [[u8; COLUMNS]; DISPLAY_HEIGHT],
+}
+
+impl Default for ColorAttributes {
+    fn default() -> Self {
+        Self {
+            background: 0,
+            zones: [[1; COLUMNS]; DISPLAY_HEIGHT],
+        }
+    }
+}
+
+impl ColorAttributes {
+    pub fn background(&self) -> u8 {
+        self.background
+    }
+
+    /// Foreground colour of the pixel at `x`, `y`.
+    pub fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.zones[y % DISPLAY_HEIGHT][x / ZONE_WIDTH % COLUMNS]
+    }
+
+    /// 02A0: switches to the next background colour.
+    pub(crate) fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLORS;
+    }
+
+    /// BXY0: colours the 8x4 zones from `left` to `left + extra_columns`
+    /// and from `top` to `top + extra_rows` (all in zones).
+    pub(crate) fn set_zones(
+        &mut self,
+        (left, extra_columns): (u8, u8),
+        (top, extra_rows): (u8, u8),
+        color: u8,
+    ) {
+        let rows = top as usize * ZONE_HEIGHT..(top + extra_rows + 1) as usize * ZONE_HEIGHT;
+        let columns = left as usize..=(left + extra_columns) as usize;
+        for row in rows {
+            for column in columns.clone() {
+                self.zones[row % DISPLAY_HEIGHT][column % COLUMNS] = color & 0x7;
+            }
+        }
+    }
+
+    /// BXYN: colours `rows` rows from `y`, in the 8 pixels wide column that
+    /// holds `x`.
+    pub(crate) fn set_rows(&mut self, x: u8, y: u8, rows: u8, color: u8) {
+        let column = x as usize / ZONE_WIDTH % COLUMNS;
+        for row in y as usize..y as usize + rows as usize {
+            self.zones[row % DISPLAY_HEIGHT][column] = color & 0x7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_background() {
+        let mut colors = ColorAttributes::default();
+        for expected in [1, 2, 3, 0] {
+            colors.cycle_background();
+            assert_eq!(colors.background(), expected);
+        }
+    }
+
+    #[test]
+    fn colours_zones_and_rows() {
+        let mut colors = ColorAttributes::default();
+        colors.set_zones((1, 1), (2, 0), 4);
+        assert_eq!(colors.foreground(8, 8), 4);
+        assert_eq!(colors.foreground(23, 11), 4);
+        assert_eq!(colors.foreground(24, 11), 1);
+        assert_eq!(colors.foreground(8, 12), 1);
+
+        colors.set_rows(60, 30, 4, 7);
+        assert_eq!(colors.foreground(56, 31), 7);
+        assert_eq!(colors.foreground(56, 0), 7);
+        assert_eq!(colors.foreground(55, 31), 1);
+    }
+}
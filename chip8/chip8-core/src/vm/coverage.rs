@@ -0,0 +1,153 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+use super::{Opcode, Platform};
+
+/// Addresses that are counted. Memory past this, which only MegaChip has,
+/// is left out to keep the counters small.
+pub const COVERAGE_LEN: usize = 0x10000;
+
+/// How many times each address was executed, read and written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    executions: Vec<u32>,
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl Coverage {
+    /// Counters for a memory of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        let len = len.min(COVERAGE_LEN);
+        Self {
+            executions: vec![0; len],
+            reads: vec![0; len],
+            writes: vec![0; len],
+        }
+    }
+
+    /// Times an instruction started at each address.
+    pub fn executions(&self) -> &[u32] {
+        &self.executions
+    }
+
+    pub fn reads(&self) -> &[u32] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[u32] {
+        &self.writes
+    }
+
+    pub fn clear(&mut self) {
+        self.executions.fill(0);
+        self.reads.fill(0);
+        self.writes.fill(0);
+    }
+
+    /// An lcov tracefile for `source`, with each instruction address in
+    /// `range` standing for a line.
+    pub fn to_lcov(&self, source: &str, range: Range<u32>) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "TN:\nSF:{}", source);
+        let (mut found, mut hit) = (0, 0);
+        for addr in range.step_by(2) {
+            let count = count_at(&self.executions, addr);
+            let _ = writeln!(report, "DA:{},{}", addr, count);
+            found += 1;
+            hit += (count > 0) as usize;
+        }
+        let _ = writeln!(report, "LF:{}\nLH:{}\nend_of_record", found, hit);
+        report
+    }
+
+    /// A disassembly of `memory` in `range`, with the executions, reads and
+    /// writes of every instruction.
+    pub fn annotate(&self, memory: &[u8], platform: Platform, range: Range<u32>) -> String {
+        let mut report = String::from("    exec    read   write\n");
+        for addr in range.step_by(2) {
+            let bytes = memory.get(addr as usize..addr as usize + 2);
+            let raw = bytes.map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+            let reads = count_at(&self.reads, addr) + count_at(&self.reads, addr + 1);
+            let writes = count_at(&self.writes, addr) + count_at(&self.writes, addr + 1);
+
+            let _ = write!(
+                report,
+                "{:>8} {:>7} {:>7}  {:04X}: {:04X}",
+                count_at(&self.executions, addr),
+                reads,
+                writes,
+                addr,
+                raw
+            );
+            let _ = match Opcode::decode(raw, platform) {
+                Ok(opcode) => writeln!(report, "  {:?}", opcode),
+                Err(_) => writeln!(report),
+            };
+        }
+        report
+    }
+
+    pub(crate) fn record_execution(&mut self, addr: u32) {
+        increment(&mut self.executions, addr);
+    }
+
+    pub(crate) fn record_reads(&mut self, addr: u32, len: usize) {
+        for offset in 0..len as u32 {
+            increment(&mut self.reads, addr + offset);
+        }
+    }
+
+    pub(crate) fn record_write(&mut self, addr: u32) {
+        increment(&mut self.writes, addr);
+    }
+}
+
+fn count_at(counts: &[u32], addr: u32) -> u32 {
+    counts.get(addr as usize).copied().unwrap_or_default()
+}
+
+fn increment(counts: &mut [u32], addr: u32) {
+    if let Some(count) = counts.get_mut(addr as usize) {
+        *count = count.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_lcov_report() {
+        let mut coverage = Coverage::new(0x1000);
+        coverage.record_execution(0x200);
+        coverage.record_execution(0x200);
+
+        assert_eq!(
+            coverage.to_lcov("test.ch8", 0x200..0x204),
+            "TN:\nSF:test.ch8\nDA:512,2\nDA:514,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn annotates_disassembly() {
+        let mut coverage = Coverage::new(0x1000);
+        coverage.record_execution(0x200);
+        coverage.record_reads(0x202, 2);
+        coverage.record_write(0x203);
+        let mut memory = vec![0; 0x204];
+        memory[0x200..].copy_from_slice(&[0x00, 0xe0, 0xff, 0xff]);
+
+        assert_eq!(
+            coverage.annotate(&memory, Platform::Chip8, 0x200..0x204),
+            "    exec    read   write\n       \
+             1       0       0  0200: 00E0  ClearScreen\n       \
+             0       2       1  0202: FFFF\n"
+        );
+        coverage.clear();
+        assert!(coverage.executions().iter().all(|count| *count == 0));
+    }
+}
@@ -1,12 +1,14 @@
-use std::error;
-use std::fmt;
+use core::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum VmError {
-    InvalidAddress(u16),
+    InvalidAddress(u32),
     InvalidOpcode(u16),
     InvalidKey(u8),
     EmptyStack,
+    InvalidMachineCode(u8),
+    /// A machine code subroutine at this address did not return.
+    MachineCodeTimeout(u16),
 }
 
 impl fmt::Display for VmError {
@@ -16,7 +18,15 @@ impl fmt::Display for VmError {
             Self::InvalidOpcode(opcode) => write!(f, "Invalid opcode: {:#06x}", opcode),
             Self::InvalidKey(id) => write!(f, "Invalid key: {:#04x}", id),
             Self::EmptyStack => write!(f, "Stack is empty"),
+            Self::InvalidMachineCode(opcode) => {
+                write!(f, "Invalid 1802 machine code: {:#04x}", opcode)
+            }
+            Self::MachineCodeTimeout(addr) => {
+                write!(f, "Machine code subroutine at {:#06x} did not return", addr)
+            }
         }
     }
 }
-impl error::Error for VmError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VmError {}
@@ -0,0 +1,384 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::convert::TryFrom;
+use core::fmt;
+
+use super::{Beeper, Display, Keypad, RandomSource, Vm};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// Unexpected character at this byte offset.
+    Unexpected(usize),
+    UnexpectedEnd,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unexpected(offset) => write!(f, "Unexpected character at {}", offset),
+            Self::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExpressionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    V(u8),
+    I,
+    Pc,
+    Delay,
+    Sound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Binary operators from the lowest precedence to the highest, as in Rust:
+/// bitwise operators bind tighter than comparisons.
+const PRECEDENCE: [&[(&str, BinaryOp)]; 9] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+    ],
+];
+
+/// Every operator, the longest first so `<<` is not read as `<`.
+const OPERATORS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/",
+    "%",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(i64),
+    Variable(Variable),
+    Memory(Box<Node>),
+    Key(Box<Node>),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+/// An expression over the state of a VM, such as `v3 == 0x10 && i > 0x300`
+/// or `mem[0x3f0] != 0`.
+///
+/// It can use numbers (decimal, `0x` hex or `0b` binary), the registers
+/// `v0` to `vf`, `i` and `pc`, the timers `dt` and `st`, bytes of memory
+/// (`mem[addr]`), keys (`key[n]`, 1 if pressed), parentheses, `!`, unary
+/// `-` and the binary operators of Rust. Comparisons and logical operators
+/// give 1 or 0; division by 0 gives 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser { text, pos: 0 };
+        let root = parser.expression(0)?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(ExpressionError::Unexpected(parser.pos));
+        }
+        Ok(Self {
+            source: text.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn evaluate<R, D, K, B>(&self, vm: &Vm<R, D, K, B>) -> i64
+    where
+        R: RandomSource,
+        D: Display,
+        K: Keypad,
+        B: Beeper,
+    {
+        evaluate(&self.root, vm)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn evaluate<R, D, K, B>(node: &Node, vm: &Vm<R, D, K, B>) -> i64
+where
+    R: RandomSource,
+    D: Display,
+    K: Keypad,
+    B: Beeper,
+{
+    match node {
+        Node::Number(value) => *value,
+        Node::Variable(Variable::V(n)) => vm.v_registers[*n as usize] as i64,
+        Node::Variable(Variable::I) => vm.i_register as i64,
+        Node::Variable(Variable::Pc) => vm.pc as i64,
+        Node::Variable(Variable::Delay) => vm.delay as i64,
+        Node::Variable(Variable::Sound) => vm.sound as i64,
+        Node::Memory(addr) => usize::try_from(evaluate(addr, vm))
+            .ok()
+            .and_then(|addr| vm.ram.get(addr))
+            .map_or(0, |value| *value as i64),
+        Node::Key(key) => usize::try_from(evaluate(key, vm))
+            .ok()
+            .and_then(|key| vm.keys.get(key))
+            .map_or(0, |pressed| *pressed as i64),
+        Node::Not(value) => (evaluate(value, vm) == 0) as i64,
+        Node::Negate(value) => evaluate(value, vm).wrapping_neg(),
+        Node::Binary(BinaryOp::Or, a, b) => (evaluate(a, vm) != 0 || evaluate(b, vm) != 0) as i64,
+        Node::Binary(BinaryOp::And, a, b) => (evaluate(a, vm) != 0 && evaluate(b, vm) != 0) as i64,
+        Node::Binary(op, a, b) => {
+            let (a, b) = (evaluate(a, vm), evaluate(b, vm));
+            let shift = |f: fn(i64, u32) -> Option<i64>| {
+                u32::try_from(b).ok().and_then(|b| f(a, b)).unwrap_or(0)
+            };
+            match op {
+                BinaryOp::Eq => (a == b) as i64,
+                BinaryOp::Ne => (a != b) as i64,
+                BinaryOp::Lt => (a < b) as i64,
+                BinaryOp::Le => (a <= b) as i64,
+                BinaryOp::Gt => (a > b) as i64,
+                BinaryOp::Ge => (a >= b) as i64,
+                BinaryOp::BitOr => a | b,
+                BinaryOp::BitXor => a ^ b,
+                BinaryOp::BitAnd => a & b,
+                BinaryOp::Shl => shift(i64::checked_shl),
+                BinaryOp::Shr => shift(i64::checked_shr),
+                BinaryOp::Add => a.wrapping_add(b),
+                BinaryOp::Sub => a.wrapping_sub(b),
+                BinaryOp::Mul => a.wrapping_mul(b),
+                BinaryOp::Div => a.checked_div(b).unwrap_or(0),
+                BinaryOp::Rem => a.checked_rem(b).unwrap_or(0),
+                BinaryOp::Or | BinaryOp::And => unreachable!(),
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn rest(&mut self) -> &str {
+        self.skip_whitespace();
+        &self.text[self.pos..]
+    }
+
+    fn error(&self) -> ExpressionError {
+        if self.pos < self.text.len() {
+            ExpressionError::Unexpected(self.pos)
+        } else {
+            ExpressionError::UnexpectedEnd
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ExpressionError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Binary operators of `level` and above.
+    fn expression(&mut self, level: usize) -> Result<Node, ExpressionError> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+
+        let mut node = self.expression(level + 1)?;
+        loop {
+            let rest = self.rest();
+            let found = OPERATORS.iter().find(|op| rest.starts_with(**op));
+            let Some(&(token, op)) =
+                found.and_then(|found| operators.iter().find(|(token, _)| token == found))
+            else {
+                return Ok(node);
+            };
+            self.pos += token.len();
+            let rhs = self.expression(level + 1)?;
+            node = Node::Binary(op, Box::new(node), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.eat("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else if self.eat("-") {
+            Ok(Node::Negate(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        if self.eat("(") {
+            let node = self.expression(0)?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+
+        self.skip_whitespace();
+        let start = self.pos;
+        let word_len = self.text[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(self.text.len() - start);
+        let word = &self.text[start..start + word_len];
+        if word.is_empty() {
+            return Err(self.error());
+        }
+        self.pos += word_len;
+
+        let error = ExpressionError::Unexpected(start);
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_number(word).map(Node::Number).ok_or(error);
+        }
+
+        let word = word.to_ascii_lowercase();
+        let variable = match word.as_str() {
+            "i" => Variable::I,
+            "pc" => Variable::Pc,
+            "dt" => Variable::Delay,
+            "st" => Variable::Sound,
+            "mem" | "key" => {
+                self.expect("[")?;
+                let index = Box::new(self.expression(0)?);
+                self.expect("]")?;
+                return Ok(match word.as_str() {
+                    "mem" => Node::Memory(index),
+                    _ => Node::Key(index),
+                });
+            }
+            _ => match word.strip_prefix('v') {
+                Some(n) if n.len() == 1 => {
+                    Variable::V(u8::from_str_radix(n, 16).map_err(|_| error)?)
+                }
+                _ => return Err(error),
+            },
+        };
+        Ok(Node::Variable(variable))
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = text.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        (binary, 2)
+    } else {
+        (text.as_str(), 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm() -> Vm<fn() -> u8> {
+        let mut vm: Vm<fn() -> u8> = Vm::new(&[0x12, 0x00], || 0);
+        vm.v_registers[3] = 0x10;
+        vm.i_register = 0x301;
+        vm.ram[0x3f0] = 7;
+        vm.keys[0xa] = true;
+        vm
+    }
+
+    fn eval(text: &str) -> i64 {
+        Expression::parse(text).unwrap().evaluate(&vm())
+    }
+
+    #[test]
+    fn evaluates_vm_state() {
+        assert_eq!(eval("v3 == 0x10 && i > 0x300"), 1);
+        assert_eq!(eval("mem[0x3f0] != 0"), 1);
+        assert_eq!(eval("mem[0x3e0 + 16] * 2"), 14);
+        assert_eq!(eval("key[0xA] && !key[1]"), 1);
+        assert_eq!(eval("pc"), 0x200);
+        assert_eq!(eval("V3 & 0b11000 == 0x10"), 1);
+        assert_eq!(eval("1 + 2 * 3 - -1 << 1"), 16);
+        assert_eq!(eval("(dt | st) == 0 || mem[0x10000]"), 1);
+        assert_eq!(eval("v3 / 0 + 7 % 4"), 3);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(
+            Expression::parse("v3 = 1"),
+            Err(ExpressionError::Unexpected(3))
+        );
+        assert_eq!(
+            Expression::parse("vg > 1"),
+            Err(ExpressionError::Unexpected(0))
+        );
+        assert_eq!(
+            Expression::parse("mem[i"),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Expression::parse("(1 + 2))"),
+            Err(ExpressionError::Unexpected(7))
+        );
+        assert_eq!(
+            Expression::parse("0xfg"),
+            Err(ExpressionError::Unexpected(0))
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Size of the 16 small hex digits, 5 bytes each.
+pub const SMALL_FONT_LEN: usize = 16 * 5;
+
+/// Where the fonts live in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontLayout {
+    /// Address of the 4x5 hex digits used by FX29.
+    pub small: u16,
+    /// Address of the 8x10 digits used by FX30, if the font has them.
+    pub big: Option<u16>,
+}
+
+/// The built-in fonts, after the interpreters that drew them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontSet {
+    /// The CHIP-48 style digits plain CHIP-8 has always used here.
+    Chip8,
+    /// The digits of the COSMAC VIP interpreter.
+    Vip,
+    /// The 3 pixels wide digits of the ETI-660.
+    Eti660,
+    Dream6800,
+    /// The CHIP-48 digits of SUPER-CHIP, with its 8x10 digits 0 to 9.
+    SuperChip,
+    /// Octo's font, which has big digits for A to F too.
+    Octo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    /// The small font must hold exactly 16 glyphs of 5 bytes.
+    SmallFontSize(usize),
+    /// The big font must hold 10 or 16 glyphs of 10 bytes.
+    BigFontSize(usize),
+    /// The glyphs overlap, or do not fit below 0x200.
+    InvalidLayout(FontLayout),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SmallFontSize(len) => write!(
+                f,
+                "small font must be {} bytes, but it is {}",
+                SMALL_FONT_LEN, len
+            ),
+            Self::BigFontSize(len) => {
+                write!(f, "big font must be 100 or 160 bytes, but it is {}", len)
+            }
+            Self::InvalidLayout(layout) => write!(
+                f,
+                "font at {:#05x} (big digits at {:#05x?}) overlaps or does not fit below 0x200",
+                layout.small, layout.big
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FontError {}
+
+/// The glyphs FX29 and FX30 point to, and where they are in memory.
+///
+/// The interpreters kept their fonts in different places, in the memory
+/// they took up below 0x200. Emulators of the VIP-era machines and of
+/// SUPER-CHIP commonly load the small digits at 0x050, with the big ones
+/// after them at 0x0A0. Octo loads them at 0x000 and 0x050, and plain
+/// CHIP-8 keeps its digits at 0x000.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    small: Vec<u8>,
+    big: Option<Vec<u8>>,
+    layout: FontLayout,
+}
+
+impl Font {
+    pub fn builtin(set: FontSet) -> Self {
+        let (small, big): (&[u8], Option<&[u8]>) = match set {
+            FontSet::Chip8 => (&CHIP8, None),
+            FontSet::Vip => (&VIP, None),
+            FontSet::Eti660 => (&ETI660, None),
+            FontSet::Dream6800 => (&DREAM6800, None),
+            FontSet::SuperChip => (&CHIP48, Some(&SCHIP_BIG)),
+            FontSet::Octo => (&CHIP48, Some(&OCTO_BIG)),
+        };
+        let layout = match set {
+            FontSet::Chip8 => FontLayout {
+                small: 0x000,
+                big: None,
+            },
+            FontSet::Vip | FontSet::Eti660 | FontSet::Dream6800 => FontLayout {
+                small: 0x050,
+                big: None,
+            },
+            FontSet::SuperChip => FontLayout {
+                small: 0x050,
+                big: Some(0x0a0),
+            },
+            FontSet::Octo => FontLayout {
+                small: 0x000,
+                big: Some(0x050),
+            },
+        };
+
+        Self {
+            small: small.to_vec(),
+            big: big.map(<[u8]>::to_vec),
+            layout,
+        }
+    }
+
+    /// A font from user-supplied glyphs: 16 small digits of 5 bytes, and
+    /// optionally 10 or 16 big digits of 10 bytes. They are loaded at 0x050
+    /// and 0x0A0 unless moved with `with_layout`.
+    pub fn custom(small: &[u8], big: Option<&[u8]>) -> Result<Self, FontError> {
+        if small.len() != SMALL_FONT_LEN {
+            return Err(FontError::SmallFontSize(small.len()));
+        }
+        if let Some(big) = big.filter(|big| big.len() != 100 && big.len() != 160) {
+            return Err(FontError::BigFontSize(big.len()));
+        }
+
+        Ok(Self {
+            small: small.to_vec(),
+            big: big.map(<[u8]>::to_vec),
+            layout: FontLayout {
+                small: 0x050,
+                big: big.map(|_| 0x0a0),
+            },
+        })
+    }
+
+    /// Moves the glyphs to `layout`. The big digits need an address if the
+    /// font has them.
+    pub fn with_layout(self, layout: FontLayout) -> Result<Self, FontError> {
+        let small = layout.small as usize..layout.small as usize + self.small.len();
+        let big = match (layout.big, &self.big) {
+            (Some(addr), Some(glyphs)) => addr as usize..addr as usize + glyphs.len(),
+            (None, None) => 0..0,
+            _ => return Err(FontError::InvalidLayout(layout)),
+        };
+        let overlaps = !big.is_empty() && small.start < big.end && big.start < small.end;
+        if overlaps || small.end > 0x200 || big.end > 0x200 {
+            return Err(FontError::InvalidLayout(layout));
+        }
+
+        Ok(Self { layout, ..self })
+    }
+
+    pub fn small(&self) -> &[u8] {
+        &self.small
+    }
+
+    pub fn big(&self) -> Option<&[u8]> {
+        self.big.as_deref()
+    }
+
+    pub fn layout(&self) -> FontLayout {
+        self.layout
+    }
+}
+
+#[rustfmt::skip]
+const CHIP8: [u8; SMALL_FONT_LEN] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0x10, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const VIP: [u8; SMALL_FONT_LEN] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0xa0, 0xa0, 0xf0, 0x20, 0x20, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xf0, 0x50, 0x70, 0x50, 0xf0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xf0, 0x50, 0x50, 0x50, 0xf0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const ETI660: [u8; SMALL_FONT_LEN] = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0xa0, 0xa0, 0xe0, 0x20, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // A
+    0xc0, 0xa0, 0xe0, 0xa0, 0xc0, // B
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // C
+    0xc0, 0xa0, 0xa0, 0xa0, 0xc0, // D
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // E
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const DREAM6800: [u8; SMALL_FONT_LEN] = [
+    0xe0, 0xa0, 0xa0, 0xa0, 0xe0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xe0, 0x20, 0xe0, 0x80, 0xe0, // 2
+    0xe0, 0x20, 0xe0, 0x20, 0xe0, // 3
+    0x80, 0xa0, 0xa0, 0xe0, 0x20, // 4
+    0xe0, 0x80, 0xe0, 0x20, 0xe0, // 5
+    0xe0, 0x80, 0xe0, 0xa0, 0xe0, // 6
+    0xe0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xe0, 0xa0, 0xe0, 0xa0, 0xe0, // 8
+    0xe0, 0xa0, 0xe0, 0x20, 0xe0, // 9
+    0xe0, 0xa0, 0xe0, 0xa0, 0xa0, // A
+    0xc0, 0xa0, 0xe0, 0xa0, 0xc0, // B
+    0xe0, 0x80, 0x80, 0x80, 0xe0, // C
+    0xc0, 0xa0, 0xa0, 0xa0, 0xc0, // D
+    0xe0, 0x80, 0xe0, 0x80, 0xe0, // E
+    0xe0, 0x80, 0xc0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const CHIP48: [u8; SMALL_FONT_LEN] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const SCHIP_BIG: [u8; 100] = [
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, // 1
+    0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, // 2
+    0x3c, 0x7e, 0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, // 3
+    0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff, 0x06, 0x06, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, // 5
+    0x3e, 0x7c, 0xc0, 0xc0, 0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, // 6
+    0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, // 8
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f, 0x03, 0x03, 0x3e, 0x7c, // 9
+];
+
+#[rustfmt::skip]
+const OCTO_BIG: [u8; 160] = [
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xff, 0xff, // 1
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // 2
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 3
+    0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0x03, 0x03, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 5
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 6
+    0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18, // 7
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 8
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 9
+    0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, // A
+    0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, // B
+    0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0xff, 0x3c, // C
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // D
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // E
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // F
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_each_font_set() {
+        assert_eq!(
+            Font::builtin(FontSet::Octo).layout(),
+            FontLayout {
+                small: 0x000,
+                big: Some(0x050)
+            }
+        );
+        assert_eq!(Font::builtin(FontSet::SuperChip).layout().big, Some(0x0a0));
+        assert_eq!(Font::builtin(FontSet::Vip).layout().small, 0x050);
+        assert_eq!(Font::builtin(FontSet::Chip8).layout().small, 0x000);
+    }
+
+    #[test]
+    fn moves_custom_fonts() {
+        let font = Font::custom(&[0; 80], Some(&[0; 160])).unwrap();
+        let layout = FontLayout {
+            small: 0x100,
+            big: Some(0x000),
+        };
+        assert_eq!(font.clone().with_layout(layout).unwrap().layout(), layout);
+
+        let overlapping = FontLayout {
+            small: 0x000,
+            big: Some(0x040),
+        };
+        assert_eq!(
+            font.clone().with_layout(overlapping),
+            Err(FontError::InvalidLayout(overlapping))
+        );
+        let too_high = FontLayout {
+            small: 0x000,
+            big: Some(0x1a0),
+        };
+        assert_eq!(
+            font.with_layout(too_high),
+            Err(FontError::InvalidLayout(too_high))
+        );
+    }
+
+    #[test]
+    fn validates_custom_fonts() {
+        assert!(Font::custom(&[0; 80], Some(&[0; 100])).is_ok());
+        assert_eq!(
+            Font::custom(&[0; 79], None),
+            Err(FontError::SmallFontSize(79))
+        );
+        assert_eq!(
+            Font::custom(&[0; 80], Some(&[0; 120])),
+            Err(FontError::BigFontSize(120))
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use super::{Rect, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+pub const MAX_DISPLAY_WIDTH: usize = 128;
+pub const MAX_DISPLAY_HEIGHT: usize = 64;
+
+/// Monochrome display, bit-packed with one word per row.
+///
+/// The leftmost pixel of a row is its most significant bit, so sprites can be
+/// XOR'ed in with a shift. Rows are `u128` so hi-res modes fit too; in the
+/// 64x32 mode only the lower 64 bits are used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    rows: [u128; MAX_DISPLAY_HEIGHT],
+}
+
+/// Pixel layout for `Framebuffer::encode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// One bit per pixel, most significant bit first. Rows are padded to a
+    /// whole byte.
+    Mono1,
+    /// One byte per pixel, holding the palette index (`0` off, `1` on).
+    Indexed8,
+    /// 16-bit little endian RGB565.
+    Rgb565,
+    /// 32-bit RGBA, with opaque alpha.
+    Rgba8888,
+}
+
+impl PixelFormat {
+    /// Size in bytes of one row of `width` pixels.
+    pub fn stride(&self, width: usize) -> usize {
+        match self {
+            Self::Mono1 => width.div_ceil(8),
+            Self::Indexed8 => width,
+            Self::Rgb565 => width * 2,
+            Self::Rgba8888 => width * 4,
+        }
+    }
+
+    /// Size in bytes of a whole frame.
+    pub fn buffer_len(&self, width: usize, height: usize) -> usize {
+        self.stride(width) * height
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new(DISPLAY_WIDTH, DISPLAY_HEIGHT)
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width <= MAX_DISPLAY_WIDTH && height <= MAX_DISPLAY_HEIGHT);
+
+        Self {
+            width,
+            height,
+            rows: [0; MAX_DISPLAY_HEIGHT],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn clear(&mut self) {
+        self.rows = [0; MAX_DISPLAY_HEIGHT];
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    pub fn row(&self, y: usize) -> u128 {
+        self.rows[y]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & self.bit(x) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        if value {
+            self.rows[y] |= self.bit(x);
+        } else {
+            self.rows[y] &= !self.bit(x);
+        }
+    }
+
+    /// Pixels in row-major order.
+    pub fn pixels(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self.get(x, y)))
+    }
+
+    /// XORs the `sprite_width` lowest bits of `sprite` into row `y`, starting
+    /// at column `x` and wrapping around the right edge. Returns the mask of
+    /// flipped pixels and whether any lit pixel got erased.
+    pub fn xor_sprite_row(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: u16,
+        sprite_width: usize,
+    ) -> (u128, bool) {
+        let aligned = (sprite as u128) << (self.width - sprite_width);
+        let mask = self.rotate_right(aligned, x % self.width);
+
+        let row = &mut self.rows[y % self.height];
+        let collision = *row & mask != 0;
+        *row ^= mask;
+
+        (mask, collision)
+    }
+
+    /// Bounding box of the pixels set in a row mask.
+    pub fn mask_bounds(&self, y: usize, mask: u128) -> Option<Rect> {
+        if mask == 0 {
+            return None;
+        }
+
+        let unused = (MAX_DISPLAY_WIDTH - self.width) as u32;
+        let left = (mask.leading_zeros() - unused) as usize;
+        let right = self.width - 1 - mask.trailing_zeros() as usize;
+
+        Some(Rect::new(left, y, right - left + 1, 1))
+    }
+
+    /// Columns of the pixels set in a row mask, left to right.
+    pub fn mask_columns(&self, mask: u128) -> impl Iterator<Item = usize> + '_ {
+        (0..self.width).filter(move |x| mask & self.bit(*x) != 0)
+    }
+
+    /// Moves every row `n` pixels down; rows scrolled in at the top are
+    /// blank.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.height);
+        self.rows.copy_within(0..self.height - n, n);
+        self.rows[..n].fill(0);
+    }
+
+    /// Moves every row `n` pixels up; rows scrolled in at the bottom are
+    /// blank.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.height);
+        self.rows.copy_within(n..self.height, 0);
+        self.rows[self.height - n..self.height].fill(0);
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shr(n as u32).unwrap_or(0);
+        }
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        let mask = u128::MAX >> (MAX_DISPLAY_WIDTH - self.width);
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shl(n as u32).unwrap_or(0) & mask;
+        }
+    }
+
+    /// Writes the frame into `out` as `format`, which must hold at least
+    /// `format.buffer_len(width, height)` bytes. `colors` are the RGB values
+    /// of unlit and lit pixels, and are ignored by the indexed formats.
+    pub fn encode(&self, format: PixelFormat, colors: [(u8, u8, u8); 2], out: &mut [u8]) {
+        self.encode_rect(
+            format,
+            colors,
+            Rect::new(0, 0, self.width, self.height),
+            out,
+        );
+    }
+
+    /// Like `encode`, but only writes the pixels inside `rect`. The rest of
+    /// `out` is left untouched.
+    pub fn encode_rect(
+        &self,
+        format: PixelFormat,
+        colors: [(u8, u8, u8); 2],
+        rect: Rect,
+        out: &mut [u8],
+    ) {
+        let stride = format.stride(self.width);
+
+        for y in rect.y..(rect.y + rect.height).min(self.height) {
+            let line = &mut out[y * stride..(y + 1) * stride];
+
+            for x in rect.x..(rect.x + rect.width).min(self.width) {
+                let pixel = self.get(x, y);
+                let (r, g, b) = colors[pixel as usize];
+
+                match format {
+                    PixelFormat::Mono1 => {
+                        let bit = 0x80 >> (x % 8);
+                        if pixel {
+                            line[x / 8] |= bit;
+                        } else {
+                            line[x / 8] &= !bit;
+                        }
+                    }
+                    PixelFormat::Indexed8 => line[x] = pixel as u8,
+                    PixelFormat::Rgb565 => {
+                        let value = rgb565(r, g, b);
+                        line[x * 2..x * 2 + 2].copy_from_slice(&value.to_le_bytes());
+                    }
+                    PixelFormat::Rgba8888 => {
+                        line[x * 4..x * 4 + 4].copy_from_slice(&[r, g, b, 0xff]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn bit(&self, x: usize) -> u128 {
+        1 << (self.width - 1 - x)
+    }
+
+    #[inline]
+    fn rotate_right(&self, value: u128, amount: usize) -> u128 {
+        if amount == 0 {
+            return value;
+        }
+
+        let mask = u128::MAX >> (MAX_DISPLAY_WIDTH - self.width);
+        ((value >> amount) | (value << (self.width - amount))) & mask
+    }
+}
+
+#[inline]
+fn rgb565(r: u8, g: u8, b: u8) -> u16 {
+    ((r as u16 & 0xf8) << 8) | ((g as u16 & 0xfc) << 3) | (b as u16 >> 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn any_framebuffer() -> Framebuffer {
+        let mut fb = Framebuffer::new(16, 2);
+        fb.set(0, 0, true);
+        fb.set(9, 1, true);
+        fb
+    }
+
+    #[test]
+    fn xor_sprite_row_wraps_around() {
+        let mut fb = Framebuffer::default();
+
+        let (mask, collision) = fb.xor_sprite_row(60, 0, 0xff, 8);
+
+        assert!(!collision);
+        assert_eq!(mask, 0xf000_0000_0000_000f);
+        assert_eq!(fb.row(0), mask);
+
+        let (_, collision) = fb.xor_sprite_row(62, 0, 0x80, 8);
+        assert!(collision);
+        assert!(!fb.get(62, 0));
+    }
+
+    #[test]
+    fn scrolls_in_all_directions() {
+        let mut fb = Framebuffer::new(8, 4);
+        fb.set(0, 0, true);
+        fb.set(7, 3, true);
+
+        fb.scroll_down(1);
+        assert!(fb.get(0, 1) && !fb.get(0, 0));
+        assert!((0..8).all(|x| !fb.get(x, 3)));
+
+        fb.scroll_right(2);
+        assert!(fb.get(2, 1));
+        fb.scroll_left(3);
+        assert!(fb.is_blank());
+
+        fb.set(4, 3, true);
+        fb.scroll_up(3);
+        assert!(fb.get(4, 0) && !fb.get(4, 3));
+    }
+
+    #[test]
+    fn mask_bounds_of_row() {
+        let fb = Framebuffer::default();
+
+        assert_eq!(fb.mask_bounds(3, 0), None);
+        assert_eq!(fb.mask_bounds(3, 0x0ff0 << 48), Some(Rect::new(4, 3, 8, 1)));
+    }
+
+    #[test]
+    fn encodes_mono1() {
+        let fb = any_framebuffer();
+        let mut out = vec![0; PixelFormat::Mono1.buffer_len(16, 2)];
+
+        fb.encode(PixelFormat::Mono1, [(0, 0, 0); 2], &mut out);
+
+        assert_eq!(out, [0x80, 0x00, 0x00, 0x40]);
+    }
+
+    #[test]
+    fn encodes_indexed8() {
+        let fb = any_framebuffer();
+        let mut out = vec![0; PixelFormat::Indexed8.buffer_len(16, 2)];
+
+        fb.encode(PixelFormat::Indexed8, [(0, 0, 0); 2], &mut out);
+
+        let lit: Vec<usize> = (0..out.len()).filter(|i| out[*i] == 1).collect();
+        assert_eq!(lit, [0, 16 + 9]);
+    }
+
+    #[test]
+    fn encodes_rgb565() {
+        let fb = any_framebuffer();
+        let mut out = vec![0; PixelFormat::Rgb565.buffer_len(16, 2)];
+
+        fb.encode(
+            PixelFormat::Rgb565,
+            [(0x00, 0x00, 0xff), (0xff, 0x00, 0x00)],
+            &mut out,
+        );
+
+        assert_eq!(out[0..4], [0x00, 0xf8, 0x1f, 0x00]);
+    }
+
+    #[test]
+    fn encodes_rgba8888_rect() {
+        let fb = any_framebuffer();
+        let mut out = vec![0; PixelFormat::Rgba8888.buffer_len(16, 2)];
+
+        fb.encode_rect(
+            PixelFormat::Rgba8888,
+            [(0x11, 0x22, 0x33), (0xaa, 0xbb, 0xcc)],
+            Rect::new(0, 0, 2, 1),
+            &mut out,
+        );
+
+        assert_eq!(out[0..8], [0xaa, 0xbb, 0xcc, 0xff, 0x11, 0x22, 0x33, 0xff]);
+        assert_eq!(out[8..], [0; 120]);
+    }
+}
@@ -0,0 +1,42 @@
+//! Traits to plug a `Vm` into a frontend.
+//!
+//! Every trait has no-op defaults and is implemented for `()`, which is what
+//! `Vm::new` uses: the VM keeps its own framebuffer and key state, and hosts
+//! read them (or call `Vm::set_key`) as usual.
+
+/// Display sink that gets notified of every change to the framebuffer.
+pub trait Display {
+    fn clear(&mut self) {}
+    fn set_pixel(&mut self, _x: usize, _y: usize, _value: bool) {}
+}
+
+/// Input source polled before each instruction.
+///
+/// Implementations update the pressed state of the 16 keys in `keys`. Keys
+/// that are not touched keep the value set via `Vm::set_key`.
+pub trait Keypad {
+    fn poll(&mut self, _keys: &mut [bool; 16]) {}
+}
+
+/// Sound output, notified whenever the sound timer starts or stops buzzing.
+pub trait Beeper {
+    fn set_buzzing(&mut self, _buzzing: bool) {}
+}
+
+/// Source of random bytes for the `CXKK` instruction.
+pub trait RandomSource {
+    fn random_byte(&mut self) -> u8;
+}
+
+impl Display for () {}
+impl Keypad for () {}
+impl Beeper for () {}
+
+impl<F> RandomSource for F
+where
+    F: FnMut() -> u8,
+{
+    fn random_byte(&mut self) -> u8 {
+        self()
+    }
+}
@@ -0,0 +1,318 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+
+/// Size of the header in front of the samples of a digitised sound.
+pub(crate) const SOUND_HEADER_LEN: usize = 6;
+
+/// How MegaChip sprites are mixed with the pixels they are drawn over, as
+/// set by 080N.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    /// The sprite is drawn with 25% opacity.
+    Alpha25,
+    Alpha50,
+    Alpha75,
+    /// The colours are added up, saturating at white.
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn from_number(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Alpha25),
+            2 => Some(Self::Alpha50),
+            3 => Some(Self::Alpha75),
+            4 => Some(Self::Add),
+            5 => Some(Self::Multiply),
+            _ => None,
+        }
+    }
+
+    /// Colour of a sprite pixel `src` drawn over `dst`, both RGBA.
+    pub fn blend(&self, src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+        let mix = |opacity: u16| {
+            let channel =
+                |i: usize| ((src[i] as u16 * opacity + dst[i] as u16 * (4 - opacity)) / 4) as u8;
+            [channel(0), channel(1), channel(2), 0xff]
+        };
+
+        match self {
+            Self::Normal => src,
+            Self::Alpha25 => mix(1),
+            Self::Alpha50 => mix(2),
+            Self::Alpha75 => mix(3),
+            Self::Add => [
+                src[0].saturating_add(dst[0]),
+                src[1].saturating_add(dst[1]),
+                src[2].saturating_add(dst[2]),
+                0xff,
+            ],
+            Self::Multiply => [
+                (src[0] as u16 * dst[0] as u16 / 255) as u8,
+                (src[1] as u16 * dst[1] as u16 / 255) as u8,
+                (src[2] as u16 * dst[2] as u16 / 255) as u8,
+                0xff,
+            ],
+        }
+    }
+}
+
+/// Digitised sound started by 060N, as 8-bit unsigned samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigitalSound {
+    pub sample_rate: u16,
+    pub looping: bool,
+    pub samples: Vec<u8>,
+}
+
+impl DigitalSound {
+    /// Reads a sound from memory: the sample rate (2 bytes), the number of
+    /// samples (3 bytes) and a reserved byte, all big endian, followed by
+    /// the samples. `None` if it runs past the end of `memory`.
+    pub fn parse(memory: &[u8], looping: bool) -> Option<Self> {
+        let size = Self::size(memory)?;
+        let header = &memory[..SOUND_HEADER_LEN];
+        let sample_rate = u16::from_be_bytes([header[0], header[1]]);
+        let samples = memory.get(SOUND_HEADER_LEN..size)?;
+
+        Some(Self {
+            sample_rate,
+            looping,
+            samples: samples.to_vec(),
+        })
+    }
+
+    /// Bytes the sound at the start of `memory` takes, header included.
+    pub(crate) fn size(memory: &[u8]) -> Option<usize> {
+        let header = memory.get(..SOUND_HEADER_LEN)?;
+        let len = u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize;
+        Some(SOUND_HEADER_LEN + len)
+    }
+}
+
+/// Changes to the digitised sound, for hosts to play.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoundEvent {
+    Play(DigitalSound),
+    Stop,
+}
+
+/// State of the MegaChip extensions: a 256x192 display with 8-bit colour
+/// sprites, which is only shown while MegaChip mode is on (0011).
+///
+/// Sprites are drawn into a back buffer, and 00E0 shows it and starts a new
+/// one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MegaChip {
+    enabled: bool,
+    /// RGBA colours; index 0 is transparent.
+    palette: [[u8; 4]; 256],
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend_mode: BlendMode,
+    collision_color: u8,
+    indices: Vec<u8>,
+    back: Vec<[u8; 4]>,
+    front: Vec<[u8; 4]>,
+    sound_event: Option<SoundEvent>,
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        let len = MEGA_WIDTH * MEGA_HEIGHT;
+        Self {
+            enabled: false,
+            palette: [[0, 0, 0, 0xff]; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xff,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            indices: vec![0; len],
+            back: vec![[0, 0, 0, 0xff]; len],
+            front: vec![[0, 0, 0, 0xff]; len],
+            sound_event: None,
+        }
+    }
+}
+
+impl MegaChip {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// RGBA colours of the frame shown by the last 00E0, in row-major
+    /// order.
+    pub fn frame(&self) -> &[[u8; 4]] {
+        &self.front
+    }
+
+    /// Opacity of the whole display set by 05NN, used to fade it out.
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Width and height in bytes of the sprites drawn by DXYN.
+    pub fn sprite_size(&self) -> (usize, usize) {
+        (self.sprite_width, self.sprite_height)
+    }
+
+    /// Switches MegaChip mode on or off, which clears the display.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        *self = Self {
+            enabled,
+            palette: self.palette,
+            sound_event: self.sound_event.take(),
+            ..Self::default()
+        };
+    }
+
+    /// 02NN: loads colours 1 to N from ARGB values in `data`.
+    pub(crate) fn load_palette(&mut self, data: &[u8]) {
+        for (i, argb) in data.chunks_exact(4).take(255).enumerate() {
+            self.palette[i + 1] = [argb[1], argb[2], argb[3], argb[0]];
+        }
+    }
+
+    /// 03NN and 04NN, where 0 stands for 256.
+    pub(crate) fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { width as usize };
+    }
+
+    pub(crate) fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 { 256 } else { height as usize };
+    }
+
+    pub(crate) fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub(crate) fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub(crate) fn set_collision_color(&mut self, color: u8) {
+        self.collision_color = color;
+    }
+
+    /// Draws a sprite of palette indices at `x`, `y`, clipped at the edges.
+    /// Returns whether it covered a pixel of the collision colour.
+    pub(crate) fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        for (row, line) in sprite.chunks(self.sprite_width.max(1)).enumerate() {
+            for (column, index) in line.iter().enumerate() {
+                let (px, py) = (x + column, y + row);
+                if *index == 0 || px >= MEGA_WIDTH || py >= MEGA_HEIGHT {
+                    continue;
+                }
+
+                let i = py * MEGA_WIDTH + px;
+                collision |= self.indices[i] != 0 && self.indices[i] == self.collision_color;
+                self.indices[i] = *index;
+                self.back[i] = self
+                    .blend_mode
+                    .blend(self.palette[*index as usize], self.back[i]);
+            }
+        }
+        collision
+    }
+
+    /// 00E0: shows the frame drawn so far and starts a blank one.
+    pub(crate) fn present(&mut self) {
+        core::mem::swap(&mut self.front, &mut self.back);
+        self.back.fill([0, 0, 0, 0xff]);
+        self.indices.fill(0);
+    }
+
+    /// Scrolls the frame being drawn by `dx`, `dy` pixels.
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        let indices = self.indices.clone();
+        let back = self.back.clone();
+        for y in 0..MEGA_HEIGHT {
+            for x in 0..MEGA_WIDTH {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                let i = y * MEGA_WIDTH + x;
+                if (0..MEGA_WIDTH as isize).contains(&sx) && (0..MEGA_HEIGHT as isize).contains(&sy)
+                {
+                    let src = sy as usize * MEGA_WIDTH + sx as usize;
+                    self.indices[i] = indices[src];
+                    self.back[i] = back[src];
+                } else {
+                    self.indices[i] = 0;
+                    self.back[i] = [0, 0, 0, 0xff];
+                }
+            }
+        }
+    }
+
+    pub(crate) fn set_sound_event(&mut self, event: SoundEvent) {
+        self.sound_event = Some(event);
+    }
+
+    pub(crate) fn take_sound_event(&mut self) -> Option<SoundEvent> {
+        self.sound_event.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blends_colours() {
+        let (src, dst) = ([200, 100, 0, 0xff], [0, 100, 200, 0xff]);
+        assert_eq!(BlendMode::Normal.blend(src, dst), src);
+        assert_eq!(BlendMode::Alpha25.blend(src, dst), [50, 100, 150, 0xff]);
+        assert_eq!(BlendMode::Add.blend(src, dst), [200, 200, 200, 0xff]);
+        assert_eq!(BlendMode::Multiply.blend(src, dst), [0, 39, 0, 0xff]);
+        assert_eq!(BlendMode::from_number(6), None);
+    }
+
+    #[test]
+    fn draws_into_back_buffer_until_presented() {
+        let mut mega = MegaChip::default();
+        mega.load_palette(&[0xff, 0x10, 0x20, 0x30, 0xff, 0x40, 0x50, 0x60]);
+        mega.set_sprite_width(2);
+        mega.set_sprite_height(1);
+
+        assert!(!mega.draw(255, 0, &[1, 2]));
+        assert_eq!(mega.frame()[255], [0, 0, 0, 0xff]);
+
+        mega.present();
+        assert_eq!(mega.frame()[255], [0x10, 0x20, 0x30, 0xff]);
+        assert_eq!(mega.frame()[256], [0, 0, 0, 0xff]);
+    }
+
+    #[test]
+    fn detects_collisions_with_collision_colour() {
+        let mut mega = MegaChip::default();
+        mega.set_sprite_width(1);
+        mega.set_collision_color(2);
+
+        assert!(!mega.draw(0, 0, &[2]));
+        assert!(mega.draw(0, 0, &[3]));
+        assert!(!mega.draw(0, 0, &[2]));
+    }
+
+    #[test]
+    fn parses_digital_sound() {
+        let memory = [0x1f, 0x40, 0x00, 0x00, 0x02, 0x00, 0x80, 0xff, 0x12];
+        let sound = DigitalSound::parse(&memory, true).unwrap();
+        assert_eq!(sound.sample_rate, 8000);
+        assert_eq!(sound.samples, [0x80, 0xff]);
+
+        assert_eq!(DigitalSound::parse(&memory[..7], false), None);
+    }
+}
@@ -0,0 +1,352 @@
+use core::convert::TryFrom;
+
+use super::{Platform, VmError};
+
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum Opcode {
+    /// `0NNN`: calls machine code of the host CPU.
+    Sys(u16),
+    ClearScreen,
+    Ret,
+    Jump(u16),
+    Call(u16),
+    SkipIfEq(u8, u8),
+    SkipIfNeq(u8, u8),
+    SkipEqVxVy(u8, u8),
+    LoadVx(u8, u8),
+    AddVx(u8, u8),
+    LoadVxVy(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    Add(u8, u8),
+    Sub(u8, u8),
+    ShiftR(u8, u8),
+    SubN(u8, u8),
+    ShiftL(u8, u8),
+    SkipNeqVxVy(u8, u8),
+    LoadI(u16),
+    JumpOffset(u16),
+    Rand(u8, u8),
+    Display(u8, u8, u8),
+    SkipIfKey(u8),
+    SkipIfNotKey(u8),
+    LoadDelay(u8),
+    WaitForKey(u8),
+    StoreDelay(u8),
+    StoreSound(u8),
+    AddI(u8),
+    LoadDigit(u8),
+    Bcd(u8),
+    StoreRegisters(u8),
+    LoadRegisters(u8),
+    StoreFlags(u8),
+    LoadFlags(u8),
+    /// SUPER-CHIP `00CN`.
+    ScrollDown(u8),
+    /// SUPER-CHIP `00FB`.
+    ScrollRight,
+    /// SUPER-CHIP `00FC`.
+    ScrollLeft,
+    /// SUPER-CHIP `00FD`: stops the program.
+    Exit,
+    /// SUPER-CHIP `00FE`: switches to the 64x32 mode.
+    LowRes,
+    /// SUPER-CHIP `00FF`: switches to the 128x64 mode.
+    HighRes,
+    /// SUPER-CHIP `FX30`: points I to the big digit in VX.
+    LoadBigDigit(u8),
+    /// MegaChip `0010` and `0011`.
+    MegaMode(bool),
+    /// MegaChip `00BN`, XO-CHIP `00DN`.
+    ScrollUp(u8),
+    /// MegaChip `01NN NNNN`: loads a 24-bit address into I. Holds the top
+    /// byte; the rest is the next word. XO-CHIP `F000 NNNN` is the same
+    /// with a top byte of 0.
+    LoadLongI(u8),
+    /// MegaChip `02NN`: loads NN colours from I.
+    LoadPalette(u8),
+    /// MegaChip `03NN`.
+    SpriteWidth(u8),
+    /// MegaChip `04NN`.
+    SpriteHeight(u8),
+    /// MegaChip `05NN`.
+    ScreenAlpha(u8),
+    /// MegaChip `060N`: plays the sound at I, looping if N is 0.
+    PlaySound(u8),
+    /// MegaChip `0700`.
+    StopSound,
+    /// MegaChip `080N`.
+    BlendMode(u8),
+    /// MegaChip `09NN`.
+    CollisionColor(u8),
+    /// CHIP-8X `02A0`.
+    CycleBackground,
+    /// CHIP-8X `BXY0`.
+    SetColorZones(u8, u8),
+    /// CHIP-8X `BXYN`.
+    SetColorRows(u8, u8, u8),
+    /// CHIP-8X `EXF2`, for the second keypad.
+    SkipIfKey2(u8),
+    /// CHIP-8X `EXF5`, for the second keypad.
+    SkipIfNotKey2(u8),
+    /// CHIP-8X `FXF8`.
+    SetTone(u8),
+    /// XO-CHIP `5XY2`: stores VX to VY (in either order) at I.
+    SaveRange(u8, u8),
+    /// XO-CHIP `5XY3`: loads VX to VY (in either order) from I.
+    LoadRange(u8, u8),
+    /// XO-CHIP `FN01`: selects the bitplanes to draw on, bit 0 being the
+    /// first plane.
+    SelectPlanes(u8),
+    /// XO-CHIP `F002`: loads the 16-byte audio pattern from I.
+    LoadPattern,
+    /// XO-CHIP `FX3A`.
+    SetPitch(u8),
+}
+
+impl Opcode {
+    /// Decodes `value` with the instruction set of `platform`, which may
+    /// give some opcodes another meaning than in CHIP-8.
+    pub fn decode(value: u16, platform: Platform) -> Result<Self, VmError> {
+        let (x, y, n) = (
+            ((value & 0x0f00) >> 8) as u8,
+            ((value & 0x00f0) >> 4) as u8,
+            (value & 0x000f) as u8,
+        );
+
+        let kk = (value & 0x00ff) as u8;
+        if platform == Platform::MegaChip {
+            match value & 0xff00 {
+                0x0000 if value == 0x0010 || value == 0x0011 => {
+                    return Ok(Self::MegaMode(value == 0x0011))
+                }
+                0x0000 if value & 0xfff0 == 0x00b0 => return Ok(Self::ScrollUp(n)),
+                0x0100 => return Ok(Self::LoadLongI(kk)),
+                0x0200 => return Ok(Self::LoadPalette(kk)),
+                0x0300 => return Ok(Self::SpriteWidth(kk)),
+                0x0400 => return Ok(Self::SpriteHeight(kk)),
+                0x0500 => return Ok(Self::ScreenAlpha(kk)),
+                0x0600 if y == 0 => return Ok(Self::PlaySound(n)),
+                0x0700 if kk == 0 => return Ok(Self::StopSound),
+                0x0800 if y == 0 => return Ok(Self::BlendMode(n)),
+                0x0900 => return Ok(Self::CollisionColor(kk)),
+                _ => {}
+            }
+        }
+
+        if platform == Platform::XoChip {
+            match (value & 0xf000, kk) {
+                (0xf000, _) if value == 0xf000 => return Ok(Self::LoadLongI(0)),
+                (0xf000, _) if value == 0xf002 => return Ok(Self::LoadPattern),
+                (0xf000, 0x01) => return Ok(Self::SelectPlanes(x)),
+                (0xf000, 0x3a) => return Ok(Self::SetPitch(x)),
+                (0x0000, _) if value & 0xfff0 == 0x00d0 => return Ok(Self::ScrollUp(n)),
+                (0x5000, _) if n == 2 => return Ok(Self::SaveRange(x, y)),
+                (0x5000, _) if n == 3 => return Ok(Self::LoadRange(x, y)),
+                _ => {}
+            }
+        }
+
+        if platform.supports_superchip() {
+            match value {
+                0x00c0..=0x00cf => return Ok(Self::ScrollDown(n)),
+                0x00fb => return Ok(Self::ScrollRight),
+                0x00fc => return Ok(Self::ScrollLeft),
+                0x00fd => return Ok(Self::Exit),
+                0x00fe => return Ok(Self::LowRes),
+                0x00ff => return Ok(Self::HighRes),
+                _ if value & 0xf0ff == 0xf030 => return Ok(Self::LoadBigDigit(x)),
+                _ => {}
+            }
+        }
+
+        match (platform, value & 0xf000, value & 0x00ff) {
+            (Platform::Chip8X, _, _) if value == 0x02a0 => Ok(Self::CycleBackground),
+            (Platform::Chip8X, 0xb000, _) if n == 0 => Ok(Self::SetColorZones(x, y)),
+            (Platform::Chip8X, 0xb000, _) => Ok(Self::SetColorRows(x, y, n)),
+            (Platform::Chip8X, 0xe000, 0xf2) => Ok(Self::SkipIfKey2(x)),
+            (Platform::Chip8X, 0xe000, 0xf5) => Ok(Self::SkipIfNotKey2(x)),
+            (Platform::Chip8X, 0xf000, 0xf8) => Ok(Self::SetTone(x)),
+            _ => Self::try_from(value),
+        }
+    }
+}
+
+impl TryFrom<u16> for Opcode {
+    type Error = VmError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let nibbles = (
+            ((value & 0xf000) >> 12) as u8,
+            ((value & 0x0f00) >> 8) as u8,
+            ((value & 0x00f0) >> 4) as u8,
+            (value & 0x000f) as u8,
+        );
+
+        let nnn = value & 0x0fff;
+        let kk = (value & 0x00ff) as u8;
+
+        match nibbles {
+            (0x0, 0x0, 0xe, 0x0) => Ok(Self::ClearScreen),
+            (0x0, 0x0, 0xe, 0xe) => Ok(Self::Ret),
+            (0x0, _, _, _) => Ok(Self::Sys(nnn)),
+            (0x1, _, _, _) => Ok(Self::Jump(nnn)),
+            (0x2, _, _, _) => Ok(Self::Call(nnn)),
+            (0x3, x, _, _) => Ok(Self::SkipIfEq(x, kk)),
+            (0x4, x, _, _) => Ok(Self::SkipIfNeq(x, kk)),
+            (0x5, x, y, 0) => Ok(Self::SkipEqVxVy(x, y)),
+            (0x6, x, _, _) => Ok(Self::LoadVx(x, kk)),
+            (0x7, x, _, _) => Ok(Self::AddVx(x, kk)),
+            (0x8, x, y, 0x0) => Ok(Self::LoadVxVy(x, y)),
+            (0x8, x, y, 0x1) => Ok(Self::Or(x, y)),
+            (0x8, x, y, 0x2) => Ok(Self::And(x, y)),
+            (0x8, x, y, 0x3) => Ok(Self::Xor(x, y)),
+            (0x8, x, y, 0x4) => Ok(Self::Add(x, y)),
+            (0x8, x, y, 0x5) => Ok(Self::Sub(x, y)),
+            (0x8, x, y, 0x6) => Ok(Self::ShiftR(x, y)),
+            (0x8, x, y, 0x7) => Ok(Self::SubN(x, y)),
+            (0x8, x, y, 0xe) => Ok(Self::ShiftL(x, y)),
+            (0x9, x, y, 0) => Ok(Self::SkipNeqVxVy(x, y)),
+            (0xa, _, _, _) => Ok(Self::LoadI(nnn)),
+            (0xb, _, _, _) => Ok(Self::JumpOffset(nnn)),
+            (0xc, x, _, _) => Ok(Self::Rand(x, kk)),
+            (0xd, x, y, n) => Ok(Self::Display(x, y, n)),
+            (0xe, x, 0x9, 0xe) => Ok(Self::SkipIfKey(x)),
+            (0xe, x, 0xa, 0x1) => Ok(Self::SkipIfNotKey(x)),
+            (0xf, x, 0x0, 0x7) => Ok(Self::LoadDelay(x)),
+            (0xf, x, 0x0, 0xa) => Ok(Self::WaitForKey(x)),
+            (0xf, x, 0x1, 0x5) => Ok(Self::StoreDelay(x)),
+            (0xf, x, 0x1, 0x8) => Ok(Self::StoreSound(x)),
+            (0xf, x, 0x1, 0xe) => Ok(Self::AddI(x)),
+            (0xf, x, 0x2, 0x9) => Ok(Self::LoadDigit(x)),
+            (0xf, x, 0x3, 0x3) => Ok(Self::Bcd(x)),
+            (0xf, x, 0x5, 0x5) => Ok(Self::StoreRegisters(x)),
+            (0xf, x, 0x6, 0x5) => Ok(Self::LoadRegisters(x)),
+            (0xf, x, 0x7, 0x5) => Ok(Self::StoreFlags(x)),
+            (0xf, x, 0x8, 0x5) => Ok(Self::LoadFlags(x)),
+            _ => Err(VmError::InvalidOpcode(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_from_short() {
+        assert_eq!(Opcode::try_from(0x00e0), Ok(Opcode::ClearScreen));
+        assert_eq!(Opcode::try_from(0x00ee), Ok(Opcode::Ret));
+        assert_eq!(Opcode::try_from(0x0abc), Ok(Opcode::Sys(0x0abc)));
+        assert_eq!(Opcode::try_from(0x1abc), Ok(Opcode::Jump(0x0abc)));
+        assert_eq!(Opcode::try_from(0x2abc), Ok(Opcode::Call(0x0abc)));
+        assert_eq!(Opcode::try_from(0x3abc), Ok(Opcode::SkipIfEq(0xa, 0xbc)));
+        assert_eq!(Opcode::try_from(0x4abc), Ok(Opcode::SkipIfNeq(0xa, 0xbc)));
+        assert_eq!(Opcode::try_from(0x5ab0), Ok(Opcode::SkipEqVxVy(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x6abc), Ok(Opcode::LoadVx(0xa, 0xbc)));
+        assert_eq!(Opcode::try_from(0x7abc), Ok(Opcode::AddVx(0xa, 0xbc)));
+        assert_eq!(Opcode::try_from(0x8ab0), Ok(Opcode::LoadVxVy(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab1), Ok(Opcode::Or(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab2), Ok(Opcode::And(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab3), Ok(Opcode::Xor(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab4), Ok(Opcode::Add(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab5), Ok(Opcode::Sub(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab6), Ok(Opcode::ShiftR(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8ab7), Ok(Opcode::SubN(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x8abe), Ok(Opcode::ShiftL(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0x9ab0), Ok(Opcode::SkipNeqVxVy(0xa, 0xb)));
+        assert_eq!(Opcode::try_from(0xaabc), Ok(Opcode::LoadI(0x0abc)));
+        assert_eq!(Opcode::try_from(0xbabc), Ok(Opcode::JumpOffset(0x0abc)));
+        assert_eq!(Opcode::try_from(0xcabc), Ok(Opcode::Rand(0xa, 0xbc)));
+        assert_eq!(Opcode::try_from(0xdabc), Ok(Opcode::Display(0xa, 0xb, 0xc)));
+        assert_eq!(Opcode::try_from(0xea9e), Ok(Opcode::SkipIfKey(0xa)));
+        assert_eq!(Opcode::try_from(0xeaa1), Ok(Opcode::SkipIfNotKey(0xa)));
+        assert_eq!(Opcode::try_from(0xfa07), Ok(Opcode::LoadDelay(0xa)));
+        assert_eq!(Opcode::try_from(0xfa0a), Ok(Opcode::WaitForKey(0xa)));
+        assert_eq!(Opcode::try_from(0xfa15), Ok(Opcode::StoreDelay(0xa)));
+        assert_eq!(Opcode::try_from(0xfa18), Ok(Opcode::StoreSound(0xa)));
+        assert_eq!(Opcode::try_from(0xfa1e), Ok(Opcode::AddI(0xa)));
+        assert_eq!(Opcode::try_from(0xfa29), Ok(Opcode::LoadDigit(0xa)));
+        assert_eq!(Opcode::try_from(0xfa33), Ok(Opcode::Bcd(0xa)));
+        assert_eq!(Opcode::try_from(0xfa55), Ok(Opcode::StoreRegisters(0xa)));
+        assert_eq!(Opcode::try_from(0xfa65), Ok(Opcode::LoadRegisters(0xa)));
+        assert_eq!(Opcode::try_from(0xfa75), Ok(Opcode::StoreFlags(0xa)));
+        assert_eq!(Opcode::try_from(0xfa85), Ok(Opcode::LoadFlags(0xa)));
+    }
+
+    #[test]
+    fn decodes_chip8x_extensions() {
+        let decode = |value| Opcode::decode(value, Platform::Chip8X);
+        assert_eq!(decode(0x02a0), Ok(Opcode::CycleBackground));
+        assert_eq!(decode(0xbab0), Ok(Opcode::SetColorZones(0xa, 0xb)));
+        assert_eq!(decode(0xbabc), Ok(Opcode::SetColorRows(0xa, 0xb, 0xc)));
+        assert_eq!(decode(0xeaf2), Ok(Opcode::SkipIfKey2(0xa)));
+        assert_eq!(decode(0xeaf5), Ok(Opcode::SkipIfNotKey2(0xa)));
+        assert_eq!(decode(0xfaf8), Ok(Opcode::SetTone(0xa)));
+        assert_eq!(decode(0x6abc), Ok(Opcode::LoadVx(0xa, 0xbc)));
+
+        assert_eq!(
+            Opcode::decode(0xbabc, Platform::Chip8),
+            Ok(Opcode::JumpOffset(0x0abc))
+        );
+        assert_eq!(
+            Opcode::decode(0xeaf2, Platform::Chip8),
+            Err(VmError::InvalidOpcode(0xeaf2))
+        );
+    }
+
+    #[test]
+    fn decodes_superchip_extensions() {
+        let decode = |value| Opcode::decode(value, Platform::SuperChip);
+        assert_eq!(decode(0x00c5), Ok(Opcode::ScrollDown(5)));
+        assert_eq!(decode(0x00fb), Ok(Opcode::ScrollRight));
+        assert_eq!(decode(0x00fc), Ok(Opcode::ScrollLeft));
+        assert_eq!(decode(0x00fd), Ok(Opcode::Exit));
+        assert_eq!(decode(0x00fe), Ok(Opcode::LowRes));
+        assert_eq!(decode(0x00ff), Ok(Opcode::HighRes));
+        assert_eq!(decode(0xfa30), Ok(Opcode::LoadBigDigit(0xa)));
+        assert_eq!(decode(0x0011), Ok(Opcode::Sys(0x011)));
+
+        assert_eq!(
+            Opcode::decode(0x00ff, Platform::Chip8),
+            Ok(Opcode::Sys(0x0ff))
+        );
+    }
+
+    #[test]
+    fn decodes_xochip_extensions() {
+        let decode = |value| Opcode::decode(value, Platform::XoChip);
+        assert_eq!(decode(0xf000), Ok(Opcode::LoadLongI(0)));
+        assert_eq!(decode(0xf002), Ok(Opcode::LoadPattern));
+        assert_eq!(decode(0xf201), Ok(Opcode::SelectPlanes(2)));
+        assert_eq!(decode(0xf43a), Ok(Opcode::SetPitch(4)));
+        assert_eq!(decode(0x00d3), Ok(Opcode::ScrollUp(3)));
+        assert_eq!(decode(0x5122), Ok(Opcode::SaveRange(1, 2)));
+        assert_eq!(decode(0x5213), Ok(Opcode::LoadRange(2, 1)));
+        assert_eq!(decode(0x00fb), Ok(Opcode::ScrollRight));
+
+        assert_eq!(
+            Opcode::decode(0x5122, Platform::SuperChip),
+            Err(VmError::InvalidOpcode(0x5122))
+        );
+    }
+
+    #[test]
+    fn decodes_megachip_extensions() {
+        let decode = |value| Opcode::decode(value, Platform::MegaChip);
+        assert_eq!(decode(0x0010), Ok(Opcode::MegaMode(false)));
+        assert_eq!(decode(0x0011), Ok(Opcode::MegaMode(true)));
+        assert_eq!(decode(0x00b3), Ok(Opcode::ScrollUp(3)));
+        assert_eq!(decode(0x0112), Ok(Opcode::LoadLongI(0x12)));
+        assert_eq!(decode(0x0204), Ok(Opcode::LoadPalette(4)));
+        assert_eq!(decode(0x0310), Ok(Opcode::SpriteWidth(0x10)));
+        assert_eq!(decode(0x0420), Ok(Opcode::SpriteHeight(0x20)));
+        assert_eq!(decode(0x0580), Ok(Opcode::ScreenAlpha(0x80)));
+        assert_eq!(decode(0x0601), Ok(Opcode::PlaySound(1)));
+        assert_eq!(decode(0x0700), Ok(Opcode::StopSound));
+        assert_eq!(decode(0x0805), Ok(Opcode::BlendMode(5)));
+        assert_eq!(decode(0x09ff), Ok(Opcode::CollisionColor(0xff)));
+        assert_eq!(decode(0x00ff), Ok(Opcode::HighRes));
+    }
+}
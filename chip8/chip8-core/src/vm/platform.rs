@@ -0,0 +1,226 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::FontSet;
+
+/// The machines and interpreters CHIP-8 programs were written for, which
+/// differ in where programs are loaded, how much memory there is and the
+/// size of the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original interpreter of the COSMAC VIP. `0NNN` is ignored, like
+    /// modern interpreters do.
+    #[default]
+    Chip8,
+    /// CHIP-8 on the COSMAC VIP for hybrid programs, which call 1802 machine
+    /// code subroutines with `0NNN`.
+    HybridVip,
+    /// The 64x64 CHIP-8 variant for the COSMAC VIP, loaded at 0x2C0.
+    Chip8HiRes,
+    /// CHIP-8X, for the COSMAC VIP with the VP-590 colour board and a second
+    /// keypad. Programs start at 0x300.
+    Chip8X,
+    /// CHIP-8 on the ETI-660, with a 64x48 display and programs at 0x600.
+    Eti660,
+    /// SUPER-CHIP on the HP 48 calculators.
+    SuperChip,
+    /// Octo's XO-CHIP, with 64 KiB of memory, two bitplanes and audio
+    /// patterns.
+    XoChip,
+    /// MegaChip, which adds a 256x192 colour mode and 32 MiB of memory to
+    /// SUPER-CHIP.
+    MegaChip,
+}
+
+/// Where the COSMAC VIP interpreter keeps its state in memory, which machine
+/// code subroutines called with `0NNN` read and write directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VipMemoryMap {
+    /// Top of the stack, which R2 points to.
+    pub stack: u16,
+    /// Address of V0..VF.
+    pub registers: u16,
+    /// Address of the display, one bit per pixel.
+    pub display: u16,
+    /// Width and height of the display in memory, in pixels.
+    pub display_size: (usize, usize),
+}
+
+/// Why a ROM cannot be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    Empty,
+    TooLarge { size: usize, max_size: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "ROM is empty"),
+            Self::TooLarge { size, max_size } => write!(
+                f,
+                "ROM is too large: {} bytes, but at most {} fit in memory",
+                size, max_size
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomError {}
+
+/// Things that look wrong in a ROM, but that do not stop it from loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    /// Instructions are two bytes long, so the last byte is probably cut off
+    /// (or the ROM carries data at odd addresses).
+    OddLength,
+}
+
+impl fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OddLength => write!(f, "ROM has an odd number of bytes"),
+        }
+    }
+}
+
+impl Platform {
+    /// Address where the ROM is loaded and execution starts.
+    pub fn load_address(&self) -> u16 {
+        match self {
+            Self::Chip8 | Self::HybridVip | Self::SuperChip | Self::XoChip | Self::MegaChip => {
+                0x200
+            }
+            Self::Chip8HiRes => 0x2c0,
+            Self::Chip8X => 0x300,
+            Self::Eti660 => 0x600,
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::XoChip => 0x10000,
+            Self::MegaChip => 0x2000000,
+            _ => 0x1000,
+        }
+    }
+
+    pub fn max_rom_size(&self) -> usize {
+        self.memory_size() - self.load_address() as usize
+    }
+
+    /// Width and height of the display when the program starts.
+    pub fn display_size(&self) -> (usize, usize) {
+        match self {
+            Self::Chip8HiRes => (64, 64),
+            Self::Eti660 => (64, 48),
+            _ => (64, 32),
+        }
+    }
+
+    /// The font of the interpreter the platform stands for. Plain CHIP-8
+    /// keeps the font it always had.
+    pub fn font_set(&self) -> FontSet {
+        match self {
+            Self::Chip8 => FontSet::Chip8,
+            Self::HybridVip | Self::Chip8HiRes | Self::Chip8X => FontSet::Vip,
+            Self::Eti660 => FontSet::Eti660,
+            Self::SuperChip | Self::MegaChip => FontSet::SuperChip,
+            Self::XoChip => FontSet::Octo,
+        }
+    }
+
+    /// Whether the platform has the SUPER-CHIP instructions: scrolling,
+    /// the 128x64 mode, 16x16 sprites and the big font.
+    pub fn supports_superchip(&self) -> bool {
+        matches!(self, Self::SuperChip | Self::XoChip | Self::MegaChip)
+    }
+
+    /// Number of RPL user flags FX75 and FX85 can save and restore: 8 on the
+    /// HP 48, 16 on XO-CHIP, none on the VIP interpreters.
+    pub fn rpl_flag_count(&self) -> usize {
+        match self {
+            Self::SuperChip | Self::MegaChip => 8,
+            Self::XoChip => 16,
+            _ => 0,
+        }
+    }
+
+    /// Memory map of the VIP interpreter, on platforms that run 1802 machine
+    /// code subroutines. On the others, `0NNN` is ignored.
+    pub fn vip_memory_map(&self) -> Option<VipMemoryMap> {
+        match self {
+            Self::HybridVip | Self::Chip8X => Some(VipMemoryMap {
+                stack: 0xecf,
+                registers: 0xef0,
+                display: 0xf00,
+                display_size: (64, 32),
+            }),
+            // the 512 bytes of the 64x64 display take the page the stack
+            // and the registers are in on the VIP
+            Self::Chip8HiRes => Some(VipMemoryMap {
+                stack: 0xdcf,
+                registers: 0xdf0,
+                display: 0xe00,
+                display_size: (64, 64),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Checks that `rom` can be loaded, and returns what looks suspicious
+    /// about it.
+    pub fn validate_rom(&self, rom: &[u8]) -> Result<Vec<RomWarning>, RomError> {
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+
+        let max_size = self.max_rom_size();
+        if rom.len() > max_size {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max_size,
+            });
+        }
+
+        let mut warnings = Vec::new();
+        if rom.len() & 1 == 1 {
+            warnings.push(RomWarning::OddLength);
+        }
+
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn max_rom_size_depends_on_load_address() {
+        assert_eq!(Platform::Chip8.max_rom_size(), 3584);
+        assert_eq!(Platform::Eti660.max_rom_size(), 2560);
+        assert_eq!(Platform::Chip8HiRes.max_rom_size(), 3392);
+        assert_eq!(Platform::Chip8X.max_rom_size(), 3328);
+        assert_eq!(Platform::XoChip.max_rom_size(), 65024);
+    }
+
+    #[test]
+    fn validates_rom() {
+        assert_eq!(Platform::Chip8.validate_rom(&[]), Err(RomError::Empty));
+        assert_eq!(
+            Platform::Chip8.validate_rom(&[0; 3585]),
+            Err(RomError::TooLarge {
+                size: 3585,
+                max_size: 3584
+            })
+        );
+        assert_eq!(Platform::XoChip.validate_rom(&[0; 3586]), Ok(vec![]));
+        assert_eq!(
+            Platform::Chip8.validate_rom(&[0; 3]),
+            Ok(vec![RomWarning::OddLength])
+        );
+    }
+}
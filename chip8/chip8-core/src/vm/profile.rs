@@ -0,0 +1,277 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Instructions run by one subroutine, which is known by its address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Instructions of the subroutine itself.
+    pub exclusive: u64,
+    /// Instructions of the subroutine and everything it called.
+    pub inclusive: u64,
+    /// Most inclusive instructions in a single frame.
+    pub max_frame: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    addr: u16,
+    children: BTreeMap<u16, usize>,
+    instructions: u64,
+}
+
+/// Attributes instructions to the subroutines that ran them, following
+/// 2NNN and 00EE.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profiler {
+    /// Call tree, with the entry point at index 0.
+    nodes: Vec<Node>,
+    /// Nodes of the current call stack.
+    path: Vec<usize>,
+    functions: BTreeMap<u16, FunctionProfile>,
+    calls: BTreeMap<(u16, u16), u64>,
+    frame: BTreeMap<u16, u64>,
+    frames: u64,
+    instructions: u64,
+}
+
+impl Profiler {
+    /// A profiler that attributes the code outside of any subroutine to
+    /// `entry`.
+    pub fn new(entry: u16) -> Self {
+        let mut functions = BTreeMap::new();
+        functions.insert(entry, FunctionProfile::default());
+        Self {
+            nodes: vec![Node {
+                addr: entry,
+                children: BTreeMap::new(),
+                instructions: 0,
+            }],
+            path: vec![0],
+            functions,
+            calls: BTreeMap::new(),
+            frame: BTreeMap::new(),
+            frames: 0,
+            instructions: 0,
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Number of frames (timer ticks) profiled.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Subroutines by address.
+    pub fn functions(&self) -> impl Iterator<Item = (u16, &FunctionProfile)> {
+        self.functions
+            .iter()
+            .map(|(addr, profile)| (*addr, profile))
+    }
+
+    /// Caller, callee and number of calls of every edge of the call graph.
+    pub fn call_graph(&self) -> impl Iterator<Item = (u16, u16, u64)> + '_ {
+        self.calls
+            .iter()
+            .map(|((caller, callee), calls)| (*caller, *callee, *calls))
+    }
+
+    /// One line per call stack with the instructions run in it, such as
+    /// `0x0200;0x0250;0x02a0 120`, for flame graph tools.
+    pub fn to_collapsed(&self) -> String {
+        let mut text = String::new();
+        let mut stack = vec![(0, String::new())];
+        while let Some((index, prefix)) = stack.pop() {
+            let node = &self.nodes[index];
+            let mut name = prefix;
+            if !name.is_empty() {
+                name.push(';');
+            }
+            let _ = write!(name, "0x{:04x}", node.addr);
+            if node.instructions > 0 {
+                let _ = writeln!(text, "{} {}", name, node.instructions);
+            }
+            for child in node.children.values().rev() {
+                stack.push((*child, name.clone()));
+            }
+        }
+        text
+    }
+
+    /// A table of the subroutines, the busiest first, with their share of a
+    /// frame of `cycles_per_frame` instructions, then the call graph.
+    pub fn report(&self, cycles_per_frame: usize) -> String {
+        let mut functions: Vec<_> = self.functions().collect();
+        functions.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(&b.0)));
+
+        let frames = self.frames.max(1);
+        let budget = (cycles_per_frame as u64).max(1);
+        let mut text =
+            String::from("address    calls  exclusive  inclusive  avg/frame  max/frame  budget\n");
+        for (addr, profile) in functions {
+            let per_frame = profile.inclusive / frames;
+            let _ = writeln!(
+                text,
+                " 0x{:04x} {:>8} {:>10} {:>10} {:>10} {:>10} {:>6}%",
+                addr,
+                profile.calls,
+                profile.exclusive,
+                profile.inclusive,
+                per_frame,
+                profile.max_frame,
+                per_frame * 100 / budget
+            );
+        }
+
+        text.push_str("\ncaller  callee     calls\n");
+        for (caller, callee, calls) in self.call_graph() {
+            let _ = writeln!(text, "0x{:04x}  0x{:04x} {:>9}", caller, callee, calls);
+        }
+        text
+    }
+
+    /// Counts an instruction for the subroutines on the call stack. A
+    /// recursive subroutine counts it once.
+    pub(crate) fn record_instruction(&mut self) {
+        self.instructions += 1;
+        let current = self.path[self.path.len() - 1];
+        self.nodes[current].instructions += 1;
+
+        let nodes = &self.nodes;
+        for (depth, index) in self.path.iter().enumerate() {
+            let addr = nodes[*index].addr;
+            let profile = self.functions.entry(addr).or_default();
+            if depth == self.path.len() - 1 {
+                profile.exclusive += 1;
+            }
+            let outermost = self.path[..depth]
+                .iter()
+                .all(|outer| nodes[*outer].addr != addr);
+            if outermost {
+                profile.inclusive += 1;
+                *self.frame.entry(addr).or_default() += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_call(&mut self, addr: u16) {
+        let parent = self.path[self.path.len() - 1];
+        let caller = self.nodes[parent].addr;
+        let index = match self.nodes[parent].children.get(&addr) {
+            Some(index) => *index,
+            None => {
+                let index = self.nodes.len();
+                self.nodes.push(Node {
+                    addr,
+                    children: BTreeMap::new(),
+                    instructions: 0,
+                });
+                self.nodes[parent].children.insert(addr, index);
+                index
+            }
+        };
+        self.path.push(index);
+        self.functions.entry(addr).or_default().calls += 1;
+        *self.calls.entry((caller, addr)).or_default() += 1;
+    }
+
+    /// Leaves the current subroutine. Returns from calls made before the
+    /// profiler started are ignored.
+    pub(crate) fn record_return(&mut self) {
+        if self.path.len() > 1 {
+            self.path.pop();
+        }
+    }
+
+    pub(crate) fn record_frame(&mut self) {
+        self.frames += 1;
+        for (addr, instructions) in core::mem::take(&mut self.frame) {
+            let profile = self.functions.entry(addr).or_default();
+            profile.max_frame = profile.max_frame.max(instructions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> Profiler {
+        let mut profiler = Profiler::new(0x200);
+        profiler.record_instruction();
+        profiler.record_call(0x250);
+        profiler.record_instruction();
+        profiler.record_call(0x250);
+        profiler.record_instruction();
+        profiler.record_instruction();
+        profiler.record_return();
+        profiler.record_return();
+        profiler.record_frame();
+        profiler.record_instruction();
+        profiler.record_call(0x2a0);
+        profiler.record_instruction();
+        profiler.record_return();
+        profiler.record_frame();
+        profiler
+    }
+
+    #[test]
+    fn counts_inclusive_and_exclusive_instructions() {
+        let profiler = profile();
+        let functions: Vec<_> = profiler.functions().map(|(addr, p)| (addr, *p)).collect();
+        assert_eq!(
+            functions,
+            [
+                (
+                    0x200,
+                    FunctionProfile {
+                        calls: 0,
+                        exclusive: 2,
+                        inclusive: 6,
+                        max_frame: 4,
+                    }
+                ),
+                (
+                    0x250,
+                    FunctionProfile {
+                        calls: 2,
+                        exclusive: 3,
+                        inclusive: 3,
+                        max_frame: 3,
+                    }
+                ),
+                (
+                    0x2a0,
+                    FunctionProfile {
+                        calls: 1,
+                        exclusive: 1,
+                        inclusive: 1,
+                        max_frame: 1,
+                    }
+                ),
+            ]
+        );
+        let calls: Vec<_> = profiler.call_graph().collect();
+        assert_eq!(
+            calls,
+            [(0x200, 0x250, 1), (0x200, 0x2a0, 1), (0x250, 0x250, 1)]
+        );
+    }
+
+    #[test]
+    fn writes_collapsed_stacks() {
+        assert_eq!(
+            profile().to_collapsed(),
+            "0x0200 2\n\
+             0x0200;0x0250 1\n\
+             0x0200;0x0250;0x0250 2\n\
+             0x0200;0x02a0 1\n"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+/// Behaviours that differ between CHIP-8 interpreters, which ROMs may rely
+/// on. Named after the quirks of the community chip-8-database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VX in place, ignoring VY.
+    pub shift: bool,
+    /// FX55 and FX65 increase I by X instead of X + 1.
+    pub memory_increment_by_x: bool,
+    /// FX55 and FX65 leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped.
+    pub wrap: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// DXYN waits for the next frame before running more instructions.
+    pub vblank: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Quirks of the original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Self {
+        Self {
+            wrap: false,
+            vblank: true,
+            logic: true,
+            ..Self::default()
+        }
+    }
+
+    /// Quirks of SUPER-CHIP 1.1 on the HP 48.
+    pub fn superchip() -> Self {
+        Self {
+            shift: true,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: true,
+            ..Self::default()
+        }
+    }
+
+    /// How much FX55 and FX65 with register X increase I by.
+    pub(crate) fn memory_increment(&self, x: u8) -> u16 {
+        if self.memory_leave_i_unchanged {
+            0
+        } else if self.memory_increment_by_x {
+            x as u16
+        } else {
+            x as u16 + 1
+        }
+    }
+}
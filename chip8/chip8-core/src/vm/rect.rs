@@ -0,0 +1,50 @@
+/// Area of the display, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Smallest rectangle that contains both `self` and `other`.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_of_disjoint_rects() {
+        let a = Rect::new(1, 2, 3, 4);
+        let b = Rect::new(10, 0, 1, 1);
+
+        assert_eq!(a.union(&b), Rect::new(1, 0, 10, 6));
+    }
+
+    #[test]
+    fn union_with_contained_rect() {
+        let a = Rect::new(0, 0, 64, 32);
+        let b = Rect::new(10, 10, 1, 1);
+
+        assert_eq!(a.union(&b), a);
+    }
+}
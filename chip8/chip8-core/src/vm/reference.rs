@@ -0,0 +1,529 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::{
+    Beeper, Display, Framebuffer, Keypad, RandomSource, TraceEntry, Vm, VmError,
+    MAX_DISPLAY_HEIGHT, MAX_DISPLAY_WIDTH,
+};
+
+/// One instruction of a reference trace, with the display after it if it
+/// changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceStep {
+    pub entry: TraceEntry,
+    pub display: Option<Framebuffer>,
+}
+
+/// A trace recorded by a known good build, or converted from the log of
+/// another emulator, to check a ROM runs the same way instruction by
+/// instruction.
+///
+/// It is text: one line per instruction in the format of the tracer, each
+/// optionally followed by a `DISPLAY <width>x<height> <pixels>` line with
+/// the pixels as hex digits, 4 per digit and row after row.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReferenceTrace {
+    steps: Vec<ReferenceStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceError {
+    /// The line (counting from 1) could not be parsed.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "Invalid reference trace at line {}", line),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReferenceError {}
+
+/// What was different at the first divergent instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Pc {
+        expected: u16,
+        actual: u16,
+    },
+    Opcode {
+        expected: u16,
+        actual: u16,
+    },
+    Register {
+        index: u8,
+        expected: u8,
+        actual: u8,
+    },
+    I {
+        expected: u32,
+        actual: u32,
+    },
+    /// `None` means the byte was not written by the instruction.
+    Memory {
+        addr: u32,
+        expected: Option<u8>,
+        actual: Option<u8>,
+    },
+    DisplaySize {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    Pixel {
+        x: usize,
+        y: usize,
+        expected: bool,
+    },
+    /// The VM failed to run the instruction.
+    Error(VmError),
+    /// The VM waited for a key, so it could not go on.
+    Stalled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub pc: u16,
+    pub difference: Difference,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cycle {} at {:#06x}: ", self.cycle, self.pc)?;
+        match &self.difference {
+            Difference::Pc { expected, actual } => {
+                write!(f, "PC is {:#06x}, expected {:#06x}", actual, expected)
+            }
+            Difference::Opcode { expected, actual } => {
+                write!(f, "opcode is {:#06x}, expected {:#06x}", actual, expected)
+            }
+            Difference::Register {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "V{:X} is {:#04x}, expected {:#04x}",
+                index, actual, expected
+            ),
+            Difference::I { expected, actual } => {
+                write!(f, "I is {:#06x}, expected {:#06x}", actual, expected)
+            }
+            Difference::Memory {
+                addr,
+                expected,
+                actual,
+            } => {
+                write!(f, "memory at {:#06x} ", addr)?;
+                match actual {
+                    Some(value) => write!(f, "was set to {:#04x}", value)?,
+                    None => write!(f, "was not written")?,
+                }
+                match expected {
+                    Some(value) => write!(f, ", expected {:#04x}", value),
+                    None => write!(f, ", expected no write"),
+                }
+            }
+            Difference::DisplaySize { expected, actual } => write!(
+                f,
+                "display is {}x{}, expected {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Difference::Pixel { x, y, expected } => write!(
+                f,
+                "pixel ({}, {}) is {}, expected {}",
+                x,
+                y,
+                if *expected { "off" } else { "on" },
+                if *expected { "on" } else { "off" }
+            ),
+            Difference::Error(err) => write!(f, "{}", err),
+            Difference::Stalled => write!(f, "waiting for a key"),
+        }
+    }
+}
+
+impl ReferenceTrace {
+    pub fn parse(text: &str) -> Result<Self, ReferenceError> {
+        let mut steps: Vec<ReferenceStep> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = ReferenceError::InvalidLine(n + 1);
+            if let Some(pixels) = line.strip_prefix("DISPLAY ") {
+                let display = parse_display(pixels).ok_or(error)?;
+                steps.last_mut().ok_or(error)?.display = Some(display);
+            } else {
+                let entry = parse_entry(line).ok_or(error)?;
+                steps.push(ReferenceStep {
+                    entry,
+                    display: None,
+                });
+            }
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// A trace of steps built by hand, to convert the log of another
+    /// emulator. Only the PC, opcode, V registers, I and writes of the
+    /// entries are compared.
+    pub fn from_steps(steps: Vec<ReferenceStep>) -> Self {
+        Self { steps }
+    }
+
+    pub fn steps(&self) -> &[ReferenceStep] {
+        &self.steps
+    }
+
+    /// Records `steps` instructions of `vm`, ticking the timers every
+    /// `cycles_per_frame` instructions. Stops early if the VM fails or
+    /// waits for a key.
+    pub fn record<R, D, K, B>(
+        vm: &mut Vm<R, D, K, B>,
+        steps: usize,
+        cycles_per_frame: usize,
+    ) -> Self
+    where
+        R: RandomSource,
+        D: Display,
+        K: Keypad,
+        B: Beeper,
+    {
+        let mut trace = Self::default();
+        for _ in 0..steps {
+            match run_step(vm, cycles_per_frame) {
+                Ok(step) => trace.steps.push(step),
+                Err(_) => break,
+            }
+        }
+        trace
+    }
+
+    /// Runs `vm` along the trace, with the same timing as `record`, and
+    /// returns the first instruction that did something else.
+    pub fn compare<R, D, K, B>(
+        &self,
+        vm: &mut Vm<R, D, K, B>,
+        cycles_per_frame: usize,
+    ) -> Option<Divergence>
+    where
+        R: RandomSource,
+        D: Display,
+        K: Keypad,
+        B: Beeper,
+    {
+        for expected in &self.steps {
+            let (cycle, pc) = (vm.cycles(), vm.pc);
+            let diverge = |difference| {
+                Some(Divergence {
+                    cycle,
+                    pc,
+                    difference,
+                })
+            };
+
+            let actual = match run_step(vm, cycles_per_frame) {
+                Ok(step) => step,
+                Err(difference) => return diverge(difference),
+            };
+            if let Some(difference) = compare_step(expected, &actual, &vm.display) {
+                return diverge(difference);
+            }
+        }
+        None
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for step in &self.steps {
+            let _ = writeln!(text, "{}", step.entry);
+            if let Some(display) = &step.display {
+                let _ = writeln!(text, "DISPLAY {}", format_display(display));
+            }
+        }
+        text
+    }
+}
+
+/// Runs one instruction with tracing on, and the display if it changed.
+fn run_step<R, D, K, B>(
+    vm: &mut Vm<R, D, K, B>,
+    cycles_per_frame: usize,
+) -> Result<ReferenceStep, Difference>
+where
+    R: RandomSource,
+    D: Display,
+    K: Keypad,
+    B: Beeper,
+{
+    let tracer = vm.disable_tracing();
+    vm.enable_tracing(1);
+
+    let cycles = vm.cycles();
+    let frame_cycle = cycles % cycles_per_frame.max(1) as u64;
+    if cycles > 0 && frame_cycle == 0 {
+        vm.tick_timers();
+    }
+    let mut res = vm.tick();
+    if res.is_ok() && vm.cycles() == cycles {
+        // waiting for the next frame to draw
+        vm.tick_timers();
+        res = vm.tick();
+    }
+
+    let entry = vm
+        .disable_tracing()
+        .and_then(|t| t.entries().next().cloned());
+    vm.tracer = tracer;
+    res.map_err(Difference::Error)?;
+
+    Ok(ReferenceStep {
+        entry: entry.ok_or(Difference::Stalled)?,
+        display: vm.take_dirty_rect().map(|_| vm.display.clone()),
+    })
+}
+
+fn compare_step(
+    expected: &ReferenceStep,
+    actual: &ReferenceStep,
+    display: &Framebuffer,
+) -> Option<Difference> {
+    let (e, a) = (&expected.entry, &actual.entry);
+    if e.pc != a.pc {
+        return Some(Difference::Pc {
+            expected: e.pc,
+            actual: a.pc,
+        });
+    }
+    if e.raw_opcode != a.raw_opcode {
+        return Some(Difference::Opcode {
+            expected: e.raw_opcode,
+            actual: a.raw_opcode,
+        });
+    }
+    for index in 0..16 {
+        let (expected, actual) = (e.v_registers[index], a.v_registers[index]);
+        if expected != actual {
+            return Some(Difference::Register {
+                index: index as u8,
+                expected,
+                actual,
+            });
+        }
+    }
+    if e.i != a.i {
+        return Some(Difference::I {
+            expected: e.i,
+            actual: a.i,
+        });
+    }
+    if let Some(difference) = compare_writes(&e.writes, &a.writes) {
+        return Some(difference);
+    }
+
+    let expected = expected.display.as_ref()?;
+    let (width, height) = (expected.width(), expected.height());
+    if (width, height) != (display.width(), display.height()) {
+        return Some(Difference::DisplaySize {
+            expected: (width, height),
+            actual: (display.width(), display.height()),
+        });
+    }
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .find(|&(x, y)| expected.get(x, y) != display.get(x, y))
+        .map(|(x, y)| Difference::Pixel {
+            x,
+            y,
+            expected: expected.get(x, y),
+        })
+}
+
+/// Compares the last value written to each address.
+fn compare_writes(expected: &[(u32, u8)], actual: &[(u32, u8)]) -> Option<Difference> {
+    let last = |writes: &[(u32, u8)], addr| {
+        writes
+            .iter()
+            .rev()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, value)| *value)
+    };
+
+    expected
+        .iter()
+        .chain(actual)
+        .map(|(addr, _)| (*addr, last(expected, *addr), last(actual, *addr)))
+        .find(|(_, expected, actual)| expected != actual)
+        .map(|(addr, expected, actual)| Difference::Memory {
+            addr,
+            expected,
+            actual,
+        })
+}
+
+fn parse_entry(line: &str) -> Option<TraceEntry> {
+    let mut tokens = line.split_whitespace();
+    let hex = |token: Option<&str>, prefix: &str| {
+        u32::from_str_radix(token?.strip_prefix(prefix)?, 16).ok()
+    };
+
+    let cycle = tokens.next()?.parse().ok()?;
+    let pc = hex(tokens.next(), "PC:")? as u16;
+    let raw_opcode = hex(tokens.next(), "OP:")? as u16;
+    let i = hex(tokens.next(), "I:")?;
+    let mut v_registers = [0; 16];
+    for (n, v) in v_registers.iter_mut().enumerate() {
+        *v = hex(tokens.next(), if n == 0 { "V:" } else { "" })? as u8;
+    }
+
+    let writes = tokens
+        .map(|token| {
+            let (addr, value) = token.strip_prefix('[')?.split_once("]=")?;
+            let addr = u32::from_str_radix(addr, 16).ok()?;
+            Some((addr, u8::from_str_radix(value, 16).ok()?))
+        })
+        .collect::<Option<_>>()?;
+
+    Some(TraceEntry {
+        cycle,
+        pc,
+        raw_opcode,
+        opcode: None,
+        v_registers,
+        changed_registers: 0,
+        i,
+        writes,
+    })
+}
+
+fn parse_display(text: &str) -> Option<Framebuffer> {
+    let (size, pixels) = text.split_once(' ')?;
+    let (width, height) = size.split_once('x')?;
+    let (width, height): (usize, usize) = (width.parse().ok()?, height.parse().ok()?);
+    if width > MAX_DISPLAY_WIDTH || height > MAX_DISPLAY_HEIGHT || width % 4 != 0 {
+        return None;
+    }
+    if pixels.len() != width * height / 4 {
+        return None;
+    }
+
+    let mut display = Framebuffer::new(width, height);
+    for (n, digit) in pixels.chars().enumerate() {
+        let nibble = digit.to_digit(16)?;
+        for bit in 0..4 {
+            let pixel = n * 4 + bit;
+            display.set(pixel % width, pixel / width, nibble & (8 >> bit) != 0);
+        }
+    }
+    Some(display)
+}
+
+fn format_display(display: &Framebuffer) -> String {
+    let mut text = String::new();
+    let _ = write!(text, "{}x{} ", display.width(), display.height());
+    let pixels: Vec<bool> = display.pixels().collect();
+    for nibble in pixels.chunks(4) {
+        let value = nibble.iter().fold(0, |value, on| value << 1 | *on as u32);
+        let _ = write!(text, "{:X}", value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    const ROM: [u8; 11] = [
+        0x60, 0x05, // V0 = 5
+        0xa2, 0x0a, // I = sprite
+        0xd0, 0x01, // draw it at 5, 5
+        0xf0, 0x33, // BCD of V0 at I
+        0x12, 0x08, // loop
+        0x80,
+    ];
+
+    fn vm() -> Vm<fn() -> u8> {
+        Vm::new(&ROM, || 0)
+    }
+
+    #[test]
+    fn round_trips_recorded_traces() {
+        let trace = ReferenceTrace::record(&mut vm(), 5, 10);
+        let parsed = ReferenceTrace::parse(&trace.to_text()).unwrap();
+
+        assert_eq!(parsed.steps().len(), 5);
+        assert_eq!(
+            parsed.steps()[3].entry.writes,
+            [(0x20a, 0), (0x20b, 0), (0x20c, 5)]
+        );
+        assert!(parsed.steps()[2].display.as_ref().unwrap().get(5, 5));
+        assert_eq!(parsed.compare(&mut vm(), 10), None);
+    }
+
+    #[test]
+    fn reports_first_divergence() {
+        let mut text = ReferenceTrace::record(&mut vm(), 5, 10).to_text();
+        text = text.replace("[020C]=05", "[020C]=06");
+        let trace = ReferenceTrace::parse(&text).unwrap();
+
+        let divergence = trace.compare(&mut vm(), 10).unwrap();
+        assert_eq!(divergence.cycle, 3);
+        assert_eq!(
+            divergence.difference,
+            Difference::Memory {
+                addr: 0x20c,
+                expected: Some(6),
+                actual: Some(5),
+            }
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "Cycle 3 at 0x0206: memory at 0x020c was set to 0x05, expected 0x06"
+        );
+    }
+
+    #[test]
+    fn reports_pixels_that_differ() {
+        let mut trace = ReferenceTrace::record(&mut vm(), 3, 10);
+        trace.steps[2].display.as_mut().unwrap().set(6, 5, true);
+
+        let divergence = trace.compare(&mut vm(), 10).unwrap();
+        assert_eq!(
+            divergence.difference,
+            Difference::Pixel {
+                x: 6,
+                y: 5,
+                expected: true
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert_eq!(
+            ReferenceTrace::parse("DISPLAY 4x1 F"),
+            Err(ReferenceError::InvalidLine(1))
+        );
+        assert_eq!(
+            ReferenceTrace::parse("000000 PC:0200 OP:6005\n"),
+            Err(ReferenceError::InvalidLine(1))
+        );
+        assert_eq!(
+            ReferenceTrace::parse(
+                "000000 PC:0200 OP:6005 I:0000 \
+                 V:05 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 ; LoadVx(0, 5)"
+            ),
+            Err(ReferenceError::InvalidLine(1))
+        );
+    }
+}
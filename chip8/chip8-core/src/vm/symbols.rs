@@ -0,0 +1,164 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use super::VmError;
+
+/// Names of addresses in a ROM, such as its labels, to show instead of raw
+/// addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Symbols {
+    names: BTreeMap<u32, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolError {
+    /// The line (counting from 1) could not be parsed.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "Invalid symbol at line {}", line),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SymbolError {}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a symbol file with an address in hex (`2a4`, `0x2a4` or
+    /// `$2a4`) and a name per line. Empty lines and lines starting with `#`
+    /// or `;` are skipped.
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Self::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let error = SymbolError::InvalidLine(n + 1);
+            let (addr, name) = line.split_once(char::is_whitespace).ok_or(error)?;
+            let addr = addr
+                .strip_prefix("0x")
+                .or_else(|| addr.strip_prefix("0X"))
+                .or_else(|| addr.strip_prefix('$'))
+                .unwrap_or(addr);
+            let addr = u32::from_str_radix(addr, 16).map_err(|_| error)?;
+            symbols.insert(addr, name.trim());
+        }
+        Ok(symbols)
+    }
+
+    /// Names `addr`, unless it already has a name.
+    pub fn insert(&mut self, addr: u32, name: &str) {
+        self.names.entry(addr).or_insert_with(|| name.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The symbols by address.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.names.iter().map(|(addr, name)| (*addr, name.as_str()))
+    }
+
+    /// The name of `addr` itself.
+    pub fn name(&self, addr: u32) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    /// The closest symbol at or before `addr`, with the offset from it.
+    pub fn lookup(&self, addr: u32) -> Option<(&str, u32)> {
+        self.names
+            .range(..=addr)
+            .next_back()
+            .map(|(start, name)| (name.as_str(), addr - start))
+    }
+
+    /// `addr` in hex, followed by its symbol if it has one: `0x02a4`,
+    /// `0x02a4 (draw)` or `0x02a6 (draw+2)`.
+    pub fn format_address(&self, addr: u32) -> String {
+        match self.lookup(addr) {
+            Some((name, 0)) => format!("{:#06x} ({})", addr, name),
+            Some((name, offset)) => format!("{:#06x} ({}+{})", addr, name, offset),
+            None => format!("{:#06x}", addr),
+        }
+    }
+
+    /// Like the error's message, with the names of the addresses in it.
+    /// `pc` is the PC after the error.
+    pub fn describe_error(&self, error: &VmError, pc: u16) -> String {
+        match error {
+            VmError::InvalidAddress(addr) => {
+                format!("Invalid address: {}", self.format_address(*addr))
+            }
+            VmError::InvalidOpcode(opcode) => format!(
+                "Invalid opcode: {:#06x} at {}",
+                opcode,
+                self.format_address(pc.wrapping_sub(2) as u32)
+            ),
+            _ => error.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const SYMBOLS: &str = "\
+        # labels\n\
+        0x200 main\n\
+        2a4 draw_player\n\
+        \n\
+        $300 sprites\n\
+        0x200 start\n";
+
+    #[test]
+    fn parses_symbol_file() {
+        let symbols = Symbols::parse(SYMBOLS).unwrap();
+        let names: Vec<_> = symbols.iter().collect();
+        assert_eq!(
+            names,
+            [(0x200, "main"), (0x2a4, "draw_player"), (0x300, "sprites")]
+        );
+        assert_eq!(
+            Symbols::parse("0x200 main\nmain"),
+            Err(SymbolError::InvalidLine(2))
+        );
+        assert_eq!(
+            Symbols::parse("main 0x200"),
+            Err(SymbolError::InvalidLine(1))
+        );
+    }
+
+    #[test]
+    fn formats_addresses() {
+        let symbols = Symbols::parse(SYMBOLS).unwrap();
+        assert_eq!(symbols.format_address(0x100), "0x0100");
+        assert_eq!(symbols.format_address(0x2a4), "0x02a4 (draw_player)");
+        assert_eq!(symbols.format_address(0x2a6), "0x02a6 (draw_player+2)");
+        assert_eq!(
+            symbols.describe_error(&VmError::InvalidOpcode(0xffff), 0x208),
+            "Invalid opcode: 0xffff at 0x0206 (main+6)"
+        );
+        assert_eq!(
+            symbols.describe_error(&VmError::InvalidAddress(0x1000), 0x208),
+            "Invalid address: 0x1000 (sprites+3328)"
+        );
+        assert_eq!(
+            symbols.describe_error(&VmError::EmptyStack, 0x208),
+            "Stack is empty"
+        );
+    }
+}
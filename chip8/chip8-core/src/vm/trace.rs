@@ -0,0 +1,196 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use super::Opcode;
+
+/// What one instruction did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// Number of instructions the VM ran before this one.
+    pub cycle: u64,
+    pub pc: u16,
+    pub raw_opcode: u16,
+    /// `None` if the opcode could not be decoded.
+    pub opcode: Option<Opcode>,
+    /// V registers after the instruction.
+    pub v_registers: [u8; 16],
+    /// Bit N is set if VN changed.
+    pub changed_registers: u16,
+    /// I after the instruction.
+    pub i: u32,
+    /// Bytes written to memory, in order.
+    pub writes: Vec<(u32, u8)>,
+}
+
+impl TraceEntry {
+    /// The V registers that changed, with their new values.
+    pub fn register_deltas(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        (0..16u8)
+            .filter(move |n| self.changed_registers & (1 << n) != 0)
+            .map(move |n| (n, self.v_registers[n as usize]))
+    }
+
+    /// Appends the entry as a binary record: the cycle (8 bytes), PC (2),
+    /// opcode (2) and I (4), then the number of changed registers and
+    /// their index and value, then the number of writes and their address
+    /// (4) and value. Numbers are little endian.
+    pub fn write_binary(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.cycle.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.raw_opcode.to_le_bytes());
+        out.extend_from_slice(&self.i.to_le_bytes());
+
+        out.push(self.changed_registers.count_ones() as u8);
+        for (n, value) in self.register_deltas() {
+            out.extend_from_slice(&[n, value]);
+        }
+
+        out.push(self.writes.len() as u8);
+        for (addr, value) in &self.writes {
+            out.extend_from_slice(&addr.to_le_bytes());
+            out.push(*value);
+        }
+    }
+}
+
+/// One line per instruction with the whole register state after it and the
+/// bytes it wrote:
+///
+/// `000042 PC:0208 OP:6A02 I:0300 V:00 00 .. 00 [0300]=02`
+///
+/// The format is this crate's own, which `ReferenceTrace` reads back. Logs
+/// of other emulators have to be converted first, see
+/// `ReferenceTrace::from_steps`.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:06} PC:{:04X} OP:{:04X} I:{:04X} V:",
+            self.cycle, self.pc, self.raw_opcode, self.i
+        )?;
+        for (n, value) in self.v_registers.iter().enumerate() {
+            write!(f, "{}{:02X}", if n == 0 { "" } else { " " }, value)?;
+        }
+        for (addr, value) in &self.writes {
+            write!(f, " [{:04X}]={:02X}", addr, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the last instructions the VM ran, for when a ROM misbehaves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracer {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+    writes: Vec<(u32, u8)>,
+}
+
+impl Tracer {
+    /// A tracer that keeps the last `capacity` instructions.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            writes: Vec::new(),
+        }
+    }
+
+    /// The recorded instructions, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(text, "{}", entry);
+        }
+        text
+    }
+
+    /// The recorded instructions as binary records, see
+    /// `TraceEntry::write_binary`.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for entry in &self.entries {
+            entry.write_binary(&mut data);
+        }
+        data
+    }
+
+    pub(crate) fn record_write(&mut self, addr: u32, value: u8) {
+        self.writes.push((addr, value));
+    }
+
+    /// Adds an instruction, with the memory writes recorded since the last
+    /// one.
+    pub(crate) fn record(&mut self, mut entry: TraceEntry) {
+        entry.writes = core::mem::take(&mut self.writes);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(cycle: u64) -> TraceEntry {
+        let mut v_registers = [0; 16];
+        v_registers[0xa] = 0x02;
+        TraceEntry {
+            cycle,
+            pc: 0x208,
+            raw_opcode: 0x6a02,
+            opcode: Some(Opcode::LoadVx(0xa, 0x02)),
+            v_registers,
+            changed_registers: 1 << 0xa,
+            i: 0x300,
+            writes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_last_entries() {
+        let mut tracer = Tracer::new(2);
+        for cycle in 0..3 {
+            tracer.record(entry(cycle));
+        }
+        let cycles: Vec<_> = tracer.entries().map(|e| e.cycle).collect();
+        assert_eq!(cycles, [1, 2]);
+    }
+
+    #[test]
+    fn formats_entries() {
+        let mut tracer = Tracer::new(1);
+        tracer.record_write(0x300, 0x02);
+        tracer.record(entry(42));
+
+        assert_eq!(
+            tracer.to_text(),
+            "000042 PC:0208 OP:6A02 I:0300 \
+             V:00 00 00 00 00 00 00 00 00 00 02 00 00 00 00 00 \
+             [0300]=02\n"
+        );
+        assert_eq!(
+            tracer.to_binary(),
+            [
+                42, 0, 0, 0, 0, 0, 0, 0, 0x08, 0x02, 0x02, 0x6a, 0x00, 0x03, 0, 0, //
+                1, 0xa, 0x02, //
+                1, 0x00, 0x03, 0, 0, 0x02,
+            ]
+        );
+    }
+}
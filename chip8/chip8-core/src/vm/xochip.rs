@@ -0,0 +1,78 @@
+use super::Framebuffer;
+
+/// Bytes in the audio pattern loaded by F002, one bit per sample.
+pub const PATTERN_LEN: usize = 16;
+
+/// Pitch the buzzer starts at, which plays the pattern at 4000 bits per
+/// second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// State of the XO-CHIP extensions: the second bitplane, the planes that
+/// drawing, clearing and scrolling act on (FN01), and the audio pattern
+/// (F002) and pitch (FX3A) of the buzzer.
+///
+/// The first plane is `Vm::display`, so programs that never select another
+/// plane draw like on SUPER-CHIP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XoChip {
+    plane: Framebuffer,
+    selected: u8,
+    pattern: Option<[u8; PATTERN_LEN]>,
+    pitch: u8,
+}
+
+impl Default for XoChip {
+    fn default() -> Self {
+        Self {
+            plane: Framebuffer::default(),
+            selected: 1,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+impl XoChip {
+    /// The second bitplane.
+    pub fn plane(&self) -> &Framebuffer {
+        &self.plane
+    }
+
+    /// Bit N is set if plane N + 1 is selected.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+
+    /// The audio pattern, most significant bit first, or `None` until the
+    /// program loads one; until then hosts play their usual buzzer tone.
+    pub fn pattern(&self) -> Option<&[u8; PATTERN_LEN]> {
+        self.pattern.as_ref()
+    }
+
+    /// Pitch of the buzzer: the pattern plays at
+    /// `4000 * 2 ^ ((pitch - 64) / 48)` bits per second.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub(crate) fn plane_mut(&mut self) -> &mut Framebuffer {
+        &mut self.plane
+    }
+
+    pub(crate) fn select_planes(&mut self, planes: u8) {
+        self.selected = planes & 0x3;
+    }
+
+    pub(crate) fn set_pattern(&mut self, pattern: [u8; PATTERN_LEN]) {
+        self.pattern = Some(pattern);
+    }
+
+    pub(crate) fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    /// Resizes the second plane along with the first, which blanks it.
+    pub(crate) fn set_resolution(&mut self, width: usize, height: usize) {
+        self.plane = Framebuffer::new(width, height);
+    }
+}
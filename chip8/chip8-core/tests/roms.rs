@@ -0,0 +1,639 @@
+//! Runs test ROMs through `Vm` for a number of frames and compares the
+//! display with the golden images in `tests/golden`, one text line per row.
+//!
+//! The `LOGO` ... `KEYPAD` ROMs are small hand-assembled versions of the
+//! community test ROMs, so their images only catch regressions. The
+//! community ROMs themselves (the IBM logo and Timendus' corax+, flags,
+//! quirks and keypad tests) run when `CHIP8_TEST_SUITE` names the `bin`
+//! directory of <https://github.com/Timendus/chip8-test-suite>, and are
+//! compared with the images in `tests/golden/suite`.
+//! Run with `UPDATE_GOLDEN=1` to write the images again after an intended
+//! change, and check the new ones by eye; check those of the suite against
+//! the same ROMs in a reference emulator such as Octo.
+
+use std::{env, fs};
+
+use chip8_core::{Platform, Quirks, Vm};
+
+const CYCLES_PER_FRAME: usize = 16;
+
+/// A key pressed (or released) at the start of a frame.
+struct KeyEvent {
+    frame: usize,
+    key: u8,
+    pressed: bool,
+}
+
+fn run(platform: Platform, quirks: Quirks, rom: &[u8], frames: usize, keys: &[KeyEvent]) -> String {
+    let mut vm = Vm::for_platform(platform, rom, || 0);
+    vm.set_quirks(quirks);
+
+    for frame in 0..frames {
+        for event in keys.iter().filter(|event| event.frame == frame) {
+            vm.set_key(event.key, event.pressed).unwrap();
+        }
+        vm.tick_timers();
+        for _ in 0..CYCLES_PER_FRAME {
+            vm.tick().unwrap();
+        }
+    }
+
+    let mut image = String::new();
+    for y in 0..vm.display.height() {
+        for x in 0..vm.display.width() {
+            image.push(if vm.display.get(x, y) { '#' } else { '.' });
+        }
+        image.push('\n');
+    }
+    image
+}
+
+fn assert_golden(name: &str, image: &str) {
+    let path = format!("{}/tests/golden/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        let dir = std::path::Path::new(&path).parent().unwrap();
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&path, image).unwrap();
+        return;
+    }
+
+    let golden = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "missing golden image {} ({}); run with UPDATE_GOLDEN=1 to create it:\n{}",
+            path, err, image
+        )
+    });
+    assert!(
+        golden == image,
+        "{} does not match {}:\n{}",
+        name,
+        path,
+        image
+    );
+}
+
+#[test]
+fn draws_logo() {
+    let image = run(Platform::Chip8, Quirks::default(), &LOGO, 10, &[]);
+    assert_golden("logo", &image);
+}
+
+#[test]
+fn passes_opcode_test() {
+    let image = run(Platform::Chip8, Quirks::default(), &OPCODES, 20, &[]);
+    assert_golden("opcodes", &image);
+}
+
+#[test]
+fn passes_flags_test() {
+    let image = run(Platform::Chip8, Quirks::default(), &FLAGS, 20, &[]);
+    assert_golden("flags", &image);
+}
+
+#[test]
+fn detects_quirks() {
+    let platforms = [
+        ("quirks_default", Platform::Chip8, Quirks::default()),
+        ("quirks_vip", Platform::Chip8, Quirks::cosmac_vip()),
+        ("quirks_superchip", Platform::SuperChip, Quirks::superchip()),
+    ];
+    for (name, platform, quirks) in platforms {
+        let image = run(platform, quirks, &QUIRKS, 30, &[]);
+        assert_golden(name, &image);
+    }
+}
+
+#[test]
+fn reads_keypad() {
+    let keys = [
+        KeyEvent {
+            frame: 2,
+            key: 0x5,
+            pressed: true,
+        },
+        KeyEvent {
+            frame: 4,
+            key: 0x5,
+            pressed: false,
+        },
+        KeyEvent {
+            frame: 6,
+            key: 0xa,
+            pressed: true,
+        },
+        KeyEvent {
+            frame: 8,
+            key: 0xa,
+            pressed: false,
+        },
+        KeyEvent {
+            frame: 10,
+            key: 0x0,
+            pressed: true,
+        },
+        KeyEvent {
+            frame: 12,
+            key: 0x0,
+            pressed: false,
+        },
+        KeyEvent {
+            frame: 14,
+            key: 0xf,
+            pressed: true,
+        },
+    ];
+    let image = run(Platform::Chip8, Quirks::default(), &KEYPAD, 20, &keys);
+    assert_golden("keypad", &image);
+}
+
+/// Reads a ROM of Timendus' test suite, or returns `None` when
+/// `CHIP8_TEST_SUITE` is not set.
+fn suite_rom(file: &str) -> Option<Vec<u8>> {
+    let dir = match env::var_os("CHIP8_TEST_SUITE") {
+        Some(dir) => dir,
+        None => {
+            eprintln!("skipping {}: CHIP8_TEST_SUITE is not set", file);
+            return None;
+        }
+    };
+    let path = std::path::Path::new(&dir).join(file);
+    Some(fs::read(&path).unwrap_or_else(|err| panic!("cannot read {:?}: {}", path, err)))
+}
+
+/// Presses `key` for `frames` frames.
+fn press(frame: usize, key: u8, frames: usize) -> Vec<KeyEvent> {
+    vec![
+        KeyEvent {
+            frame,
+            key,
+            pressed: true,
+        },
+        KeyEvent {
+            frame: frame + frames,
+            key,
+            pressed: false,
+        },
+    ]
+}
+
+#[test]
+fn suite_draws_ibm_logo() {
+    if let Some(rom) = suite_rom("2-ibm-logo.ch8") {
+        let image = run(Platform::Chip8, Quirks::default(), &rom, 20, &[]);
+        assert_golden("suite/ibm_logo", &image);
+    }
+}
+
+#[test]
+fn suite_passes_corax_test() {
+    if let Some(rom) = suite_rom("3-corax+.ch8") {
+        let image = run(Platform::Chip8, Quirks::default(), &rom, 60, &[]);
+        assert_golden("suite/corax", &image);
+    }
+}
+
+#[test]
+fn suite_passes_flags_test() {
+    if let Some(rom) = suite_rom("4-flags.ch8") {
+        let image = run(Platform::Chip8, Quirks::default(), &rom, 200, &[]);
+        assert_golden("suite/flags", &image);
+    }
+}
+
+#[test]
+fn suite_detects_quirks() {
+    let rom = match suite_rom("5-quirks.ch8") {
+        Some(rom) => rom,
+        None => return,
+    };
+
+    // the menu asks for the platform: 1 for CHIP-8, 2 then 1 for modern
+    // SUPER-CHIP
+    let chip8 = press(5, 0x1, 3);
+    let image = run(Platform::Chip8, Quirks::cosmac_vip(), &rom, 400, &chip8);
+    assert_golden("suite/quirks_chip8", &image);
+
+    let mut superchip = press(5, 0x2, 3);
+    superchip.extend(press(15, 0x1, 3));
+    let image = run(
+        Platform::SuperChip,
+        Quirks::superchip(),
+        &rom,
+        400,
+        &superchip,
+    );
+    assert_golden("suite/quirks_superchip", &image);
+}
+
+#[test]
+fn suite_reads_keypad() {
+    if let Some(rom) = suite_rom("6-keypad.ch8") {
+        // 3 picks the FX0A test, which passes once a key is pressed and
+        // released
+        let mut keys = press(5, 0x3, 3);
+        keys.extend(press(20, 0x5, 5));
+        let image = run(Platform::Chip8, Quirks::default(), &rom, 40, &keys);
+        assert_golden("suite/keypad", &image);
+    }
+}
+
+/// Draws "CHIP8" in the middle of the screen, like the IBM logo ROM.
+#[rustfmt::skip]
+const LOGO: [u8; 63] = [
+    0x00, 0xe0, // clear
+    0x60, 0x08, // V0 = 0x08
+    0x61, 0x0c, // V1 = 0x0c
+    0xa2, 0x26, // I = c
+    0xd0, 0x15, // draw it at V0, V1
+    0x70, 0x0a, // V0 += 10
+    0xa2, 0x2b, // I = h
+    0xd0, 0x15, // draw it at V0, V1
+    0x70, 0x0a, // V0 += 10
+    0xa2, 0x30, // I = i
+    0xd0, 0x15, // draw it at V0, V1
+    0x70, 0x0a, // V0 += 10
+    0xa2, 0x35, // I = p
+    0xd0, 0x15, // draw it at V0, V1
+    0x70, 0x0a, // V0 += 10
+    0xa2, 0x3a, // I = eight
+    0xd0, 0x15, // draw it at V0, V1
+    0x70, 0x0a, // V0 += 10
+    0x12, 0x24, // loop
+    0xfe, 0xc0, 0xc0, 0xc0, 0xfe, // C
+    0xc6, 0xc6, 0xfe, 0xc6, 0xc6, // H
+    0x7c, 0x38, 0x38, 0x38, 0x7c, // I
+    0xfc, 0xc6, 0xfc, 0xc0, 0xc0, // P
+    0x7c, 0xc6, 0x7c, 0xc6, 0x7c, // 8
+];
+
+/// Checks the result of each instruction, like the corax+ opcode test.
+/// Draws a tick for each passed check and a cross for each failed one.
+#[rustfmt::skip]
+const OPCODES: [u8; 288] = [
+    0x00, 0xe0, // clear
+    0x6d, 0x01, // VD = 1
+    0x6e, 0x01, // VE = 1
+    0x60, 0x42, // V0 = 0x42
+    0x6c, 0x00, // 6XNN: VC = 0
+    0x40, 0x42, // skip if V0 != 0x42
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0xff, // V0 = 0xff
+    0x70, 0x02, // V0 += 2
+    0x6c, 0x00, // 7XNN: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x61, 0x33, // V1 = 0x33
+    0x80, 0x10, // V0 = V1
+    0x6c, 0x00, // 8XY0: VC = 0
+    0x40, 0x33, // skip if V0 != 0x33
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x0f, // V0 = 0x0f
+    0x61, 0xf0, // V1 = 0xf0
+    0x80, 0x11, // V0 |= V1
+    0x6c, 0x00, // 8XY1: VC = 0
+    0x40, 0xff, // skip if V0 != 0xff
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x3c, // V0 = 0x3c
+    0x61, 0x0f, // V1 = 0x0f
+    0x80, 0x12, // V0 &= V1
+    0x6c, 0x00, // 8XY2: VC = 0
+    0x40, 0x0c, // skip if V0 != 0x0c
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x3c, // V0 = 0x3c
+    0x80, 0x13, // V0 ^= V1
+    0x6c, 0x00, // 8XY3: VC = 0
+    0x40, 0x33, // skip if V0 != 0x33
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0xf0, // V0 = 0xf0
+    0x61, 0x20, // V1 = 0x20
+    0x80, 0x14, // V0 += V1
+    0x6c, 0x00, // 8XY4: VC = 0
+    0x40, 0x10, // skip if V0 != 0x10
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x20, // V0 = 0x20
+    0x61, 0x30, // V1 = 0x30
+    0x80, 0x15, // V0 -= V1
+    0x6c, 0x00, // 8XY5: VC = 0
+    0x40, 0xf0, // skip if V0 != 0xf0
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x10, // V0 = 0x10
+    0x80, 0x17, // V0 = V1 - V0
+    0x6c, 0x00, // 8XY7: VC = 0
+    0x40, 0x20, // skip if V0 != 0x20
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x81, // V0 = 0x81
+    0x61, 0x81, // V1 = 0x81
+    0x80, 0x16, // V0 >>= 1
+    0x6c, 0x00, // 8XY6: VC = 0
+    0x40, 0x40, // skip if V0 != 0x40
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x81, // V0 = 0x81
+    0x80, 0x1e, // V0 <<= 1
+    0x6c, 0x00, // 8XYE: VC = 0
+    0x40, 0x02, // skip if V0 != 0x02
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x05, // V0 = 0x05
+    0x61, 0x05, // V1 = 0x05
+    0x6c, 0x00, // 5XY0: VC = 0
+    0x50, 0x10, // skip if V0 == V1
+    0x23, 0x02, // report failure
+    0x90, 0x10, // skip if V0 != V1
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x6c, 0x00, // 3XNN: VC = 0
+    0x30, 0x05, // skip if V0 == 5
+    0x23, 0x02, // report failure
+    0x40, 0x05, // skip if V0 != 5
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0xa4, 0x00, // I = 0x400
+    0x62, 0x10, // V2 = 0x10
+    0xf2, 0x1e, // I += V2
+    0x60, 0x5a, // V0 = 0x5a
+    0xf0, 0x55, // store V0 at I
+    0xa4, 0x10, // I = 0x410
+    0x60, 0x00, // V0 = 0x00
+    0xf0, 0x65, // load V0 from I
+    0x6c, 0x00, // FX1E FX55 FX65: VC = 0
+    0x40, 0x5a, // skip if V0 != 0x5a
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x00, // V0 = 0x00
+    0x22, 0xfe, // call sub
+    0x6c, 0x00, // 2NNN 00EE: VC = 0
+    0x40, 0x99, // skip if V0 != 0x99
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x60, 0x7b, // V0 = 0x7b
+    0xa4, 0x00, // I = 0x400
+    0xf0, 0x33, // BCD of V0
+    0xa4, 0x00, // I = 0x400
+    0xf2, 0x65, // load V0 to V2
+    0x85, 0x10, // V5 = V1
+    0x86, 0x20, // V6 = V2
+    0x6c, 0x00, // FX33 hundreds: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x80, 0x50, // V0 = V5
+    0x6c, 0x00, // FX33 tens: VC = 0
+    0x40, 0x02, // skip if V0 != 0x02
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x80, 0x60, // V0 = V6
+    0x6c, 0x00, // FX33 ones: VC = 0
+    0x40, 0x03, // skip if V0 != 0x03
+    0x6c, 0x01, // VC = 1
+    0x23, 0x02, // report
+    0x12, 0xfc, // loop
+    0x60, 0x99, // sub: V0 = 0x99
+    0x00, 0xee, // return
+    0xa3, 0x1b, // report: I = cross
+    0x3c, 0x00, // skip if VC == 0
+    0xa3, 0x16, // I = tick
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x3d, 0x3d, // skip if VD == 61
+    0x00, 0xee, // return
+    0x6d, 0x01, // VD = 1
+    0x7e, 0x06, // VE += 6
+    0x00, 0xee, // return
+    0x08, 0x08, 0x90, 0x50, 0x20, // tick
+    0x88, 0x50, 0x20, 0x50, 0x88, // cross
+];
+
+/// Checks VF after the arithmetic instructions, like the flags test.
+#[rustfmt::skip]
+const FLAGS: [u8; 228] = [
+    0x00, 0xe0, // clear
+    0x6d, 0x01, // VD = 1
+    0x6e, 0x01, // VE = 1
+    0x60, 0xff, // V0 = 0xff
+    0x61, 0x01, // V1 = 0x01
+    0x80, 0x14, // 8XY4 carry
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY4 carry flag: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x01, // V0 = 0x01
+    0x61, 0x01, // V1 = 0x01
+    0x80, 0x14, // 8XY4 no carry
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY4 no carry flag: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x05, // V0 = 0x05
+    0x61, 0x03, // V1 = 0x03
+    0x80, 0x15, // 8XY5 no borrow
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY5 no borrow flag: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x03, // V0 = 0x03
+    0x61, 0x05, // V1 = 0x05
+    0x80, 0x15, // 8XY5 borrow
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY5 borrow flag: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x03, // V0 = 0x03
+    0x61, 0x05, // V1 = 0x05
+    0x80, 0x17, // 8XY7 no borrow
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY7 no borrow flag: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x05, // V0 = 0x05
+    0x61, 0x03, // V1 = 0x03
+    0x80, 0x17, // 8XY7 borrow
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY7 borrow flag: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x01, // V0 = 0x01
+    0x61, 0x01, // V1 = 0x01
+    0x80, 0x16, // 8XY6 shifted out 1
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY6 shifted out 1 flag: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x02, // V0 = 0x02
+    0x61, 0x02, // V1 = 0x02
+    0x80, 0x16, // 8XY6 shifted out 0
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XY6 shifted out 0 flag: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x80, // V0 = 0x80
+    0x61, 0x80, // V1 = 0x80
+    0x80, 0x1e, // 8XYE shifted out 1
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XYE shifted out 1 flag: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0x40, // V0 = 0x40
+    0x61, 0x40, // V1 = 0x40
+    0x80, 0x1e, // 8XYE shifted out 0
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // 8XYE shifted out 0 flag: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x6f, 0xff, // VF = 0xff
+    0x61, 0x01, // V1 = 0x01
+    0x8f, 0x14, // VF += V1
+    0x80, 0xf0, // V0 = VF
+    0x6c, 0x00, // VF as VX: VC = 0
+    0x40, 0x01, // skip if V0 != 0x01
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x60, 0xff, // V0 = 0xff
+    0x6f, 0x01, // VF = 0x01
+    0x80, 0xf4, // V0 += VF
+    0x6c, 0x00, // VF as VY result: VC = 0
+    0x40, 0x00, // skip if V0 != 0x00
+    0x6c, 0x01, // VC = 1
+    0x22, 0xc6, // report
+    0x12, 0xc4, // loop
+    0xa2, 0xdf, // report: I = cross
+    0x3c, 0x00, // skip if VC == 0
+    0xa2, 0xda, // I = tick
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x3d, 0x3d, // skip if VD == 61
+    0x00, 0xee, // return
+    0x6d, 0x01, // VD = 1
+    0x7e, 0x06, // VE += 6
+    0x00, 0xee, // return
+    0x08, 0x08, 0x90, 0x50, 0x20, // tick
+    0x88, 0x50, 0x20, 0x50, 0x88, // cross
+];
+
+/// Draws a digit per quirk, like the quirks test: VF reset, memory
+/// (I left unchanged), shifting, jumping, display wait and clipping.
+#[rustfmt::skip]
+const QUIRKS: [u8; 152] = [
+    0x00, 0xe0, // clear
+    0xa2, 0x96, // I = 8 pixels
+    0x61, 0x3e, // V1 = 0x3e
+    0x62, 0x14, // V2 = 0x14
+    0xd1, 0x21, // draw at 62, 20
+    0xa2, 0x97, // I = 1 pixel
+    0x61, 0x00, // V1 = 0x00
+    0xd1, 0x21, // draw at 0, 20
+    0x63, 0x01, // V3 = 1 (clipped)
+    0x3f, 0x00, // skip if VF == 0
+    0x63, 0x00, // V3 = 0 (wrapped)
+    0x00, 0xe0, // clear
+    0x6d, 0x01, // VD = 1
+    0x6e, 0x01, // VE = 1
+    0x6f, 0x55, // VF = 0x55
+    0x60, 0x01, // V0 = 0x01
+    0x61, 0x02, // V1 = 0x02
+    0x80, 0x11, // V0 |= V1
+    0x60, 0x00, // V0 = 0x00
+    0x4f, 0x00, // skip if VF != 0
+    0x60, 0x01, // V0 = 0x01
+    0xf0, 0x29, // VF reset: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x60, 0xaa, // V0 = 0xaa
+    0x61, 0xbb, // V1 = 0xbb
+    0xa4, 0x00, // I = 0x400
+    0xf1, 0x55, // store V0, V1
+    0xa4, 0x00, // I = 0x400
+    0xf0, 0x65, // load V0
+    0xf0, 0x65, // load V0 again
+    0x61, 0x00, // V1 = 0x00
+    0x30, 0xbb, // skip if V0 == 0xbb
+    0x61, 0x01, // V1 = 1 (I unchanged)
+    0x80, 0x10, // V0 = V1
+    0xf0, 0x29, // memory: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x60, 0x10, // V0 = 0x10
+    0x61, 0x04, // V1 = 0x04
+    0x80, 0x16, // V0 = V1 >> 1 or V0 >> 1
+    0x61, 0x01, // V1 = 0x01
+    0x30, 0x08, // skip if V0 == 8
+    0x61, 0x00, // V1 = 0x00
+    0x80, 0x10, // V0 = V1
+    0xf0, 0x29, // shifting: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x60, 0x00, // V0 = 0x00
+    0x62, 0x02, // V2 = 0x02
+    0xb2, 0x66, // jump to target + V0 or V2
+    0x12, 0x6c, // target: V0 was used
+    0x60, 0x01, // V0 = 0x01
+    0x12, 0x6e, // V2 was used
+    0x60, 0x00, // V0 = 0x00
+    0xf0, 0x29, // jumping: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x60, 0xff, // V0 = 0xff
+    0xf0, 0x15, // delay = V0
+    0xa2, 0x97, // I = 1 pixel
+    0xd1, 0x21, // draw it
+    0xd1, 0x21, // erase it
+    0xf1, 0x07, // V1 = delay
+    0x60, 0x00, // V0 = 0x00
+    0x31, 0xff, // skip if V1 == 0xff
+    0x60, 0x01, // V0 = 0x01
+    0xf0, 0x29, // display wait: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x80, 0x30, // V0 = V3
+    0xf0, 0x29, // clipping: I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x12, 0x94, // loop
+    0xff, // 8 pixels
+    0x80, // 1 pixel
+];
+
+/// Draws the digits of three keys read with FX0A, then a bar once F is
+/// held down, like the keypad test.
+#[rustfmt::skip]
+const KEYPAD: [u8; 39] = [
+    0x00, 0xe0, // clear
+    0x6d, 0x01, // VD = 1
+    0x6e, 0x01, // VE = 1
+    0xf0, 0x0a, // wait: V0 = next key
+    0xf0, 0x29, // I = digit V0
+    0xdd, 0xe5, // draw it at VD, VE
+    0x7d, 0x06, // VD += 6
+    0x4d, 0x13, // skip if VD != 19
+    0x12, 0x14, // three keys read
+    0x12, 0x06, // next key
+    0x61, 0x0f, // held: V1 = 0xf
+    0xe1, 0xa1, // skip if key V1 is up
+    0x12, 0x1c, // key F is down
+    0x12, 0x14, // keep polling
+    0xa2, 0x26, // pressed: I = bar
+    0x61, 0x01, // V1 = 0x01
+    0x62, 0x0a, // V2 = 0x0a
+    0xd1, 0x21, // draw it at 1, 10
+    0x12, 0x24, // loop
+    0xff, // bar
+];
@@ -0,0 +1,1201 @@
+//! An assembler for Octo, the language Octo cartridges carry their programs
+//! in, so cartridges run without Octo itself.
+//!
+//! It follows the Octo manual: all CHIP-8, SUPER-CHIP and XO-CHIP
+//! statements, `if`/`loop` blocks, labels, `:const`, `:alias`, `:calc`,
+//! `:macro`, `:org`, `:next`, `:unpack`, `:byte`, `:pointer`, `:call`,
+//! `:assert` and `:breakpoint`. `:monitor` only matters to Octo's debugger
+//! and is skipped, `:stringmode` is not supported.
+//!
+//! Like Octo, the program starts with a `jump main` at 0x200.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error as ErrorTrait;
+use std::fmt;
+
+use chip8_core::Symbols;
+
+/// The address programs are loaded at.
+const START: usize = 0x200;
+/// XO-CHIP programs can fill all 64 KiB of memory.
+const MEMORY_SIZE: usize = 0x10000;
+
+/// Keys of Octo's keyboard layout, by the keypad key they stand for.
+const KEY_CONSTANTS: [(&str, u8); 16] = [
+    ("OCTO_KEY_1", 0x1),
+    ("OCTO_KEY_2", 0x2),
+    ("OCTO_KEY_3", 0x3),
+    ("OCTO_KEY_4", 0xc),
+    ("OCTO_KEY_Q", 0x4),
+    ("OCTO_KEY_W", 0x5),
+    ("OCTO_KEY_E", 0x6),
+    ("OCTO_KEY_R", 0xd),
+    ("OCTO_KEY_A", 0x7),
+    ("OCTO_KEY_S", 0x8),
+    ("OCTO_KEY_D", 0x9),
+    ("OCTO_KEY_F", 0xe),
+    ("OCTO_KEY_Z", 0xa),
+    ("OCTO_KEY_X", 0x0),
+    ("OCTO_KEY_C", 0xb),
+    ("OCTO_KEY_V", 0xf),
+];
+
+/// Why a program could not be assembled, and the line (counting from 1) at
+/// fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl ErrorTrait for AssemblyError {}
+
+type Result<T> = std::result::Result<T, AssemblyError>;
+
+/// An assembled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The bytes to load at 0x200.
+    pub rom: Vec<u8>,
+    /// Labels and breakpoints by address.
+    pub symbols: Symbols,
+}
+
+/// Assembles the Octo `source` into ROM bytes.
+pub fn assemble(source: &str) -> Result<Program> {
+    let mut assembler = Assembler::new(tokenize(source)?);
+    while let Some(token) = assembler.next() {
+        assembler.statement(token)?;
+    }
+    assembler.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    /// Whether the token was quoted, which only `:assert` messages are.
+    quoted: bool,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    for (n, line) in source.lines().enumerate() {
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '#' {
+                break;
+            } else if c == '"' {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => {
+                            return Err(AssemblyError {
+                                line: n + 1,
+                                message: "Missing closing quote".to_string(),
+                            })
+                        }
+                    }
+                }
+                tokens.push(Token {
+                    text,
+                    quoted: true,
+                    line: n + 1,
+                });
+            } else {
+                let mut text = String::new();
+                while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                    text.push(c);
+                    chars.next();
+                }
+                tokens.push(Token {
+                    text,
+                    quoted: false,
+                    line: n + 1,
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A value that may name a label defined further down.
+enum Operand {
+    Known(i64),
+    Forward(String),
+}
+
+/// How to write a label's address once it is known.
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    /// The lower 12 bits of an instruction.
+    Address,
+    /// Two bytes, for `i := long` and `:pointer`.
+    Long,
+    /// The immediates of `v0 := nibble << 4 | high` and `v1 := low`, or of
+    /// `v0 := high` with `:unpack long`.
+    Unpack(Option<u8>),
+}
+
+struct Fixup {
+    addr: usize,
+    name: String,
+    patch: Patch,
+    line: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+/// An open `loop`, with the jumps its `while`s leave it by.
+struct Loop {
+    start: usize,
+    exits: Vec<usize>,
+}
+
+struct Assembler {
+    /// Tokens left, last one first, so macros can push their bodies.
+    tokens: Vec<Token>,
+    line: usize,
+    memory: Vec<u8>,
+    here: usize,
+    end: usize,
+    constants: HashMap<String, i64>,
+    labels: HashMap<String, usize>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    loops: Vec<Loop>,
+    /// Jumps of `if ... begin` and `else` waiting for their `else` or `end`.
+    branches: Vec<usize>,
+    /// Label to put on the immediate of the next instruction (`:next`).
+    next_label: Option<String>,
+    symbols: Symbols,
+}
+
+impl Assembler {
+    fn new(mut tokens: Vec<Token>) -> Self {
+        tokens.reverse();
+        let constants = KEY_CONSTANTS
+            .iter()
+            .map(|(name, key)| (name.to_string(), i64::from(*key)))
+            .collect();
+        Self {
+            tokens,
+            line: 1,
+            memory: vec![0; MEMORY_SIZE],
+            // the first instruction is the jump to `main`
+            here: START + 2,
+            end: START + 2,
+            constants,
+            labels: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: vec![],
+            loops: vec![],
+            branches: vec![],
+            next_label: None,
+            symbols: Symbols::new(),
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T> {
+        Err(AssemblyError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.pop()?;
+        self.line = token.line;
+        Some(token)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|token| token.text.as_str())
+    }
+
+    fn expect_token(&mut self) -> Result<String> {
+        match self.next() {
+            Some(token) => Ok(token.text),
+            None => self.error("Unexpected end of program"),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<()> {
+        let token = self.expect_token()?;
+        if token == text {
+            Ok(())
+        } else {
+            self.error(format!("Expected '{}', found '{}'", text, token))
+        }
+    }
+
+    fn statement(&mut self, token: Token) -> Result<()> {
+        if token.quoted {
+            return self.error(format!("Unexpected string \"{}\"", token.text));
+        }
+        let text = token.text;
+        if let Some(register) = self.register_named(&text) {
+            return self.register_statement(register);
+        }
+        if let Some(value) = parse_number(&text) {
+            return self.emit_byte(value);
+        }
+        match text.as_str() {
+            ":" => {
+                let name = self.new_name()?;
+                self.define_label(name, self.here)
+            }
+            ":const" => {
+                let name = self.new_name()?;
+                let value = self.known_value()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.new_name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+                Ok(())
+            }
+            ":calc" => {
+                let name = self.new_name()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.calc()?
+                } else {
+                    self.known_value()?
+                };
+                self.emit_byte(value)
+            }
+            ":pointer" => {
+                let operand = self.operand()?;
+                let addr = self.here;
+                self.emit(0, 0)?;
+                self.write_operand(addr, operand, Patch::Long)
+            }
+            ":org" => {
+                let addr = self.known_value()?;
+                if !(START as i64..MEMORY_SIZE as i64).contains(&addr) {
+                    return self.error(format!("Address {:#x} is outside the program", addr));
+                }
+                self.here = addr as usize;
+                Ok(())
+            }
+            ":next" => {
+                let name = self.new_name()?;
+                self.next_label = Some(name);
+                Ok(())
+            }
+            ":unpack" => {
+                let nibble = if self.peek() == Some("long") {
+                    self.next();
+                    None
+                } else {
+                    Some(self.nibble()?)
+                };
+                let operand = self.operand()?;
+                let addr = self.here;
+                self.emit(0x60, 0)?;
+                self.emit(0x61, 0)?;
+                self.write_operand(addr, operand, Patch::Unpack(nibble))
+            }
+            ":call" => self.address_instruction(0x2000),
+            ":breakpoint" => {
+                let name = self.expect_token()?;
+                self.symbols.insert(self.here as u32, &name);
+                Ok(())
+            }
+            ":monitor" => {
+                self.expect_token()?;
+                self.expect_token()?;
+                Ok(())
+            }
+            ":assert" => {
+                let message = match self.tokens.last() {
+                    Some(token) if token.quoted => self.next().map(|token| token.text),
+                    _ => None,
+                };
+                if self.calc()? == 0 {
+                    let message = message.unwrap_or_else(|| "Assertion failed".to_string());
+                    return self.error(message);
+                }
+                Ok(())
+            }
+            ":macro" => self.define_macro(),
+            ":stringmode" => self.error(":stringmode is not supported"),
+            "clear" => self.emit(0x00, 0xe0),
+            "return" | ";" => self.emit(0x00, 0xee),
+            "exit" => self.emit(0x00, 0xfd),
+            "lores" => self.emit(0x00, 0xfe),
+            "hires" => self.emit(0x00, 0xff),
+            "scroll-right" => self.emit(0x00, 0xfb),
+            "scroll-left" => self.emit(0x00, 0xfc),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00, 0xc0 | n)
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(0x00, 0xd0 | n)
+            }
+            "audio" => self.emit(0xf0, 0x02),
+            "plane" => {
+                let n = self.nibble()?;
+                if n > 3 {
+                    return self.error(format!("Invalid plane {}", n));
+                }
+                self.emit(0xf0 | n, 0x01)
+            }
+            "saveflags" => self.register_instruction(0xf0, 0x75),
+            "loadflags" => self.register_instruction(0xf0, 0x85),
+            "bcd" => self.register_instruction(0xf0, 0x33),
+            "save" => self.memory_statement(0x55, 0x02),
+            "load" => self.memory_statement(0x65, 0x03),
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xd0 | x, y << 4 | n)
+            }
+            "jump" => self.address_instruction(0x1000),
+            "jump0" => self.address_instruction(0xb000),
+            "native" => self.address_instruction(0x0000),
+            "delay" => self.timer_statement(0x15),
+            "buzzer" => self.timer_statement(0x18),
+            "pitch" => self.timer_statement(0x3a),
+            "i" => self.i_statement(),
+            "if" => self.if_statement(),
+            "else" => {
+                let branch = match self.branches.pop() {
+                    Some(branch) => branch,
+                    None => return self.error("'else' without 'if ... begin'"),
+                };
+                let jump = self.here;
+                self.emit(0x10, 0)?;
+                self.patch_jump(branch, self.here);
+                self.branches.push(jump);
+                Ok(())
+            }
+            "end" => match self.branches.pop() {
+                Some(branch) => {
+                    self.patch_jump(branch, self.here);
+                    Ok(())
+                }
+                None => self.error("'end' without 'if ... begin'"),
+            },
+            "loop" => {
+                self.loops.push(Loop {
+                    start: self.here,
+                    exits: vec![],
+                });
+                Ok(())
+            }
+            "while" => {
+                if self.loops.is_empty() {
+                    return self.error("'while' outside of a loop");
+                }
+                self.conditional(true)?;
+                let exit = self.here;
+                self.emit(0x10, 0)?;
+                if let Some(open) = self.loops.last_mut() {
+                    open.exits.push(exit);
+                }
+                Ok(())
+            }
+            "again" => {
+                let open = match self.loops.pop() {
+                    Some(open) => open,
+                    None => return self.error("'again' without 'loop'"),
+                };
+                self.emit_jump(0x1000, open.start)?;
+                for exit in open.exits {
+                    self.patch_jump(exit, self.here);
+                }
+                Ok(())
+            }
+            _ if self.macros.contains_key(&text) => self.expand_macro(&text),
+            _ if is_name(&text) => {
+                // a bare name calls the subroutine
+                let operand = self.name_operand(text);
+                let addr = self.here;
+                self.emit(0x20, 0)?;
+                self.write_operand(addr, operand, Patch::Address)
+            }
+            _ => self.error(format!("Unexpected '{}'", text)),
+        }
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<()> {
+        let op = self.expect_token()?;
+        let arithmetic = |n| match op.as_str() {
+            "+=" => Some(0x4),
+            "-=" => Some(0x5),
+            "=-" => Some(0x7),
+            "|=" => Some(0x1),
+            "&=" => Some(0x2),
+            "^=" => Some(0x3),
+            ">>=" => Some(0x6),
+            "<<=" => Some(0xe),
+            ":=" => Some(n),
+            _ => None,
+        };
+        let next = self.peek().unwrap_or("").to_string();
+        if let Some(y) = self.register_named(&next) {
+            let n = match arithmetic(0x0) {
+                Some(n) => n,
+                None => return self.error(format!("Unknown operator '{}'", op)),
+            };
+            self.next();
+            return self.emit(0x80 | x, y << 4 | n);
+        }
+        match (op.as_str(), next.as_str()) {
+            (":=", "key") => {
+                self.next();
+                self.emit(0xf0 | x, 0x0a)
+            }
+            (":=", "delay") => {
+                self.next();
+                self.emit(0xf0 | x, 0x07)
+            }
+            (":=", "random") => {
+                self.next();
+                let mask = self.byte()?;
+                self.emit(0xc0 | x, mask)
+            }
+            (":=", _) => {
+                let value = self.byte()?;
+                self.emit(0x60 | x, value)
+            }
+            ("+=", _) => {
+                let value = self.byte()?;
+                self.emit(0x70 | x, value)
+            }
+            ("-=", _) => {
+                let value = self.byte()?;
+                self.emit(0x70 | x, value.wrapping_neg())
+            }
+            _ => self.error(format!("Unknown operator '{}'", op)),
+        }
+    }
+
+    fn i_statement(&mut self) -> Result<()> {
+        let op = self.expect_token()?;
+        match (op.as_str(), self.peek()) {
+            (":=", Some("long")) => {
+                self.next();
+                let operand = self.operand()?;
+                self.emit(0xf0, 0x00)?;
+                let addr = self.here;
+                self.emit(0, 0)?;
+                self.write_operand(addr, operand, Patch::Long)
+            }
+            (":=", Some("hex")) => {
+                self.next();
+                self.register_instruction(0xf0, 0x29)
+            }
+            (":=", Some("bighex")) => {
+                self.next();
+                self.register_instruction(0xf0, 0x30)
+            }
+            (":=", _) => self.address_instruction(0xa000),
+            ("+=", _) => self.register_instruction(0xf0, 0x1e),
+            _ => self.error(format!("Unknown operator '{}'", op)),
+        }
+    }
+
+    /// `delay := vx`, `buzzer := vx` and `pitch := vx`.
+    fn timer_statement(&mut self, low: u8) -> Result<()> {
+        self.expect(":=")?;
+        self.register_instruction(0xf0, low)
+    }
+
+    /// `save vx`, or `save vx - vy` on XO-CHIP; the same for `load`.
+    fn memory_statement(&mut self, low: u8, range: u8) -> Result<()> {
+        let x = self.register()?;
+        if self.peek() == Some("-") {
+            self.next();
+            let y = self.register()?;
+            self.emit(0x50 | x, y << 4 | range)
+        } else {
+            self.emit(0xf0 | x, low)
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<()> {
+        // the condition is read before knowing which form it takes
+        let mut condition = vec![];
+        loop {
+            match self.next() {
+                Some(token) if token.text == "then" || token.text == "begin" => {
+                    let begin = token.text == "begin";
+                    condition.reverse();
+                    self.tokens.extend(condition);
+                    self.conditional(begin)?;
+                    if begin {
+                        self.branches.push(self.here);
+                        self.emit(0x10, 0)?;
+                    }
+                    return Ok(());
+                }
+                Some(token) if condition.len() < 3 => condition.push(token),
+                _ => return self.error("Expected 'then' or 'begin' after 'if'"),
+            }
+        }
+    }
+
+    /// Emits the instructions that skip the next one unless the condition
+    /// holds, or if it holds when `negated`.
+    fn conditional(&mut self, negated: bool) -> Result<()> {
+        let x = self.register()?;
+        let mut op = self.expect_token()?;
+        if negated {
+            let flipped = match op.as_str() {
+                "==" => "!=",
+                "!=" => "==",
+                "<" => ">=",
+                ">" => "<=",
+                "<=" => ">",
+                ">=" => "<",
+                "key" => "-key",
+                "-key" => "key",
+                _ => return self.error(format!("Unknown comparison '{}'", op)),
+            };
+            op = flipped.to_string();
+        }
+        match op.as_str() {
+            "key" => return self.emit(0xe0 | x, 0xa1),
+            "-key" => return self.emit(0xe0 | x, 0x9e),
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {}
+            _ => return self.error(format!("Unknown comparison '{}'", op)),
+        }
+
+        let rhs = self.peek().and_then(|text| self.register_named(text));
+        if rhs.is_some() {
+            self.next();
+        }
+        match (op.as_str(), rhs) {
+            ("==", Some(y)) => return self.emit(0x90 | x, y << 4),
+            ("==", None) => {
+                let value = self.byte()?;
+                return self.emit(0x40 | x, value);
+            }
+            ("!=", Some(y)) => return self.emit(0x50 | x, y << 4),
+            ("!=", None) => {
+                let value = self.byte()?;
+                return self.emit(0x30 | x, value);
+            }
+            _ => {}
+        }
+
+        // the others compare through vf, whose flag tells if there was a
+        // borrow
+        let temp = self.aliases.get("compare-temp").copied().unwrap_or(0xf);
+        match rhs {
+            Some(y) => self.emit(0x80 | temp, y << 4)?,
+            None => {
+                let value = self.byte()?;
+                self.emit(0x60 | temp, value)?;
+            }
+        }
+        let (subtract, skip) = match op.as_str() {
+            ">" => (0x5, 0x30),
+            "<" => (0x7, 0x30),
+            ">=" => (0x7, 0x40),
+            _ => (0x5, 0x40),
+        };
+        self.emit(0x80 | temp, x << 4 | subtract)?;
+        self.emit(skip | temp, 1)
+    }
+
+    fn define_macro(&mut self) -> Result<()> {
+        let name = self.new_name()?;
+        let mut params = vec![];
+        loop {
+            let token = self.expect_token()?;
+            if token == "{" {
+                break;
+            }
+            params.push(token);
+        }
+        let mut body = vec![];
+        let mut depth = 1;
+        loop {
+            let token = match self.next() {
+                Some(token) => token,
+                None => return self.error(format!("Macro '{}' is missing its '}}'", name)),
+            };
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(
+            name,
+            Macro {
+                params,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<()> {
+        let count = self.macros[name].params.len();
+        let mut args = HashMap::new();
+        for i in 0..count {
+            let arg = match self.next() {
+                Some(token) => token,
+                None => return self.error(format!("Macro '{}' needs {} arguments", name, count)),
+            };
+            args.insert(self.macros[name].params[i].clone(), arg.text);
+        }
+
+        let line = self.line;
+        let definition = self.macros.get_mut(name).expect("macro exists");
+        let calls = definition.calls.to_string();
+        definition.calls += 1;
+        let expanded = definition.body.iter().rev().map(|token| {
+            let text = match token.text.as_str() {
+                "CALLS" => calls.clone(),
+                text => args.get(text).cloned().unwrap_or_else(|| text.to_string()),
+            };
+            // errors are reported where the macro is used
+            Token {
+                text,
+                quoted: token.quoted,
+                line,
+            }
+        });
+        self.tokens.extend(expanded.collect::<Vec<_>>());
+        Ok(())
+    }
+
+    /// Reads a `{ ... }` expression. Like in Octo, operators have no
+    /// precedence and apply right to left, so `{ 2 * 3 + 1 }` is 8.
+    fn calc(&mut self) -> Result<i64> {
+        self.expect("{")?;
+        let mut tokens = vec![];
+        let mut depth = 1;
+        loop {
+            let token = self.expect_token()?;
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            tokens.push(token);
+        }
+        tokens.reverse();
+        let value = self.expression(&mut tokens)?;
+        if let Some(token) = tokens.pop() {
+            return self.error(format!("Unexpected '{}' in expression", token));
+        }
+        Ok(value as i64)
+    }
+
+    fn expression(&self, tokens: &mut Vec<String>) -> Result<f64> {
+        let lhs = self.term(tokens)?;
+        let op = match tokens.last().map(String::as_str) {
+            None | Some(")") => return Ok(lhs),
+            Some(_) => tokens.pop().unwrap_or_default(),
+        };
+        let rhs = self.expression(tokens)?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        let bool = |b: bool| if b { 1.0 } else { 0.0 };
+        let value = match op.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" if rhs == 0.0 => return self.error("Division by zero"),
+            "/" => lhs / rhs,
+            "%" if b == 0 => return self.error("Division by zero"),
+            "%" => (a % b) as f64,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<" => bool(lhs < rhs),
+            "<=" => bool(lhs <= rhs),
+            ">" => bool(lhs > rhs),
+            ">=" => bool(lhs >= rhs),
+            "==" => bool(lhs == rhs),
+            "!=" => bool(lhs != rhs),
+            _ => return self.error(format!("Unknown operator '{}'", op)),
+        };
+        Ok(value)
+    }
+
+    fn term(&self, tokens: &mut Vec<String>) -> Result<f64> {
+        let token = match tokens.pop() {
+            Some(token) => token,
+            None => return self.error("Incomplete expression"),
+        };
+        let unary: Option<fn(f64) -> f64> = match token.as_str() {
+            "-" => Some(|v| -v),
+            "~" => Some(|v| !(v as i64) as f64),
+            "!" => Some(|v| if v == 0.0 { 1.0 } else { 0.0 }),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(f) = unary {
+            return Ok(f(self.term(tokens)?));
+        }
+        match token.as_str() {
+            "(" => {
+                let value = self.expression(tokens)?;
+                match tokens.pop().as_deref() {
+                    Some(")") => Ok(value),
+                    _ => self.error("Missing ')' in expression"),
+                }
+            }
+            "@" => {
+                let addr = self.term(tokens)? as i64;
+                match usize::try_from(addr).ok().and_then(|a| self.memory.get(a)) {
+                    Some(byte) => Ok(f64::from(*byte)),
+                    None => self.error(format!("Address {:#x} is outside memory", addr)),
+                }
+            }
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            "HERE" => Ok(self.here as f64),
+            _ => match self.known(&token) {
+                Some(value) => Ok(value as f64),
+                None => self.error(format!("Undefined name '{}'", token)),
+            },
+        }
+    }
+
+    /// The value of a number, constant or label defined so far.
+    fn known(&self, text: &str) -> Option<i64> {
+        parse_number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|addr| *addr as i64))
+    }
+
+    fn name_operand(&self, name: String) -> Operand {
+        match self.known(&name) {
+            Some(value) => Operand::Known(value),
+            None => Operand::Forward(name),
+        }
+    }
+
+    /// A value that may be a label defined later.
+    fn operand(&mut self) -> Result<Operand> {
+        if self.peek() == Some("{") {
+            return Ok(Operand::Known(self.calc()?));
+        }
+        let token = self.expect_token()?;
+        match self.known(&token) {
+            Some(value) => Ok(Operand::Known(value)),
+            None if is_name(&token) => Ok(Operand::Forward(token)),
+            None => self.error(format!("Expected a value, found '{}'", token)),
+        }
+    }
+
+    fn known_value(&mut self) -> Result<i64> {
+        match self.operand()? {
+            Operand::Known(value) => Ok(value),
+            Operand::Forward(name) => self.error(format!("Undefined name '{}'", name)),
+        }
+    }
+
+    /// A byte; negative values count down from 256.
+    fn byte(&mut self) -> Result<u8> {
+        let value = self.known_value()?;
+        if (-128..=255).contains(&value) {
+            Ok(value as u8)
+        } else {
+            self.error(format!("{} does not fit in a byte", value))
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u8> {
+        let value = self.known_value()?;
+        if (0..=15).contains(&value) {
+            Ok(value as u8)
+        } else {
+            self.error(format!("{} does not fit in a nibble", value))
+        }
+    }
+
+    fn register_named(&self, text: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(text) {
+            return Some(*register);
+        }
+        let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+        if digit.len() == 1 {
+            u8::from_str_radix(digit, 16).ok()
+        } else {
+            None
+        }
+    }
+
+    fn register(&mut self) -> Result<u8> {
+        let token = self.expect_token()?;
+        match self.register_named(&token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("Expected a register, found '{}'", token)),
+        }
+    }
+
+    /// A name for a label, constant, alias or macro.
+    fn new_name(&mut self) -> Result<String> {
+        let token = self.expect_token()?;
+        if is_name(&token) && self.register_named(&token).is_none() {
+            Ok(token)
+        } else {
+            self.error(format!("'{}' cannot be used as a name", token))
+        }
+    }
+
+    fn define_label(&mut self, name: String, addr: usize) -> Result<()> {
+        if self.labels.contains_key(&name) {
+            return self.error(format!("Label '{}' is defined twice", name));
+        }
+        self.symbols.insert(addr as u32, &name);
+        self.labels.insert(name, addr);
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, value: i64) -> Result<()> {
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("{} does not fit in a byte", value));
+        }
+        if self.here >= MEMORY_SIZE {
+            return self.error("The program does not fit in memory");
+        }
+        self.memory[self.here] = value as u8;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    fn emit(&mut self, high: u8, low: u8) -> Result<()> {
+        if let Some(name) = self.next_label.take() {
+            self.define_label(name, self.here + 1)?;
+        }
+        self.emit_byte(i64::from(high))?;
+        self.emit_byte(i64::from(low))
+    }
+
+    fn register_instruction(&mut self, high: u8, low: u8) -> Result<()> {
+        let x = self.register()?;
+        self.emit(high | x, low)
+    }
+
+    /// An instruction with a 12-bit address, such as `jump`.
+    fn address_instruction(&mut self, opcode: u16) -> Result<()> {
+        let operand = self.operand()?;
+        let addr = self.here;
+        self.emit((opcode >> 8) as u8, 0)?;
+        self.write_operand(addr, operand, Patch::Address)
+    }
+
+    fn emit_jump(&mut self, opcode: u16, target: usize) -> Result<()> {
+        let addr = self.here;
+        self.emit((opcode >> 8) as u8, 0)?;
+        self.write_operand(addr, Operand::Known(target as i64), Patch::Address)
+    }
+
+    fn patch_jump(&mut self, addr: usize, target: usize) {
+        self.memory[addr] = 0x10 | (target >> 8) as u8 & 0xf;
+        self.memory[addr + 1] = target as u8;
+    }
+
+    fn write_operand(&mut self, addr: usize, operand: Operand, patch: Patch) -> Result<()> {
+        match operand {
+            Operand::Known(value) => self.patch(addr, value, patch),
+            Operand::Forward(name) => {
+                self.fixups.push(Fixup {
+                    addr,
+                    name,
+                    patch,
+                    line: self.line,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn patch(&mut self, addr: usize, value: i64, patch: Patch) -> Result<()> {
+        match patch {
+            Patch::Address => {
+                if !(0..0x1000).contains(&value) {
+                    return self.error(format!("Address {:#x} is above 0xfff", value));
+                }
+                self.memory[addr] |= (value >> 8) as u8;
+                self.memory[addr + 1] = value as u8;
+            }
+            Patch::Long => {
+                if !(0..0x10000).contains(&value) {
+                    return self.error(format!("Address {:#x} is above 0xffff", value));
+                }
+                self.memory[addr] = (value >> 8) as u8;
+                self.memory[addr + 1] = value as u8;
+            }
+            Patch::Unpack(nibble) => {
+                let high = match nibble {
+                    Some(nibble) => nibble << 4 | ((value >> 8) & 0xf) as u8,
+                    None => (value >> 8) as u8,
+                };
+                self.memory[addr + 1] = high;
+                self.memory[addr + 3] = value as u8;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Program> {
+        if !self.loops.is_empty() {
+            return self.error("'loop' without 'again'");
+        }
+        if !self.branches.is_empty() {
+            return self.error("'if ... begin' without 'end'");
+        }
+        if let Some(name) = self.next_label.take() {
+            return self.error(format!(":next {} is not followed by an instruction", name));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let value = match self.known(&fixup.name) {
+                Some(value) => value,
+                None => return self.error(format!("Undefined name '{}'", fixup.name)),
+            };
+            self.patch(fixup.addr, value, fixup.patch)?;
+        }
+        let main = match self.labels.get("main") {
+            Some(main) => *main as i64,
+            None => return self.error("The program has no 'main' label"),
+        };
+        self.memory[START] = 0x10;
+        self.patch(START, main, Patch::Address)?;
+
+        Ok(Program {
+            rom: self.memory[START..self.end].to_vec(),
+            symbols: self.symbols,
+        })
+    }
+}
+
+/// A decimal, `0x` hexadecimal or `0b` binary number, maybe negative.
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '-')
+        && parse_number(text).is_none()
+        && !text.starts_with(":")
+        && text != "-"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(source: &str) -> Vec<u8> {
+        assemble(source).unwrap().rom
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let err = assemble(source).unwrap_err();
+        (err.line, err.message)
+    }
+
+    #[test]
+    fn assembles_statements() {
+        let rom = rom(": main
+              clear
+              v0 := 5  v1 := v0  v2 += 3  v2 -= 1  v3 := random 0xf
+              v0 += v1  v0 -= v1  v0 =- v1  v0 >>= v1  v0 <<= v1  v0 ^= v1
+              i := sprite  i += v2  i := hex v1  i := long sprite
+              sprite v0 v1 5
+              v4 := key  delay := v4  buzzer := v4  v5 := delay
+              save v3  load v3  save v1 - v2  bcd v0
+              plane 3  audio  pitch := v1  scroll-down 4  hires
+              jump main
+            : sprite 0xff 0b10000001 -1");
+
+        let expected = [
+            0x12, 0x02, 0x00, 0xe0, // jump main, clear
+            0x60, 0x05, 0x81, 0x00, 0x72, 0x03, 0x72, 0xff, 0xc3, 0x0f, //
+            0x80, 0x14, 0x80, 0x15, 0x80, 0x17, 0x80, 0x16, 0x80, 0x1e, 0x80, 0x13, //
+            0xa2, 0x42, 0xf2, 0x1e, 0xf1, 0x29, 0xf0, 0x00, 0x02, 0x42, //
+            0xd0, 0x15, //
+            0xf4, 0x0a, 0xf4, 0x15, 0xf4, 0x18, 0xf5, 0x07, //
+            0xf3, 0x55, 0xf3, 0x65, 0x51, 0x22, 0xf0, 0x33, //
+            0xf3, 0x01, 0xf0, 0x02, 0xf1, 0x3a, 0x00, 0xc4, 0x00, 0xff, //
+            0x12, 0x02, //
+            0xff, 0x81, 0xff,
+        ];
+        assert_eq!(rom, expected);
+    }
+
+    #[test]
+    fn assembles_control_flow() {
+        let rom = rom(": main
+              if v0 == 1 then v1 := 2
+              if v0 != v1 begin
+                clear
+              else
+                draw
+              end
+              loop
+                while v2 key
+                v2 += 1
+              again
+            : draw  return");
+
+        let expected = [
+            0x12, 0x02, //
+            0x40, 0x01, 0x61, 0x02, // if then
+            0x90, 0x10, 0x12, 0x0e, 0x00, 0xe0, 0x12, 0x10, 0x22, 0x18, // if begin else end
+            0xe2, 0x9e, 0x12, 0x18, 0x72, 0x01, 0x12, 0x10, // loop while again
+            0x00, 0xee,
+        ];
+        assert_eq!(rom, expected);
+    }
+
+    #[test]
+    fn compares_through_vf() {
+        let rom = rom(": main
+              if v1 > 5 then clear
+              if v1 <= v2 then clear");
+
+        let expected = [
+            0x12, 0x02, //
+            0x6f, 0x05, 0x8f, 0x15, 0x3f, 0x01, 0x00, 0xe0, //
+            0x8f, 0x20, 0x8f, 0x15, 0x4f, 0x01, 0x00, 0xe0,
+        ];
+        assert_eq!(rom, expected);
+    }
+
+    #[test]
+    fn assembles_directives() {
+        let program = assemble(
+            ":const SPEED 3
+            :alias speed v7
+            :calc DOUBLE { SPEED * 2 + 1 }
+            :macro move reg { reg += SPEED :byte CALLS }
+            : main
+              speed := DOUBLE
+              move v1
+              move v2
+              :unpack 0xa data
+              :next target v3 := 0
+              :breakpoint stop
+              :call 0x300
+              :byte { target - HERE }
+              :pointer data
+            :org 0x300
+            : data 1 2",
+        )
+        .unwrap();
+
+        let expected = [
+            0x12, 0x02, 0x67, 0x09, // speed := 3 * (2 + 1)
+            0x71, 0x03, 0x00, 0x72, 0x03, 0x01, // macros and CALLS
+            0x60, 0xa3, 0x61, 0x00, // unpack
+            0x63, 0x00, 0x23, 0x00, 0xfd, 0x03, 0x00,
+        ];
+        let mut rom = program.rom;
+        assert_eq!(rom[..expected.len()], expected);
+        assert_eq!(rom.split_off(0x100), [1, 2]);
+
+        let symbols: Vec<_> = program.symbols.iter().collect();
+        assert_eq!(
+            symbols,
+            [
+                (0x202, "main"),
+                (0x20f, "target"),
+                (0x210, "stop"),
+                (0x300, "data")
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_lines() {
+        assert_eq!(
+            error("clear"),
+            (1, "The program has no 'main' label".into())
+        );
+        assert_eq!(
+            error(": main\n  v0 := 300"),
+            (2, "300 does not fit in a byte".into())
+        );
+        assert_eq!(
+            error(": main\n\n  jump nowhere"),
+            (3, "Undefined name 'nowhere'".into())
+        );
+        assert_eq!(
+            error(": main\n  loop\n  v0 += 1"),
+            (3, "'loop' without 'again'".into())
+        );
+        assert_eq!(
+            error(": main\n  :assert \"too slow\" { 1 > 2 }"),
+            (2, "too slow".into())
+        );
+        assert_eq!(
+            error(": main\n  v0 *= v1"),
+            (2, "Unknown operator '*='".into())
+        );
+    }
+}
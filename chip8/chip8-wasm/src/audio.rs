@@ -0,0 +1,334 @@
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use chip8_core::{DigitalSound, DEFAULT_PITCH, PATTERN_LEN};
+
+use crate::FRAME_RATE;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    /// Value of the wave, from -1.0 to 1.0, at `phase` (0.0 to 1.0) of its
+    /// period.
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Self::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Self::Sine => (2.0 * PI * phase).sin(),
+            Self::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Self::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// Digitised sound played by a MegaChip program.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    sample_rate: u32,
+    looping: bool,
+    samples: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl Sample {
+    #[wasm_bindgen(getter, js_name=sampleRate)]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Samples from -1.0 to 1.0.
+    #[wasm_bindgen(getter)]
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples.clone()
+    }
+}
+
+impl From<&DigitalSound> for Sample {
+    fn from(sound: &DigitalSound) -> Self {
+        Self {
+            sample_rate: sound.sample_rate as u32,
+            looping: sound.looping,
+            samples: sound
+                .samples
+                .iter()
+                .map(|sample| (*sample as f32 - 128.0) / 128.0)
+                .collect(),
+        }
+    }
+}
+
+/// Lowest sample rate of the samples made from audio patterns, which Web
+/// Audio buffers can all play.
+const MIN_PATTERN_SAMPLE_RATE: f32 = 8000.0;
+
+/// Bits per second an XO-CHIP audio pattern plays at, for the pitch set by
+/// FX3A.
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2_f32.powf((pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+}
+
+impl Sample {
+    /// One period of an XO-CHIP audio pattern, looping. Every bit is
+    /// repeated as often as needed to reach a sample rate Web Audio plays.
+    pub fn from_pattern(pattern: &[u8; PATTERN_LEN], pitch: u8) -> Self {
+        let rate = pattern_rate(pitch);
+        let mut repeat = 1;
+        while rate * (repeat as f32) < MIN_PATTERN_SAMPLE_RATE {
+            repeat *= 2;
+        }
+
+        Self {
+            sample_rate: (rate * repeat as f32).round() as u32,
+            looping: true,
+            samples: (0..PATTERN_LEN * 8 * repeat)
+                .map(|n| pattern_bit(pattern, n / repeat))
+                .collect(),
+        }
+    }
+}
+
+/// Bit `n` of `pattern`, most significant bit first, as -1.0 or 1.0.
+fn pattern_bit(pattern: &[u8; PATTERN_LEN], n: usize) -> f32 {
+    if pattern[n / 8] & 0x80 >> (n % 8) != 0 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// The sound of the buzzer.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// In Hz.
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// From 0.0 (silent) to 1.0.
+    pub volume: f32,
+    /// Samples per second of the rendered audio.
+    pub sample_rate: u32,
+}
+
+#[wasm_bindgen]
+impl Tone {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            frequency: 261.63,
+            waveform: Waveform::Square,
+            volume: 0.25,
+            sample_rate: 44100,
+        }
+    }
+}
+
+/// Renders the buzzer, frame by frame, into 16-bit PCM samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioRenderer {
+    tone: Tone,
+    /// The XO-CHIP audio pattern and its pitch, played instead of the tone.
+    pattern: Option<([u8; PATTERN_LEN], u8)>,
+    phase: f32,
+    frames: u64,
+    samples: Vec<i16>,
+}
+
+impl AudioRenderer {
+    pub fn new(tone: Tone) -> Self {
+        Self {
+            tone,
+            pattern: None,
+            phase: 0.0,
+            frames: 0,
+            samples: vec![],
+        }
+    }
+
+    pub fn set_pattern(&mut self, pattern: Option<([u8; PATTERN_LEN], u8)>) {
+        self.pattern = pattern;
+    }
+
+    /// Renders the audio of one frame, with the buzzer on or off.
+    pub fn render_frame(&mut self, buzzing: bool) {
+        let sample_rate = self.tone.sample_rate as u64;
+        self.frames += 1;
+        // count from the start to not lose the fractions of a sample
+        let total = (self.frames * sample_rate / FRAME_RATE as u64) as usize;
+        // the phase of a pattern goes over all its bits
+        let frequency = match &self.pattern {
+            Some((_, pitch)) => pattern_rate(*pitch) / (PATTERN_LEN * 8) as f32,
+            None => self.tone.frequency,
+        };
+        let step = frequency / self.tone.sample_rate as f32;
+        let amplitude = self.tone.volume.clamp(0.0, 1.0) * i16::MAX as f32;
+
+        while self.samples.len() < total {
+            let sample = if buzzing {
+                let value = match &self.pattern {
+                    Some((pattern, _)) => {
+                        pattern_bit(pattern, (self.phase * (PATTERN_LEN * 8) as f32) as usize)
+                    }
+                    None => self.tone.waveform.sample(self.phase),
+                };
+                self.phase = (self.phase + step).fract();
+                (value * amplitude).round() as i16
+            } else {
+                0
+            };
+            self.samples.push(sample);
+        }
+    }
+
+    /// Encodes the rendered audio as a mono 16-bit PCM WAV file.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let sample_rate = self.tone.sample_rate;
+        let data_len = (self.samples.len() * 2) as u32;
+
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16_u32.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1_u16.to_le_bytes()); // channels
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // bytes per second
+        bytes.extend_from_slice(&2_u16.to_le_bytes()); // bytes per sample
+        bytes.extend_from_slice(&16_u16.to_le_bytes()); // bits per sample
+
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(waveform: Waveform) -> Tone {
+        Tone {
+            frequency: 1.0,
+            waveform,
+            volume: 1.0,
+            sample_rate: 240,
+        }
+    }
+
+    #[test]
+    fn renders_samples_of_every_frame() {
+        let mut renderer = AudioRenderer::new(tone(Waveform::Square));
+
+        renderer.render_frame(true);
+        renderer.render_frame(false);
+
+        assert_eq!(
+            renderer.samples,
+            [i16::MAX, i16::MAX, i16::MAX, i16::MAX, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn plays_audio_patterns() {
+        let mut pattern = [0; PATTERN_LEN];
+        pattern[0] = 0xf0;
+        let mut renderer = AudioRenderer::new(Tone {
+            sample_rate: 4000,
+            volume: 1.0,
+            ..Tone::default()
+        });
+        renderer.set_pattern(Some((pattern, DEFAULT_PITCH)));
+
+        renderer.render_frame(true);
+        renderer.render_frame(true);
+
+        assert_eq!(
+            renderer.samples[..6],
+            [i16::MAX, i16::MAX, i16::MAX, i16::MAX, -i16::MAX, -i16::MAX]
+        );
+        assert_eq!(renderer.samples[128], i16::MAX);
+    }
+
+    #[test]
+    fn turns_patterns_into_samples() {
+        let mut pattern = [0; PATTERN_LEN];
+        pattern[0] = 0x80;
+
+        let sample = Sample::from_pattern(&pattern, DEFAULT_PITCH);
+        assert_eq!(sample.sample_rate, 8000);
+        assert_eq!(sample.samples.len(), 256);
+        assert_eq!(sample.samples[..3], [1.0, 1.0, -1.0]);
+        assert!(sample.looping);
+
+        assert_eq!(Sample::from_pattern(&pattern, 112).sample_rate, 8000);
+        assert!((pattern_rate(0) - 1587.4).abs() < 0.1);
+    }
+
+    #[test]
+    fn keeps_fractions_of_samples() {
+        let mut renderer = AudioRenderer::new(Tone {
+            sample_rate: 44100,
+            ..Tone::default()
+        });
+
+        for _ in 0..60 {
+            renderer.render_frame(false);
+        }
+
+        assert_eq!(renderer.samples.len(), 44100);
+    }
+
+    #[test]
+    fn samples_waveforms() {
+        assert_eq!(Waveform::Sawtooth.sample(0.0), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.5), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.0), 1.0);
+        assert!((Waveform::Sine.sample(0.25) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn encodes_wav_header() {
+        let mut renderer = AudioRenderer::new(tone(Waveform::Square));
+        renderer.render_frame(true);
+
+        let wav = renderer.to_wav();
+
+        assert_eq!(wav.len(), 44 + 4 * 2);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(wav[24..28], 240_u32.to_le_bytes());
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(wav[44..46], i16::MAX.to_le_bytes());
+    }
+}
@@ -0,0 +1,98 @@
+use wasm_bindgen::JsValue;
+
+use std::error::Error as ErrorTrait;
+use std::fmt;
+
+use chip8_core::{ExpressionError, FontError, RomError, SymbolError, VmError};
+
+use crate::assembler::AssemblyError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    VmError(VmError),
+    InvalidRom(RomError),
+    InvalidTheme,
+    Encoding(String),
+    NotRecording,
+    InvalidKeyScript(usize),
+    InvalidRomDatabase,
+    InvalidCartridge,
+    /// The Octo program of a cartridge could not be assembled.
+    InvalidProgram(AssemblyError),
+    InvalidRplFlags,
+    InvalidFont(FontError),
+    InvalidSymbols(SymbolError),
+    InvalidBreakpoint(ExpressionError),
+    /// The ROM crashed, with a description of the error naming addresses.
+    Crashed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source() {
+            Some(err) => write!(f, "{}", err),
+            None => match self {
+                Self::InvalidKeyScript(line) => write!(f, "Invalid key script at line {}", line),
+                Self::Encoding(reason) => write!(f, "Could not encode image: {}", reason),
+                Self::Crashed(description) => write!(f, "{}", description),
+                _ => write!(f, "{:?}", self),
+            },
+        }
+    }
+}
+
+impl ErrorTrait for Error {
+    fn source(&self) -> Option<&(dyn ErrorTrait + 'static)> {
+        match self {
+            Self::VmError(ref e) => Some(e),
+            Self::InvalidRom(ref e) => Some(e),
+            Self::InvalidFont(ref e) => Some(e),
+            Self::InvalidSymbols(ref e) => Some(e),
+            Self::InvalidBreakpoint(ref e) => Some(e),
+            Self::InvalidProgram(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(value: Error) -> Self {
+        JsValue::from(format!("{}", value))
+    }
+}
+
+impl From<RomError> for Error {
+    fn from(value: RomError) -> Self {
+        Self::InvalidRom(value)
+    }
+}
+
+impl From<VmError> for Error {
+    fn from(value: VmError) -> Self {
+        Self::VmError(value)
+    }
+}
+
+impl From<FontError> for Error {
+    fn from(value: FontError) -> Self {
+        Self::InvalidFont(value)
+    }
+}
+
+impl From<SymbolError> for Error {
+    fn from(value: SymbolError) -> Self {
+        Self::InvalidSymbols(value)
+    }
+}
+
+impl From<ExpressionError> for Error {
+    fn from(value: ExpressionError) -> Self {
+        Self::InvalidBreakpoint(value)
+    }
+}
+
+impl From<AssemblyError> for Error {
+    fn from(value: AssemblyError) -> Self {
+        Self::InvalidProgram(value)
+    }
+}
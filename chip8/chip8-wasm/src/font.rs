@@ -0,0 +1,54 @@
+use wasm_bindgen::prelude::*;
+
+use chip8_core::{Font, FontError, FontSet as CoreFontSet, SMALL_FONT_LEN};
+
+/// The built-in fonts FX29 and FX30 can point to.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontSet {
+    Vip,
+    Eti660,
+    Dream6800,
+    SuperChip,
+    Octo,
+    Chip8,
+}
+
+impl From<FontSet> for CoreFontSet {
+    fn from(set: FontSet) -> Self {
+        match set {
+            FontSet::Vip => Self::Vip,
+            FontSet::Eti660 => Self::Eti660,
+            FontSet::Dream6800 => Self::Dream6800,
+            FontSet::SuperChip => Self::SuperChip,
+            FontSet::Octo => Self::Octo,
+            FontSet::Chip8 => Self::Chip8,
+        }
+    }
+}
+
+/// Reads a font file: the 80 bytes of the small digits, followed by the big
+/// digits if there are any.
+pub fn parse_font(data: &[u8]) -> Result<Font, FontError> {
+    if data.len() < SMALL_FONT_LEN {
+        return Err(FontError::SmallFontSize(data.len()));
+    }
+    let (small, big) = data.split_at(SMALL_FONT_LEN);
+    Font::custom(small, (!big.is_empty()).then_some(big))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_font_files() {
+        let font = parse_font(&[0x11; 180]).unwrap();
+        assert_eq!(font.small(), [0x11; 80]);
+        assert_eq!(font.big(), Some(&[0x11; 100][..]));
+
+        assert_eq!(parse_font(&[0; 80]).unwrap().big(), None);
+        assert_eq!(parse_font(&[0; 40]), Err(FontError::SmallFontSize(40)));
+        assert_eq!(parse_font(&[0; 90]), Err(FontError::BigFontSize(10)));
+    }
+}
@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+
+use crate::render::Rgb;
+use crate::{Error, Result, FRAME_RATE};
+
+/// An image in an animation, as indices into the palette of the animation,
+/// shown for `duration` frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationFrame {
+    pub pixels: Vec<u8>,
+    pub duration: u32,
+}
+
+/// Encodes a `width` x `height` RGBA image as PNG.
+pub fn encode_png(rgba: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    writer.write_image_data(rgba).map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)?;
+
+    Ok(bytes)
+}
+
+/// Encodes `width` x `height` frames as an animated PNG that loops forever.
+pub fn encode_apng(
+    frames: &[AnimationFrame],
+    palette: &[Rgb],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette_bytes(palette));
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(encoding_error)?;
+
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    for frame in frames {
+        let duration = frame.duration.min(u16::MAX as u32) as u16;
+        writer
+            .set_frame_delay(duration, FRAME_RATE as u16)
+            .map_err(encoding_error)?;
+        writer
+            .write_image_data(&frame.pixels)
+            .map_err(encoding_error)?;
+    }
+    writer.finish().map_err(encoding_error)?;
+
+    Ok(bytes)
+}
+
+/// Encodes `width` x `height` frames as an animated GIF that loops forever,
+/// with `palette` as its global colour table.
+pub fn encode_gif(
+    frames: &[AnimationFrame],
+    palette: &[Rgb],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+
+    {
+        let palette = palette_bytes(palette);
+        let mut encoder = gif::Encoder::new(&mut bytes, width as u16, height as u16, &palette)
+            .map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        // GIF delays are in hundredths of a second, so round the time at
+        // which every frame starts to avoid drifting
+        let mut elapsed = 0;
+        for frame in frames {
+            let start = elapsed * 100 / FRAME_RATE;
+            elapsed += frame.duration;
+            let end = elapsed * 100 / FRAME_RATE;
+
+            let gif_frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                buffer: Cow::Borrowed(&frame.pixels),
+                delay: (end - start).clamp(1, u16::MAX as u32) as u16,
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&gif_frame).map_err(gif_error)?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn palette_bytes(palette: &[Rgb]) -> Vec<u8> {
+    palette.iter().flat_map(|&(r, g, b)| [r, g, b]).collect()
+}
+
+fn encoding_error(err: png::EncodingError) -> Error {
+    Error::Encoding(err.to_string())
+}
+
+fn gif_error(err: gif::EncodingError) -> Error {
+    Error::Encoding(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_png_that_decodes_back() {
+        let rgba = [0xff, 0x00, 0x00, 0xff, 0x00, 0x00, 0xff, 0xff];
+        let bytes = encode_png(&rgba, 2, 1).unwrap();
+
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut out = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut out).unwrap();
+
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(out, rgba);
+    }
+
+    const PALETTE: [Rgb; 3] = [(0xff, 0x00, 0x00), (0x00, 0x00, 0xff), (0x12, 0x34, 0x56)];
+
+    fn frames() -> Vec<AnimationFrame> {
+        vec![
+            AnimationFrame {
+                pixels: vec![0],
+                duration: 3,
+            },
+            AnimationFrame {
+                pixels: vec![1],
+                duration: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn encodes_gif_with_delays_in_hundredths() {
+        let bytes = encode_gif(&frames(), &PALETTE, 1, 1).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes.as_slice()).unwrap();
+        assert_eq!(
+            decoder.global_palette().unwrap()[..9],
+            palette_bytes(&PALETTE)
+        );
+
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(frame.delay, 5);
+        assert_eq!(*frame.buffer, [0xff, 0x00, 0x00, 0xff]);
+
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(frame.delay, 1);
+        assert_eq!(*frame.buffer, [0x00, 0x00, 0xff, 0xff]);
+
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn encodes_apng_with_every_frame() {
+        let bytes = encode_apng(&frames(), &PALETTE, 1, 1).unwrap();
+
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+
+        assert_eq!(control.num_frames, 2);
+        assert_eq!(control.num_plays, 0);
+    }
+
+    #[test]
+    fn returns_error_for_wrong_image_size() {
+        assert!(matches!(encode_png(&[0; 4], 2, 1), Err(Error::Encoding(_))));
+    }
+}
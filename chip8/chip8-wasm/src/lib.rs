@@ -0,0 +1,1337 @@
+mod assembler;
+mod audio;
+mod error;
+mod font;
+mod image;
+mod octo;
+mod persistence;
+mod platform;
+mod recording;
+mod render;
+mod romdb;
+mod script;
+mod theme;
+mod utils;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use wasm_bindgen::prelude::*;
+
+use audio::AudioRenderer;
+use chip8_core::{
+    Breakpoint, Font, PixelFormat, Rect, RomWarning, SoundEvent, Symbols, Vm, VmError, MEGA_HEIGHT,
+    MEGA_WIDTH, PATTERN_LEN,
+};
+use persistence::{Persistence, Phosphor};
+use recording::{Recorder, DEFAULT_MAX_FRAMES};
+use render::{Renderer, Rgb};
+use romdb::RomDatabase;
+use theme::{Color, Theme, VP590_BACKGROUNDS, VP590_FOREGROUNDS};
+
+pub use audio::{Sample, Tone, Waveform};
+pub use error::Error;
+pub use font::FontSet;
+pub use octo::Cartridge;
+pub use platform::Platform;
+pub use recording::RecordingFormat;
+pub use render::Filter;
+pub use romdb::RomInfo;
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Frames per second the emulator runs at.
+const FRAME_RATE: u32 = 60;
+
+/// Approximate clock of the CHIP-8X tone generator, which divides it by the
+/// value written with FXF8 plus one.
+const VP595_CLOCK: f32 = 27535.0;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl From<Rect> for DirtyRect {
+    fn from(rect: Rect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, PartialEq)]
+pub struct Emu {
+    vm: Vm<fn() -> u8>,
+    theme: Theme,
+    renderer: Renderer,
+    phosphor: Phosphor,
+    output_buffer: Vec<u8>,
+    /// The display in the colours it is shown in, when it goes through the
+    /// renderer.
+    frame: Vec<Rgb>,
+    dirty: Option<Rect>,
+    recorder: Option<Recorder>,
+    rom_info: Option<RomInfo>,
+    rom_warnings: Vec<RomWarning>,
+    rom_hash: String,
+    rpl_flags_changed: bool,
+    digital_sound: Option<Sample>,
+    digital_sound_changed: bool,
+    /// The XO-CHIP audio pattern and pitch, as of the last call to `run`.
+    audio_pattern: Option<([u8; PATTERN_LEN], u8)>,
+    audio_pattern_changed: bool,
+}
+
+#[wasm_bindgen]
+impl Emu {
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Self {
+        Self::with_vm(Vm::new(rom, rand::random), rom)
+    }
+
+    #[wasm_bindgen]
+    pub fn run(&mut self, cycles: usize) -> Result<bool> {
+        self.vm.tick_timers();
+        let rpl_flags = self.vm.rpl_flags();
+
+        let mut shall_halt = false;
+        for _ in 0..cycles {
+            let res = self.vm.tick();
+            shall_halt = match res {
+                Ok(_) => false,
+                Err(VmError::InvalidOpcode(_)) => true,
+                Err(err) => return Err(Error::Crashed(self.vm.describe_error(&err))),
+            };
+
+            if shall_halt || self.vm.is_halted() {
+                shall_halt = true;
+                break;
+            }
+            if self.vm.breakpoint_hit().is_some() {
+                break;
+            }
+        }
+
+        if let Some(event) = self.vm.take_sound_event() {
+            self.digital_sound = match event {
+                SoundEvent::Play(sound) => Some(Sample::from(&sound)),
+                SoundEvent::Stop => None,
+            };
+            self.digital_sound_changed = true;
+        }
+
+        let audio_pattern = self
+            .vm
+            .xochip()
+            .and_then(|xo| Some((*xo.pattern()?, xo.pitch())));
+        if audio_pattern != self.audio_pattern {
+            self.audio_pattern = audio_pattern;
+            self.audio_pattern_changed = true;
+        }
+
+        // the display changes size when programs switch modes
+        let (width, height) = self.output_size();
+        if self.output_buffer.len() != 4 * width * height {
+            self.output_buffer = vec![0; 4 * width * height];
+            self.vm.invalidate_display();
+        }
+
+        let mut dirty = self.vm.take_dirty_rect();
+        if self.phosphor.is_enabled() && !self.vm.is_mega_mode() {
+            // fading pixels keep changing even if the display did not
+            dirty = match (dirty, self.phosphor.update(&self.vm.display)) {
+                (Some(a), Some(b)) => Some(a.union(&b)),
+                (a, b) => a.or(b),
+            };
+        }
+        self.dirty = dirty.map(|rect| self.update_display_buffer(rect));
+
+        self.rpl_flags_changed |= self.vm.rpl_flags() != rpl_flags;
+
+        if self.recorder.as_ref().is_some_and(|r| !r.is_full()) {
+            let pixels = self.themed_pixels();
+            let (width, height) = self.frame_size();
+            if let Some(recorder) = &mut self.recorder {
+                recorder.push(&pixels, width, height);
+            }
+        }
+
+        Ok(shall_halt)
+    }
+
+    #[wasm_bindgen(js_name=setTheme)]
+    pub fn set_theme(&mut self, off_color: &str, on_color: &str) -> Result<()> {
+        let palette = [Color::parse(off_color)?, Color::parse(on_color)?];
+        self.apply_theme(Theme::from_palette("Custom", &palette)?);
+
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name=setThemePreset)]
+    pub fn set_theme_preset(&mut self, name: &str) -> Result<()> {
+        let theme = Theme::preset(name).ok_or(Error::InvalidTheme)?;
+        self.apply_theme(theme);
+
+        Ok(())
+    }
+
+    /// Loads a theme from JSON or from a Lospec `.hex` palette.
+    #[wasm_bindgen(js_name=importTheme)]
+    pub fn import_theme(&mut self, name: &str, data: &str) -> Result<()> {
+        self.apply_theme(Theme::import(name, data)?);
+
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name=exportThemeJson)]
+    pub fn export_theme_json(&self) -> String {
+        self.theme.to_json()
+    }
+
+    #[wasm_bindgen(js_name=exportThemeHex)]
+    pub fn export_theme_hex(&self) -> String {
+        self.theme.to_hex_palette()
+    }
+
+    #[wasm_bindgen(js_name=borderColor)]
+    pub fn border_color(&self) -> String {
+        self.theme.border.to_string()
+    }
+
+    #[wasm_bindgen(js_name=backgroundColor)]
+    pub fn background_color(&self) -> String {
+        self.theme.background.to_string()
+    }
+
+    /// Selects how the display is scaled into the display buffer. `scale` is
+    /// ignored by the Scale2x and Scale3x filters, which have a fixed factor.
+    #[wasm_bindgen(js_name=setFilter)]
+    pub fn set_filter(&mut self, filter: Filter, scale: usize) {
+        self.renderer = Renderer::new(filter, scale);
+
+        let (width, height) = self.output_size();
+        self.output_buffer = vec![0; 4 * width * height];
+        self.vm.invalidate_display();
+    }
+
+    /// Makes pixels fade out instead of turning off at once, losing `rate`
+    /// (0.0 to 1.0) of their brightness every frame.
+    #[wasm_bindgen(js_name=setPhosphorDecay)]
+    pub fn set_phosphor_decay(&mut self, rate: f32) {
+        self.set_persistence(Persistence::Decay(rate));
+    }
+
+    /// Keeps pixels lit if they were lit in any of the last `frames` frames.
+    #[wasm_bindgen(js_name=setFrameBlending)]
+    pub fn set_frame_blending(&mut self, frames: usize) {
+        self.set_persistence(Persistence::Blend(frames));
+    }
+
+    #[wasm_bindgen(js_name=disablePersistence)]
+    pub fn disable_persistence(&mut self) {
+        self.set_persistence(Persistence::Off);
+    }
+
+    #[wasm_bindgen(js_name=outputWidth)]
+    pub fn output_width(&self) -> usize {
+        self.output_size().0
+    }
+
+    #[wasm_bindgen(js_name=outputHeight)]
+    pub fn output_height(&self) -> usize {
+        self.output_size().1
+    }
+
+    /// Whether the display buffer changed during the last call to `run`.
+    #[wasm_bindgen(js_name=isDirty)]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Area of the display buffer that changed during the last call to `run`.
+    #[wasm_bindgen(js_name=dirtyRect)]
+    pub fn dirty_rect(&self) -> Option<DirtyRect> {
+        self.dirty.map(DirtyRect::from)
+    }
+
+    #[wasm_bindgen(js_name=displayBuffer)]
+    pub fn display_buffer(&self) -> *const u8 {
+        self.output_buffer.as_ptr()
+    }
+
+    #[wasm_bindgen(js_name=updateKeyState)]
+    pub fn update_key_state(&mut self, key_code: &str, value: bool) -> Result<()> {
+        let rom_key = self.rom_info.as_ref().and_then(|info| {
+            info.keymap
+                .iter()
+                .find(|(code, _)| code == key_code)
+                .map(|(_, key)| *key)
+        });
+        if let Some(key) = rom_key {
+            return Ok(self.vm.set_key(key, value)?);
+        }
+
+        let mapped_key = match key_code {
+            "Digit1" => Some(0x1),
+            "Digit2" => Some(0x2),
+            "Digit3" => Some(0x3),
+            "Digit4" => Some(0xc),
+            "KeyQ" => Some(0x4),
+            "KeyW" => Some(0x5),
+            "KeyE" => Some(0x6),
+            "KeyR" => Some(0xd),
+            "KeyA" => Some(0x7),
+            "KeyS" => Some(0x8),
+            "KeyD" => Some(0x9),
+            "KeyF" => Some(0xe),
+            "KeyZ" => Some(0xa),
+            "KeyX" => Some(0x0),
+            "KeyC" => Some(0xb),
+            "KeyV" => Some(0xf),
+            _ => None,
+        };
+
+        if let Some(key) = mapped_key {
+            self.vm.set_key(key, value)?;
+        }
+
+        // the second CHIP-8X keypad, laid out like the first one on the
+        // numeric keypad
+        let second_key = match key_code {
+            "Numpad7" => Some(0x1),
+            "Numpad8" => Some(0x2),
+            "Numpad9" => Some(0x3),
+            "NumpadDivide" => Some(0xc),
+            "Numpad4" => Some(0x4),
+            "Numpad5" => Some(0x5),
+            "Numpad6" => Some(0x6),
+            "NumpadMultiply" => Some(0xd),
+            "Numpad1" => Some(0x7),
+            "Numpad2" => Some(0x8),
+            "Numpad3" => Some(0x9),
+            "NumpadSubtract" => Some(0xe),
+            "Numpad0" => Some(0xa),
+            "NumpadDecimal" => Some(0x0),
+            "NumpadEnter" => Some(0xb),
+            "NumpadAdd" => Some(0xf),
+            _ => None,
+        };
+
+        if let Some(key) = second_key {
+            self.vm.set_second_key(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name=isBuzzing)]
+    pub fn is_buzzing(&self) -> bool {
+        self.vm.sound() > 0
+    }
+
+    /// Whether the MegaChip digitised sound started or stopped during the
+    /// last calls to `run`. Resets the flag.
+    #[wasm_bindgen(js_name=takeDigitalSoundChanged)]
+    pub fn take_digital_sound_changed(&mut self) -> bool {
+        std::mem::take(&mut self.digital_sound_changed)
+    }
+
+    /// The MegaChip digitised sound that should be playing, if any.
+    #[wasm_bindgen(js_name=digitalSound)]
+    pub fn digital_sound(&self) -> Option<Sample> {
+        self.digital_sound.clone()
+    }
+
+    /// Whether the XO-CHIP audio pattern or its pitch changed during the
+    /// last calls to `run`. Resets the flag.
+    #[wasm_bindgen(js_name=takeAudioPatternChanged)]
+    pub fn take_audio_pattern_changed(&mut self) -> bool {
+        std::mem::take(&mut self.audio_pattern_changed)
+    }
+
+    /// The XO-CHIP audio pattern the buzzer plays instead of its tone, if
+    /// the program loaded one.
+    #[wasm_bindgen(js_name=audioPattern)]
+    pub fn audio_pattern(&self) -> Option<Sample> {
+        self.audio_pattern
+            .map(|(pattern, pitch)| Sample::from_pattern(&pattern, pitch))
+    }
+
+    /// Pitch of the buzzer in Hz, if the program set one (CHIP-8X only).
+    #[wasm_bindgen(js_name=toneFrequency)]
+    pub fn tone_frequency(&self) -> Option<f32> {
+        self.vm.tone().map(|tone| VP595_CLOCK / (tone as f32 + 1.0))
+    }
+
+    /// Encodes the display, with the current theme, as a PNG image scaled
+    /// up `scale` times.
+    pub fn screenshot(&self, scale: usize) -> Result<Vec<u8>> {
+        let renderer = Renderer::new(Filter::Nearest, scale);
+        let (width, height) = self.frame_size();
+        let (out_width, out_height) = renderer.output_size(width, height);
+
+        let mut rgba = vec![0; out_width * out_height * 4];
+        renderer.render(
+            &self.themed_pixels(),
+            width,
+            height,
+            Rect::new(0, 0, width, height),
+            self.background_rgb(),
+            &mut rgba,
+        );
+
+        image::encode_png(&rgba, out_width, out_height)
+    }
+
+    /// Starts recording every frame from now on, scaled up `scale` times
+    /// and with the current theme. Recording stops by itself after
+    /// `max_frames`, or after a minute if not given.
+    #[wasm_bindgen(js_name=startRecording)]
+    pub fn start_recording(
+        &mut self,
+        format: RecordingFormat,
+        scale: usize,
+        max_frames: Option<usize>,
+    ) {
+        let palette: Vec<_> = self.theme.palette.iter().map(|c| c.to_rgb()).collect();
+        let max_frames = max_frames.unwrap_or(DEFAULT_MAX_FRAMES);
+        self.recorder = Some(Recorder::new(format, scale, &palette, max_frames));
+    }
+
+    /// Stops recording and returns the recorded animation.
+    #[wasm_bindgen(js_name=stopRecording)]
+    pub fn stop_recording(&mut self) -> Result<Vec<u8>> {
+        let recorder = self.recorder.take().ok_or(Error::NotRecording)?;
+        recorder.finish()
+    }
+
+    #[wasm_bindgen(js_name=isRecording)]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    #[wasm_bindgen(js_name=recordedFrames)]
+    pub fn recorded_frames(&self) -> usize {
+        self.recorder.as_ref().map_or(0, |r| r.frame_count())
+    }
+
+    /// SHA-1 of the loaded ROM, in lowercase hex, to key data saved per ROM.
+    #[wasm_bindgen(js_name=romHash)]
+    pub fn rom_hash(&self) -> String {
+        self.rom_hash.clone()
+    }
+
+    /// The RPL user flags of the platform, to be saved between runs.
+    #[wasm_bindgen(js_name=exportRplFlags)]
+    pub fn export_rpl_flags(&self) -> Vec<u8> {
+        let count = self.vm.platform().rpl_flag_count();
+        self.vm.rpl_flags()[..count].to_vec()
+    }
+
+    /// Restores RPL user flags saved with `exportRplFlags`. Saves with fewer
+    /// flags than the platform has, such as the 8 of the HP 48 on XO-CHIP,
+    /// are padded with zeros.
+    #[wasm_bindgen(js_name=importRplFlags)]
+    pub fn import_rpl_flags(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() > self.vm.platform().rpl_flag_count() {
+            return Err(Error::InvalidRplFlags);
+        }
+        let mut flags = [0; 16];
+        flags[..data.len()].copy_from_slice(data);
+        self.vm.set_rpl_flags(flags);
+
+        Ok(())
+    }
+
+    /// Switches to a built-in font, replacing the platform's own.
+    #[wasm_bindgen(js_name=setFontSet)]
+    pub fn set_font_set(&mut self, set: FontSet) {
+        self.vm.set_font(Font::builtin(set.into()));
+    }
+
+    /// Goes back to the font of the ROM's platform.
+    #[wasm_bindgen(js_name=resetFont)]
+    pub fn reset_font(&mut self) {
+        self.vm
+            .set_font(Font::builtin(self.vm.platform().font_set()));
+    }
+
+    /// Loads a font file of 80 bytes of small digits, optionally followed
+    /// by 100 or 160 bytes of big digits.
+    #[wasm_bindgen(js_name=importFont)]
+    pub fn import_font(&mut self, data: &[u8]) -> Result<()> {
+        self.vm.set_font(font::parse_font(data)?);
+        Ok(())
+    }
+
+    /// Starts keeping the last `capacity` instructions, to see what led to
+    /// a crash.
+    #[wasm_bindgen(js_name=enableTracing)]
+    pub fn enable_tracing(&mut self, capacity: usize) {
+        self.vm.enable_tracing(capacity);
+    }
+
+    #[wasm_bindgen(js_name=disableTracing)]
+    pub fn disable_tracing(&mut self) {
+        self.vm.disable_tracing();
+    }
+
+    /// The traced instructions, one per line.
+    #[wasm_bindgen(js_name=traceText)]
+    pub fn trace_text(&self) -> String {
+        self.vm.tracer().map(|t| t.to_text()).unwrap_or_default()
+    }
+
+    #[wasm_bindgen(js_name=traceBinary)]
+    pub fn trace_binary(&self) -> Vec<u8> {
+        self.vm.tracer().map(|t| t.to_binary()).unwrap_or_default()
+    }
+
+    /// Starts counting executions, reads and writes per memory address.
+    #[wasm_bindgen(js_name=enableCoverage)]
+    pub fn enable_coverage(&mut self) {
+        self.vm.enable_coverage();
+    }
+
+    #[wasm_bindgen(js_name=disableCoverage)]
+    pub fn disable_coverage(&mut self) {
+        self.vm.disable_coverage();
+    }
+
+    /// Times an instruction started at each address, empty if coverage is
+    /// disabled.
+    #[wasm_bindgen(js_name=coverageExecutions)]
+    pub fn coverage_executions(&self) -> Vec<u32> {
+        self.vm
+            .coverage()
+            .map(|c| c.executions().to_vec())
+            .unwrap_or_default()
+    }
+
+    #[wasm_bindgen(js_name=coverageReads)]
+    pub fn coverage_reads(&self) -> Vec<u32> {
+        self.vm
+            .coverage()
+            .map(|c| c.reads().to_vec())
+            .unwrap_or_default()
+    }
+
+    #[wasm_bindgen(js_name=coverageWrites)]
+    pub fn coverage_writes(&self) -> Vec<u32> {
+        self.vm
+            .coverage()
+            .map(|c| c.writes().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Loads names for addresses, either exported by Octo as JSON or as
+    /// `addr name` lines.
+    #[wasm_bindgen(js_name=importSymbols)]
+    pub fn import_symbols(&mut self, text: &str) -> Result<()> {
+        let symbols = if text.trim_start().starts_with('{') {
+            octo::parse_symbols(text)?
+        } else {
+            Symbols::parse(text)?
+        };
+        self.vm.set_symbols(symbols);
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name=clearSymbols)]
+    pub fn clear_symbols(&mut self) {
+        self.vm.set_symbols(Symbols::new());
+    }
+
+    /// `addr` in hex, with the name of its symbol if there is one, such as
+    /// `0x02a6 (draw+2)`.
+    #[wasm_bindgen(js_name=formatAddress)]
+    pub fn format_address(&self, addr: u32) -> String {
+        self.vm.format_address(addr)
+    }
+
+    /// The PC and the calls that led to it, innermost first.
+    #[wasm_bindgen(js_name=formatStack)]
+    pub fn format_stack(&self) -> String {
+        self.vm.format_stack()
+    }
+
+    /// Adds a breakpoint such as `pc == 0x2a4 && v3 == 0x10`, or a tracepoint
+    /// such as `pc == 0x300 => score {mem[i]}`, and returns its id. `run`
+    /// stops early when a breakpoint is hit.
+    #[wasm_bindgen(js_name=addBreakpoint)]
+    pub fn add_breakpoint(&mut self, text: &str) -> Result<usize> {
+        let breakpoint = Breakpoint::parse(text)?;
+        Ok(self.vm.add_breakpoint(breakpoint))
+    }
+
+    #[wasm_bindgen(js_name=removeBreakpoint)]
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.vm.remove_breakpoint(id).is_some()
+    }
+
+    #[wasm_bindgen(js_name=clearBreakpoints)]
+    pub fn clear_breakpoints(&mut self) {
+        self.vm.clear_breakpoints();
+    }
+
+    /// The id of the breakpoint the last `run` stopped at, if any.
+    #[wasm_bindgen(js_name=breakpointHit)]
+    pub fn breakpoint_hit(&self) -> Option<usize> {
+        self.vm.breakpoint_hit()
+    }
+
+    #[wasm_bindgen(js_name=breakpointHits)]
+    pub fn breakpoint_hits(&self, id: usize) -> Option<u32> {
+        self.vm
+            .breakpoint(id)
+            .map(|b| b.hits().min(u32::MAX as u64) as u32)
+    }
+
+    /// Messages of the tracepoints since the last call, one per line.
+    #[wasm_bindgen(js_name=takeTracepointLog)]
+    pub fn take_tracepoint_log(&mut self) -> String {
+        self.vm.take_tracepoint_log().join("\n")
+    }
+
+    /// Starts attributing instructions to subroutines.
+    #[wasm_bindgen(js_name=enableProfiling)]
+    pub fn enable_profiling(&mut self) {
+        self.vm.enable_profiling();
+    }
+
+    #[wasm_bindgen(js_name=disableProfiling)]
+    pub fn disable_profiling(&mut self) {
+        self.vm.disable_profiling();
+    }
+
+    /// The profiled call stacks in the collapsed format of flame graph tools.
+    #[wasm_bindgen(js_name=profileCollapsed)]
+    pub fn profile_collapsed(&self) -> String {
+        self.vm
+            .profiler()
+            .map(|p| p.to_collapsed())
+            .unwrap_or_default()
+    }
+
+    /// A table of the time each subroutine takes out of frames of
+    /// `cycles_per_frame` instructions.
+    #[wasm_bindgen(js_name=profileReport)]
+    pub fn profile_report(&self, cycles_per_frame: usize) -> String {
+        self.vm
+            .profiler()
+            .map(|p| p.report(cycles_per_frame))
+            .unwrap_or_default()
+    }
+
+    /// Whether the ROM changed the RPL user flags since the last call.
+    #[wasm_bindgen(js_name=takeRplFlagsChanged)]
+    pub fn take_rpl_flags_changed(&mut self) -> bool {
+        std::mem::take(&mut self.rpl_flags_changed)
+    }
+
+    /// Problems found in the ROM that did not stop it from loading.
+    #[wasm_bindgen(js_name=romWarnings)]
+    pub fn rom_warnings(&self) -> Vec<String> {
+        self.rom_warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    /// What the ROM database knows about the loaded ROM, if anything.
+    #[wasm_bindgen(js_name=romInfo)]
+    pub fn rom_info(&self) -> Option<RomInfo> {
+        self.rom_info.clone()
+    }
+
+    fn with_vm(vm: Vm<fn() -> u8>, rom: &[u8]) -> Self {
+        let display = &vm.display;
+        let buffer_len = 4 * display.width() * display.height();
+
+        Self {
+            vm,
+            theme: Theme::default(),
+            renderer: Renderer::default(),
+            phosphor: Phosphor::default(),
+            output_buffer: vec![0; buffer_len],
+            frame: vec![],
+            dirty: None,
+            recorder: None,
+            rom_info: None,
+            rom_warnings: vec![],
+            rom_hash: romdb::rom_hash(rom),
+            rpl_flags_changed: false,
+            digital_sound: None,
+            digital_sound_changed: false,
+            audio_pattern: None,
+            audio_pattern_changed: false,
+        }
+    }
+
+    /// Sets the quirks, colours and keymap recommended for the ROM.
+    fn apply_rom_info(&mut self, info: RomInfo) {
+        if let Some(quirks) = info.quirks {
+            self.vm.set_quirks(quirks);
+        }
+
+        let palette: Option<Vec<_>> = info
+            .colors()
+            .iter()
+            .map(|color| Color::parse(color).ok())
+            .collect();
+        if let Some(theme) = palette.and_then(|p| Theme::from_palette(&info.title(), &p).ok()) {
+            self.apply_theme(theme);
+        }
+
+        self.rom_info = Some(info);
+    }
+
+    fn apply_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.vm.invalidate_display();
+    }
+
+    fn set_persistence(&mut self, persistence: Persistence) {
+        self.phosphor = Phosphor::new(persistence);
+        self.vm.invalidate_display();
+    }
+
+    /// Colours of the display pixels as they are shown, including the glow
+    /// of fading pixels.
+    fn themed_pixels(&self) -> Vec<Rgb> {
+        let (width, height) = self.frame_size();
+        (0..width * height)
+            .map(|i| self.themed_pixel(i % width, i / width))
+            .collect()
+    }
+
+    /// Colour of the pixel at `x`, `y` as it is shown.
+    fn themed_pixel(&self, x: usize, y: usize) -> Rgb {
+        if let Some(mega) = self.vm.megachip().filter(|mega| mega.is_enabled()) {
+            // fades are drawn over black
+            let alpha = mega.alpha() as u16;
+            let fade = |channel: u8| (channel as u16 * alpha / 255) as u8;
+            let [r, g, b, _] = mega.frame()[y * MEGA_WIDTH + x];
+            return (fade(r), fade(g), fade(b));
+        }
+
+        if self.vm.xochip().is_some() {
+            return self.theme.plane_color(self.vm.plane_bits(x, y));
+        }
+
+        let [off, on] = self.pixel_colors(x, y);
+        if self.phosphor.is_enabled() {
+            let intensity = self.phosphor.intensities()[y * self.vm.display.width() + x];
+            render::blend(off, on, intensity)
+        } else if self.vm.display.get(x, y) {
+            on
+        } else {
+            off
+        }
+    }
+
+    /// Unlit and lit colour of the pixel at `x`, `y`: the zone colours on
+    /// CHIP-8X, the theme everywhere else.
+    fn pixel_colors(&self, x: usize, y: usize) -> [Rgb; 2] {
+        match self.vm.colors() {
+            Some(colors) => [
+                VP590_BACKGROUNDS[colors.background() as usize],
+                VP590_FOREGROUNDS[colors.foreground(x, y) as usize],
+            ],
+            None => [self.theme.off_color(), self.theme.on_color()],
+        }
+    }
+
+    fn background_rgb(&self) -> Rgb {
+        self.pixel_colors(0, 0)[0]
+    }
+
+    /// Size of the frame shown: the MegaChip display in MegaChip mode,
+    /// the framebuffer otherwise.
+    fn frame_size(&self) -> (usize, usize) {
+        if self.vm.is_mega_mode() {
+            (MEGA_WIDTH, MEGA_HEIGHT)
+        } else {
+            (self.vm.display.width(), self.vm.display.height())
+        }
+    }
+
+    fn output_size(&self) -> (usize, usize) {
+        let (width, height) = self.frame_size();
+        self.renderer.output_size(width, height)
+    }
+
+    /// Redraws the display buffer after `rect` changed in the display, and
+    /// returns the area of the buffer that got updated.
+    fn update_display_buffer(&mut self, rect: Rect) -> Rect {
+        let colors = [self.theme.off_color(), self.theme.on_color()];
+        let display = &self.vm.display;
+
+        let is_monochrome =
+            self.vm.colors().is_none() && !self.vm.is_mega_mode() && self.vm.xochip().is_none();
+        if self.renderer.is_identity() && !self.phosphor.is_enabled() && is_monochrome {
+            display.encode_rect(PixelFormat::Rgba8888, colors, rect, &mut self.output_buffer);
+            return rect;
+        }
+
+        // the MegaChip display has no dirty tracking
+        let (width, height) = self.frame_size();
+        let rect = if self.vm.is_mega_mode() || self.frame.len() != width * height {
+            self.frame.resize(width * height, (0, 0, 0));
+            Rect::new(0, 0, width, height)
+        } else {
+            rect
+        };
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                self.frame[y * width + x] = self.themed_pixel(x, y);
+            }
+        }
+
+        let background = self.background_rgb();
+        self.renderer.render(
+            &self.frame,
+            width,
+            height,
+            rect,
+            background,
+            &mut self.output_buffer,
+        )
+    }
+}
+
+#[wasm_bindgen(js_name=themePresetNames)]
+pub fn theme_preset_names() -> Vec<String> {
+    theme::presets()
+        .into_iter()
+        .map(|theme| theme.name)
+        .collect()
+}
+
+#[wasm_bindgen(js_name=loadRom)]
+pub fn load_rom(rom: &[u8]) -> Result<Emu> {
+    let info = RomDatabase::embedded().lookup(rom);
+    let platform = info
+        .as_ref()
+        .and_then(|info| info.platform())
+        .and_then(|id| Platform::from_database_id(&id))
+        .unwrap_or(Platform::Chip8);
+
+    let mut emu = load_rom_for_platform(rom, platform)?;
+    if let Some(info) = info {
+        emu.apply_rom_info(info);
+    }
+
+    Ok(emu)
+}
+
+#[wasm_bindgen(js_name=loadRomForPlatform)]
+pub fn load_rom_for_platform(rom: &[u8], platform: Platform) -> Result<Emu> {
+    utils::set_panic_hook();
+
+    let platform: chip8_core::Platform = platform.into();
+    let warnings = platform.validate_rom(rom)?;
+
+    let mut emu = Emu::with_vm(Vm::for_platform(platform, rom, rand::random), rom);
+    emu.rom_warnings = warnings;
+
+    Ok(emu)
+}
+
+/// Reads the Octo source and options of a cartridge GIF and assembles the
+/// source.
+#[wasm_bindgen(js_name=decodeCartridge)]
+pub fn decode_cartridge(gif: &[u8]) -> Result<Cartridge> {
+    octo::decode_cartridge(gif)
+}
+
+/// Loads the program of a cartridge GIF as XO-CHIP, with the quirks, colours
+/// and speed of the cartridge in `romInfo`.
+#[wasm_bindgen(js_name=loadCartridge)]
+pub fn load_cartridge(gif: &[u8]) -> Result<Emu> {
+    let cartridge = octo::decode_cartridge(gif)?;
+    let mut emu = load_rom_for_platform(&cartridge.rom(), Platform::XoChip)?;
+    emu.vm.set_symbols(cartridge.symbols.clone());
+    emu.apply_rom_info(cartridge.rom_info());
+
+    Ok(emu)
+}
+
+/// Runs `rom` on `platform` for `frames` frames, pressing keys as told by
+/// `key_script` (see `script::parse_key_script`), and returns the sound of
+/// the buzzer as a WAV file. The frames after the program halts are silent.
+/// Random numbers always come from the same seed, so the same input renders
+/// the same audio.
+#[wasm_bindgen(js_name=renderAudio)]
+pub fn render_audio(
+    rom: &[u8],
+    platform: Platform,
+    frames: usize,
+    cycles_per_frame: usize,
+    key_script: &str,
+    tone: &Tone,
+) -> Result<Vec<u8>> {
+    let platform: chip8_core::Platform = platform.into();
+    platform.validate_rom(rom)?;
+
+    let events = script::parse_key_script(key_script)?;
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut vm = Vm::for_platform(platform, rom, move || rng.gen::<u8>());
+    let mut audio = AudioRenderer::new(*tone);
+
+    let mut events = events.iter().peekable();
+    let mut halted = false;
+    for frame in 0..frames {
+        while let Some(event) = events.next_if(|event| event.frame <= frame) {
+            vm.set_key(event.key, event.pressed)?;
+        }
+
+        vm.tick_timers();
+        for _ in 0..cycles_per_frame {
+            if halted {
+                break;
+            }
+            match vm.tick() {
+                Ok(_) => halted = vm.is_halted(),
+                Err(VmError::InvalidOpcode(_)) => halted = true,
+                Err(err) => return Err(Error::from(err)),
+            }
+        }
+
+        audio.set_pattern(
+            vm.xochip()
+                .and_then(|xo| Some((*xo.pattern()?, xo.pitch()))),
+        );
+        audio.render_frame(!halted && vm.sound() > 0);
+    }
+
+    Ok(audio.to_wav())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::RomError;
+
+    #[test]
+    fn load_rom_returns_error_for_invalid_roms() {
+        let rom = [0_u8; 4096];
+        let res = load_rom(&rom);
+        assert_eq!(
+            res,
+            Err(Error::InvalidRom(RomError::TooLarge {
+                size: 4096,
+                max_size: 3584
+            }))
+        );
+
+        assert_eq!(load_rom(&[]), Err(Error::InvalidRom(RomError::Empty)));
+        assert!(load_rom_for_platform(&rom, Platform::XoChip).is_ok());
+    }
+
+    #[test]
+    fn loads_cartridges_as_xochip() {
+        let gif = octo::cartridge_gif(
+            r##"{"program": ": main\n  v0 := 1\n  loop again",
+                "options": {"tickrate": 200, "fillColor": "#FF0000", "backgroundColor": "#000"}}"##,
+        );
+        let emu = load_cartridge(&gif).unwrap();
+        assert_eq!(emu.vm.platform(), chip8_core::Platform::XoChip);
+        assert_eq!(
+            &emu.vm.memory()[0x200..0x206],
+            [0x12, 0x02, 0x60, 0x01, 0x12, 0x04]
+        );
+        assert_eq!(emu.vm.symbols().iter().next(), Some((0x202, "main")));
+        assert_eq!(emu.theme.on_color(), (0xff, 0, 0));
+        assert_eq!(emu.rom_info().and_then(|info| info.tickrate()), Some(200));
+    }
+
+    #[test]
+    fn run_reports_dirty_rect() {
+        let rom = [0xa2, 0x06, 0xd0, 0x11, 0x12, 0x04, 0x80];
+        let mut emu = Emu::new(&rom);
+
+        assert_eq!(emu.run(1), Ok(false));
+        assert_eq!(
+            emu.dirty_rect(),
+            Some(DirtyRect {
+                x: 0,
+                y: 0,
+                width: 64,
+                height: 32
+            })
+        );
+
+        assert_eq!(emu.run(1), Ok(false));
+        assert_eq!(
+            emu.dirty_rect(),
+            Some(DirtyRect {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1
+            })
+        );
+        assert_eq!(emu.output_buffer[0..4], [0xff, 0xff, 0xff, 0xff]);
+
+        assert_eq!(emu.run(1), Ok(false));
+        assert!(!emu.is_dirty());
+    }
+
+    #[test]
+    fn run_ignores_machine_code_by_default() {
+        let mut emu = Emu::new(&[0x00, 0x00, 0x12, 0x00]);
+        assert_eq!(emu.run(4), Ok(false));
+    }
+
+    #[test]
+    fn scales_display_buffer_with_filter() {
+        let rom = [0xa2, 0x06, 0xd0, 0x11, 0x12, 0x04, 0x80];
+        let mut emu = Emu::new(&rom);
+        emu.set_filter(Filter::Nearest, 2);
+
+        assert_eq!((emu.output_width(), emu.output_height()), (128, 64));
+
+        let _ = emu.run(2);
+        assert_eq!(emu.output_buffer.len(), 128 * 64 * 4);
+        assert_eq!(
+            emu.output_buffer[0..12],
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0xff]
+        );
+        assert_eq!(emu.output_buffer[128 * 4..128 * 4 + 8], [0xff; 8]);
+    }
+
+    #[test]
+    fn blends_fading_pixels_with_phosphor_decay() {
+        let rom = [0xa2, 0x08, 0xd0, 0x11, 0xd0, 0x11, 0x12, 0x06, 0x80];
+        let mut emu = Emu::new(&rom);
+        emu.set_phosphor_decay(0.5);
+
+        let _ = emu.run(2);
+        assert_eq!(emu.output_buffer[0..4], [0xff, 0xff, 0xff, 0xff]);
+
+        let _ = emu.run(1);
+        assert_eq!(emu.output_buffer[0..4], [0x80, 0x80, 0x80, 0xff]);
+        assert!(emu.is_dirty());
+
+        let _ = emu.run(1);
+        assert_eq!(emu.output_buffer[0..4], [0x40, 0x40, 0x40, 0xff]);
+    }
+
+    #[test]
+    fn sets_theme_from_css_colors() {
+        let mut emu = Emu::new(&[]);
+
+        assert_eq!(emu.set_theme("navy", "#ff0"), Ok(()));
+        assert_eq!(emu.theme.off_color(), (0x00, 0x00, 0x80));
+        assert_eq!(emu.theme.on_color(), (0xff, 0xff, 0x00));
+        assert_eq!(emu.set_theme("#12", "#ff0"), Err(Error::InvalidTheme));
+    }
+
+    #[test]
+    fn sets_theme_preset() {
+        let mut emu = Emu::new(&[]);
+
+        assert!(theme_preset_names().contains(&"Paperback-2".to_string()));
+        assert_eq!(emu.set_theme_preset("Paperback-2"), Ok(()));
+        assert_eq!(emu.border_color(), "#382b26");
+        assert_eq!(emu.set_theme_preset("Nope"), Err(Error::InvalidTheme));
+    }
+
+    #[test]
+    fn takes_screenshot_with_theme() {
+        let rom = [0xa2, 0x06, 0xd0, 0x11, 0x12, 0x04, 0x80];
+        let mut emu = Emu::new(&rom);
+        let _ = emu.set_theme("#000080", "#ffff00");
+        let _ = emu.run(2);
+
+        let png = emu.screenshot(2).unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+
+        assert_eq!((info.width, info.height), (128, 64));
+        assert_eq!(
+            rgba[0..12],
+            [0xff, 0xff, 0, 0xff, 0xff, 0xff, 0, 0xff, 0, 0, 0x80, 0xff]
+        );
+    }
+
+    #[test]
+    fn records_frames_into_animation() {
+        let rom = [0xa2, 0x06, 0xd0, 0x11, 0x12, 0x04, 0x80];
+        let mut emu = Emu::new(&rom);
+
+        assert_eq!(emu.stop_recording(), Err(Error::NotRecording));
+
+        emu.start_recording(RecordingFormat::Apng, 1, Some(3));
+        for _ in 0..4 {
+            let _ = emu.run(1);
+        }
+        assert_eq!(emu.recorded_frames(), 3);
+
+        let apng = emu.stop_recording().unwrap();
+        let decoder = png::Decoder::new(apng.as_slice());
+        let reader = decoder.read_info().unwrap();
+        // the sprite is drawn in the second frame and then nothing changes
+        assert_eq!(reader.info().animation_control.unwrap().num_frames, 2);
+        assert!(!emu.is_recording());
+    }
+
+    #[test]
+    fn renders_buzzer_audio() {
+        // LD V0, 0x3C; LD ST, V0; loop waiting for key 5; LD ST, V0
+        let rom = [0x60, 0x3c, 0xf0, 0x18, 0xf1, 0x0a, 0xf0, 0x18, 0x12, 0x08];
+        let tone = Tone {
+            sample_rate: 600,
+            ..Tone::default()
+        };
+
+        let wav = render_audio(&rom, Platform::Chip8, 180, 10, "120 5 down", &tone).unwrap();
+        let samples: Vec<i16> = wav[44..]
+            .chunks(2)
+            .map(|c| i16::from_le_bytes([c[0], c[1]]))
+            .collect();
+
+        assert_eq!(samples.len(), 3 * 600);
+        assert!(samples[..590].iter().any(|s| *s != 0));
+        assert!(samples[600..1200].iter().all(|s| *s == 0));
+        assert!(samples[1200..].iter().any(|s| *s != 0));
+
+        assert_eq!(
+            render_audio(&rom, Platform::Chip8, 1, 10, "x", &tone),
+            Err(Error::InvalidKeyScript(1))
+        );
+
+        // LD V0, 0x3C; LD ST, V0; exit: the buzzer stops with the program
+        let rom = [0x60, 0x3c, 0xf0, 0x18, 0x00, 0xfd];
+        let wav = render_audio(&rom, Platform::SuperChip, 60, 10, "", &tone).unwrap();
+        assert!(wav[44..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn load_rom_applies_rom_database_metadata() {
+        let rom = include_bytes!("../../../app/public/roms/poker.ch8");
+        let emu = load_rom(rom).unwrap();
+
+        let info = emu.rom_info().unwrap();
+        assert_eq!(info.title(), "Poker");
+        assert_eq!(info.tickrate(), Some(12));
+        assert!(!emu.vm.quirks().wrap);
+        assert_eq!(emu.border_color(), "#1a472a");
+
+        let emu = load_rom(&[0x12, 0x00]).unwrap();
+        assert_eq!(emu.rom_info(), None);
+    }
+
+    #[test]
+    fn maps_keys_from_rom_database() {
+        let rom = include_bytes!("../../../app/public/roms/wait_for_key.ch8");
+        let mut emu = load_rom(rom).unwrap();
+        let _ = emu.run(6);
+        let _ = emu.run(6);
+        assert!(!emu.is_dirty());
+
+        // the ROM waits for any key, so a press shows the next sprite
+        assert_eq!(emu.update_key_state("ArrowUp", true), Ok(()));
+        let _ = emu.run(3);
+        assert!(!emu.is_dirty());
+
+        assert_eq!(emu.update_key_state("Space", true), Ok(()));
+        let _ = emu.run(3);
+        assert!(emu.is_dirty());
+    }
+
+    #[test]
+    fn resizes_output_when_display_mode_changes() {
+        let rom = [0x00, 0xff, 0x00, 0x11, 0x12, 0x04];
+        let mut emu = load_rom_for_platform(&rom, Platform::MegaChip).unwrap();
+
+        emu.run(1).unwrap();
+        assert_eq!((emu.output_width(), emu.output_height()), (128, 64));
+
+        emu.run(1).unwrap();
+        assert_eq!((emu.output_width(), emu.output_height()), (256, 192));
+        assert_eq!(emu.output_buffer.len(), 256 * 192 * 4);
+        assert_eq!(
+            emu.dirty_rect(),
+            Some(DirtyRect::from(Rect::new(0, 0, 256, 192)))
+        );
+    }
+
+    #[test]
+    fn draws_xochip_planes_and_reports_audio_patterns() {
+        let rom = [
+            0xf2, 0x01, // select the second plane
+            0xa2, 0x0a, // I = sprite
+            0xd0, 0x01, // draw it
+            0xf0, 0x02, // load the audio pattern
+            0x12, 0x08, // loop
+            0x80,
+        ];
+        let mut emu = load_rom_for_platform(&rom, Platform::XoChip).unwrap();
+        let palette =
+            ["#000000", "#ffffff", "#ff0000", "#00ff00"].map(|c| Color::parse(c).unwrap());
+        emu.apply_theme(Theme::from_palette("Planes", &palette).unwrap());
+
+        emu.run(4).unwrap();
+        assert_eq!(emu.output_buffer[..8], [0xff, 0, 0, 0xff, 0, 0, 0, 0xff]);
+
+        assert!(emu.take_audio_pattern_changed());
+        let pattern = emu.audio_pattern().unwrap();
+        assert_eq!(pattern.samples()[..3], [1.0, 1.0, -1.0]);
+        emu.run(1).unwrap();
+        assert!(!emu.take_audio_pattern_changed());
+    }
+
+    #[test]
+    fn reports_digital_sound_changes() {
+        let rom = [
+            0xa2, 0x08, // I = sound
+            0x06, 0x01, // play once
+            0x07, 0x00, // stop
+            0x12, 0x06, // loop
+            0x1f, 0x40, 0x00, 0x00, 0x01, 0x00, 0xc0,
+        ];
+        let mut emu = load_rom_for_platform(&rom, Platform::MegaChip).unwrap();
+
+        emu.run(2).unwrap();
+        assert!(emu.take_digital_sound_changed());
+        let sound = emu.digital_sound().unwrap();
+        assert_eq!((sound.sample_rate(), sound.looping()), (8000, false));
+        assert_eq!(sound.samples(), [0.5]);
+        assert!(!emu.take_digital_sound_changed());
+
+        emu.run(1).unwrap();
+        assert!(emu.take_digital_sound_changed());
+        assert_eq!(emu.digital_sound(), None);
+    }
+
+    #[test]
+    fn keeps_trace_of_crashed_roms() {
+        let mut emu = load_rom(&[0x60, 0x01, 0x00, 0xee]).unwrap();
+        emu.enable_tracing(8);
+
+        assert!(emu.run(2).is_err());
+        let trace = emu.trace_text();
+        assert_eq!(trace.lines().count(), 2);
+        assert!(trace.lines().last().unwrap().contains("PC:0202 OP:00EE"));
+    }
+
+    #[test]
+    fn counts_coverage() {
+        let mut emu = load_rom(&[0xa2, 0x06, 0xf0, 0x55, 0x12, 0x04]).unwrap();
+        assert!(emu.coverage_executions().is_empty());
+        emu.enable_coverage();
+
+        emu.run(4).unwrap();
+        assert_eq!(emu.coverage_executions()[0x200..0x206], [1, 0, 1, 0, 2, 0]);
+        assert_eq!(emu.coverage_writes()[0x206], 1);
+        assert_eq!(emu.coverage_reads()[0x206], 0);
+    }
+
+    #[test]
+    fn names_addresses_in_errors() {
+        let mut emu = load_rom(&[0x22, 0x04, 0x12, 0x02, 0xaf, 0xff, 0xff, 0x65]).unwrap();
+        emu.import_symbols("200 main\n204 load_data\nfff data")
+            .unwrap();
+
+        let err = emu.run(3).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid address: 0x1000 (data+1)");
+        assert_eq!(emu.format_address(0x206), "0x0206 (load_data+2)");
+        assert_eq!(
+            emu.format_stack(),
+            "at 0x0208 (load_data+4)\ncalled from 0x0200 (main)\n"
+        );
+    }
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut emu = load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let id = emu.add_breakpoint("v0 == 3").unwrap();
+        emu.add_breakpoint("pc == 0x200 => v0 = {v0:x}").unwrap();
+        assert!(emu.add_breakpoint("v0 ==").is_err());
+
+        assert!(!emu.run(10).unwrap());
+        assert_eq!(emu.breakpoint_hit(), Some(id));
+        assert_eq!(emu.breakpoint_hits(id), Some(1));
+        assert_eq!(emu.take_tracepoint_log(), "v0 = 0x0\nv0 = 0x1\nv0 = 0x2");
+
+        assert!(emu.remove_breakpoint(id));
+        emu.run(1).unwrap();
+        assert_eq!(emu.breakpoint_hit(), None);
+    }
+
+    #[test]
+    fn profiles_subroutines() {
+        let mut emu = load_rom(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xee]).unwrap();
+        emu.enable_profiling();
+
+        emu.run(4).unwrap();
+        assert_eq!(emu.profile_collapsed(), "0x0200 3\n0x0200;0x0204 1\n");
+        assert!(emu.profile_report(4).contains("0x0200  0x0204         1"));
+    }
+
+    #[test]
+    fn renders_chip8x_zone_colours() {
+        let rom = [
+            0x60, 0x00, // V0 = 0
+            0x62, 0x04, // V2 = green
+            0xb0, 0x21, // colour the top left 8x1 zone
+            0xa3, 0x10, // I = sprite
+            0xd0, 0x02, // draw two rows at 0, 0
+            0x63, 0x7f, // V3 = 0x7f
+            0xf3, 0xf8, // tone
+            0x13, 0x0e, // loop
+            0x80, 0x80,
+        ];
+        let mut emu = load_rom_for_platform(&rom, Platform::Chip8X).unwrap();
+        emu.run(8).unwrap();
+
+        let pixel = |x: usize, y: usize| {
+            let i = (y * 64 + x) * 4;
+            emu.output_buffer[i..i + 3].to_vec()
+        };
+        assert_eq!(pixel(0, 0), [0x00, 0xff, 0x00]);
+        assert_eq!(pixel(0, 1), [0xff, 0x00, 0x00]);
+        assert_eq!(pixel(1, 0), [0x00, 0x00, 0x80]);
+        assert_eq!(emu.tone_frequency(), Some(VP595_CLOCK / 128.0));
+    }
+
+    #[test]
+    fn loads_rom_for_platform_with_warnings() {
+        let emu = load_rom_for_platform(&[0x12, 0x00, 0x00], Platform::Eti660).unwrap();
+
+        assert_eq!(emu.vm.platform(), chip8_core::Platform::Eti660);
+        assert_eq!((emu.output_width(), emu.output_height()), (64, 48));
+        assert_eq!(emu.output_buffer.len(), 64 * 48 * 4);
+        assert_eq!(emu.rom_warnings(), ["ROM has an odd number of bytes"]);
+    }
+
+    #[test]
+    fn exports_and_imports_rpl_flags() {
+        // LD V0, 0x2A; LD R, V0; loop
+        let rom = [0x60, 0x2a, 0xf0, 0x75, 0x12, 0x04];
+        let mut emu = load_rom_for_platform(&rom, Platform::XoChip).unwrap();
+
+        let _ = emu.run(2);
+        assert!(emu.take_rpl_flags_changed());
+        assert!(!emu.take_rpl_flags_changed());
+        assert_eq!(emu.export_rpl_flags()[0..2], [0x2a, 0x00]);
+
+        assert_eq!(emu.import_rpl_flags(&[1, 2, 3]), Ok(()));
+        assert_eq!(emu.export_rpl_flags()[0..4], [1, 2, 3, 0]);
+        assert_eq!(emu.import_rpl_flags(&[0; 17]), Err(Error::InvalidRplFlags));
+
+        let mut emu = load_rom_for_platform(&rom, Platform::SuperChip).unwrap();
+        assert_eq!(emu.export_rpl_flags().len(), 8);
+        assert_eq!(emu.import_rpl_flags(&[0; 9]), Err(Error::InvalidRplFlags));
+        let mut emu = Emu::new(&rom);
+        assert_eq!(emu.import_rpl_flags(&[0]), Err(Error::InvalidRplFlags));
+        assert_eq!(emu.rom_hash().len(), 40);
+    }
+}
@@ -0,0 +1,298 @@
+//! Octo cartridges: GIF images that carry an Octo program and its options in
+//! the two lowest bits of every pixel's palette index.
+//!
+//! Cartridges hold the Octo source of the program rather than ROM bytes, so
+//! it is assembled when the cartridge is decoded.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use chip8_core::{Quirks, SymbolError, Symbols};
+
+use crate::assembler;
+use crate::romdb::RomInfo;
+use crate::theme::Color;
+use crate::{Error, Result};
+
+/// Octo's emulator options, as stored in cartridges and `.octo.rc` files.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>,
+    pub background_color: Option<String>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    pub shift_quirks: bool,
+    pub load_store_quirks: bool,
+    pub clip_quirks: bool,
+    pub jump_quirks: bool,
+    pub v_blank_quirks: bool,
+    pub logic_quirks: bool,
+}
+
+impl OctoOptions {
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            vblank: self.v_blank_quirks,
+            logic: self.logic_quirks,
+        }
+    }
+
+    /// The colours of the options up to the first missing or invalid one,
+    /// unlit first, then the colours of the XO-CHIP bit planes.
+    pub fn colors(&self) -> Vec<String> {
+        [
+            &self.background_color,
+            &self.fill_color,
+            &self.fill_color2,
+            &self.blend_color,
+        ]
+        .iter()
+        .map_while(|color| (*color).clone().filter(|c| Color::parse(c).is_ok()))
+        .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: OctoOptions,
+}
+
+/// The contents of an Octo cartridge.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cartridge {
+    source: String,
+    rom: Vec<u8>,
+    /// Labels and breakpoints of the program.
+    #[wasm_bindgen(skip)]
+    pub symbols: Symbols,
+    #[wasm_bindgen(skip)]
+    pub options: OctoOptions,
+}
+
+#[wasm_bindgen]
+impl Cartridge {
+    /// Octo source code of the program.
+    #[wasm_bindgen(getter)]
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// The assembled program.
+    #[wasm_bindgen(getter)]
+    pub fn rom(&self) -> Vec<u8> {
+        self.rom.clone()
+    }
+
+    /// Instructions per frame the program expects.
+    #[wasm_bindgen(getter)]
+    pub fn tickrate(&self) -> Option<u32> {
+        self.options.tickrate
+    }
+}
+
+impl Cartridge {
+    /// Metadata to load the program with: XO-CHIP, with the quirks, speed
+    /// and colours of the options.
+    pub fn rom_info(&self) -> RomInfo {
+        RomInfo::unlisted(
+            "Octo cartridge",
+            "xochip",
+            "XO-CHIP",
+            self.options.quirks(),
+            self.options.tickrate,
+            self.options.colors(),
+        )
+    }
+}
+
+/// Extracts the program and options from the bytes of a cartridge GIF and
+/// assembles the program.
+pub fn decode_cartridge(gif: &[u8]) -> Result<Cartridge> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(gif)
+        .map_err(|_| Error::InvalidCartridge)?;
+
+    // every pixel holds two bits, most significant first
+    let mut bytes = vec![];
+    let mut pending = vec![];
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|_| Error::InvalidCartridge)?
+    {
+        for index in frame.buffer.iter() {
+            pending.push(index & 0b11);
+            if pending.len() == 4 {
+                bytes.push(pending.iter().fold(0, |byte, bits| byte << 2 | bits));
+                pending.clear();
+            }
+        }
+    }
+
+    let size_bytes = bytes.get(0..4).ok_or(Error::InvalidCartridge)?;
+    let size = u32::from_be_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]]);
+    let json = bytes
+        .get(4..4 + size as usize)
+        .ok_or(Error::InvalidCartridge)?;
+    let payload: Payload = serde_json::from_slice(json).map_err(|_| Error::InvalidCartridge)?;
+
+    let program = assembler::assemble(&payload.program)?;
+
+    Ok(Cartridge {
+        source: payload.program,
+        rom: program.rom,
+        symbols: program.symbols,
+        options: payload.options,
+    })
+}
+
+/// Labels, breakpoints and monitors as kept by Octo's compiler (`dict`,
+/// `breakpoints` and `monitors`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct OctoSymbols {
+    #[serde(alias = "labels")]
+    dict: BTreeMap<String, Value>,
+    /// Names by decimal address.
+    breakpoints: BTreeMap<String, String>,
+    monitors: BTreeMap<String, Value>,
+}
+
+/// The first address of a ROM, below which only constants can be.
+const ROM_START: u64 = 0x200;
+
+/// Parses symbols exported by Octo as JSON. Labels take priority over
+/// breakpoints and monitors at the same address, and `:const` values below
+/// 0x200 are skipped, since they cannot be told from labels.
+pub fn parse_symbols(json: &str) -> Result<Symbols> {
+    let octo: OctoSymbols = serde_json::from_str(json)
+        .map_err(|err| Error::InvalidSymbols(SymbolError::InvalidLine(err.line())))?;
+
+    let mut symbols = Symbols::new();
+    for (name, value) in &octo.dict {
+        if let Some(addr) = value.as_u64().filter(|addr| *addr >= ROM_START) {
+            symbols.insert(addr as u32, name);
+        }
+    }
+    for (addr, name) in &octo.breakpoints {
+        if let Ok(addr) = addr.parse() {
+            symbols.insert(addr, name);
+        }
+    }
+    for (name, monitor) in &octo.monitors {
+        if let Some(addr) = monitor.get("base").and_then(Value::as_u64) {
+            symbols.insert(addr as u32, name);
+        }
+    }
+    Ok(symbols)
+}
+
+/// Packs `payload` into a one-frame cartridge GIF the way Octo does.
+#[cfg(test)]
+pub(crate) fn cartridge_gif(payload: &str) -> Vec<u8> {
+    let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(payload.as_bytes());
+    let mut pixels: Vec<u8> = data
+        .iter()
+        .flat_map(|byte| [byte >> 6, byte >> 4, byte >> 2, *byte].map(|bits| bits & 0b11))
+        // the label is drawn with the upper bits of the indices
+        .map(|bits| 0b0100 | bits)
+        .collect();
+    let width = 16;
+    pixels.resize(pixels.len().div_ceil(width) * width, 0);
+    let height = pixels.len() / width;
+
+    let palette = [0_u8; 8 * 3];
+    let mut bytes = vec![];
+    {
+        let mut encoder =
+            gif::Encoder::new(&mut bytes, width as u16, height as u16, &palette).unwrap();
+        let frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+        encoder.write_frame(&frame).unwrap();
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_program_and_options() {
+        let gif = cartridge_gif(
+            r##"{"program": ": main\n  loop again", "options": {
+                "tickrate": 20, "backgroundColor": "#000000", "fillColor": "#FF0000",
+                "shiftQuirks": true, "clipQuirks": true, "fontStyle": "octo"
+            }}"##,
+        );
+
+        let cartridge = decode_cartridge(&gif).unwrap();
+
+        assert_eq!(cartridge.source(), ": main\n  loop again");
+        assert_eq!(cartridge.rom(), [0x12, 0x02, 0x12, 0x02]);
+        assert_eq!(cartridge.tickrate(), Some(20));
+        let quirks = cartridge.options.quirks();
+        assert!(quirks.shift && !quirks.wrap && !quirks.jump);
+        assert_eq!(cartridge.options.colors(), ["#000000", "#FF0000"]);
+
+        let info = cartridge.rom_info();
+        assert_eq!(info.platform().as_deref(), Some("xochip"));
+        assert_eq!(info.tickrate(), Some(20));
+        assert_eq!(info.quirks, Some(quirks));
+    }
+
+    #[test]
+    fn parses_exported_symbols() {
+        let symbols = parse_symbols(
+            r#"{"dict": {"main": 512, "draw": 548, "SPEED": 4},
+                "breakpoints": {"548": "before-draw", "552": "after-draw"},
+                "monitors": {"score": {"base": 768, "length": 3}}}"#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = symbols.iter().collect();
+        assert_eq!(
+            names,
+            [
+                (0x200, "main"),
+                (0x224, "draw"),
+                (0x228, "after-draw"),
+                (0x300, "score")
+            ]
+        );
+        assert_eq!(
+            parse_symbols("{\n\"dict\": []}").unwrap_err(),
+            Error::InvalidSymbols(SymbolError::InvalidLine(2))
+        );
+    }
+
+    #[test]
+    fn returns_error_for_images_without_payload() {
+        let gif = cartridge_gif("not json");
+        assert_eq!(decode_cartridge(&gif), Err(Error::InvalidCartridge));
+        assert_eq!(decode_cartridge(b"GIF89a"), Err(Error::InvalidCartridge));
+    }
+
+    #[test]
+    fn returns_error_for_programs_that_do_not_assemble() {
+        let gif = cartridge_gif(r#"{"program": ": main\n  v0 := 256"}"#);
+        assert!(matches!(
+            decode_cartridge(&gif),
+            Err(Error::InvalidProgram(err)) if err.line == 2
+        ));
+    }
+}
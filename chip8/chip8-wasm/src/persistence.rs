@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+use chip8_core::{Framebuffer, Rect};
+
+/// How long lit pixels linger on screen after being turned off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Persistence {
+    Off,
+    /// Pixels fade out, losing this fraction (0.0 to 1.0) of their
+    /// brightness every frame.
+    Decay(f32),
+    /// Pixels stay lit if they were lit in any of the last N frames.
+    Blend(usize),
+}
+
+/// Per-pixel brightness of a simulated phosphor screen, to hide the flicker
+/// of sprites being erased and redrawn every frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phosphor {
+    mode: Persistence,
+    width: usize,
+    height: usize,
+    intensities: Vec<f32>,
+    history: VecDeque<Framebuffer>,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Self::new(Persistence::Off)
+    }
+}
+
+impl Phosphor {
+    pub fn new(mode: Persistence) -> Self {
+        Self {
+            mode,
+            width: 0,
+            height: 0,
+            intensities: vec![],
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != Persistence::Off
+    }
+
+    /// Brightness of every pixel, from 0.0 (off) to 1.0 (fully lit), in
+    /// row-major order.
+    pub fn intensities(&self) -> &[f32] {
+        &self.intensities
+    }
+
+    /// Advances one frame with the current state of `display`. Returns the
+    /// area of the pixels whose brightness changed.
+    pub fn update(&mut self, display: &Framebuffer) -> Option<Rect> {
+        let (width, height) = (display.width(), display.height());
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.intensities = vec![0.0; width * height];
+            self.history.clear();
+        }
+
+        if let Persistence::Blend(frames) = self.mode {
+            self.history.push_front(display.clone());
+            self.history.truncate(frames.max(1));
+        }
+
+        let mut changed: Option<Rect> = None;
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                let value = self.next_intensity(display, x, y, self.intensities[i]);
+                if value != self.intensities[i] {
+                    self.intensities[i] = value;
+                    let rect = Rect::new(x, y, 1, 1);
+                    changed = Some(changed.map_or(rect, |c| c.union(&rect)));
+                }
+            }
+        }
+
+        changed
+    }
+
+    fn next_intensity(&self, display: &Framebuffer, x: usize, y: usize, current: f32) -> f32 {
+        match self.mode {
+            _ if display.get(x, y) => 1.0,
+            Persistence::Off => 0.0,
+            Persistence::Decay(rate) => {
+                let value = current * (1.0 - rate.clamp(0.0, 1.0));
+                // below this the colour does not change anymore
+                if value < 1.0 / 255.0 {
+                    0.0
+                } else {
+                    value
+                }
+            }
+            Persistence::Blend(_) => {
+                if self.history.iter().any(|frame| frame.get(x, y)) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit_display() -> Framebuffer {
+        let mut display = Framebuffer::new(2, 1);
+        display.set(0, 0, true);
+        display
+    }
+
+    #[test]
+    fn decay_fades_out_pixels() {
+        let mut phosphor = Phosphor::new(Persistence::Decay(0.5));
+
+        assert_eq!(phosphor.update(&lit_display()), Some(Rect::new(0, 0, 1, 1)));
+        assert_eq!(phosphor.intensities(), [1.0, 0.0]);
+
+        let blank = Framebuffer::new(2, 1);
+        assert_eq!(phosphor.update(&blank), Some(Rect::new(0, 0, 1, 1)));
+        assert_eq!(phosphor.intensities(), [0.5, 0.0]);
+
+        for _ in 0..8 {
+            phosphor.update(&blank);
+        }
+        assert_eq!(phosphor.intensities(), [0.0, 0.0]);
+        assert_eq!(phosphor.update(&blank), None);
+    }
+
+    #[test]
+    fn blend_keeps_pixels_lit_for_n_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Blend(2));
+        let blank = Framebuffer::new(2, 1);
+
+        phosphor.update(&lit_display());
+        phosphor.update(&blank);
+        assert_eq!(phosphor.intensities(), [1.0, 0.0]);
+
+        phosphor.update(&blank);
+        assert_eq!(phosphor.intensities(), [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,48 @@
+use wasm_bindgen::prelude::*;
+
+use chip8_core::Platform as CorePlatform;
+
+/// The platform a ROM targets, which decides where it is loaded and the
+/// size of memory and the display.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Chip8,
+    Chip8HiRes,
+    Eti660,
+    SuperChip,
+    XoChip,
+    Chip8X,
+    MegaChip,
+    HybridVip,
+}
+
+impl Platform {
+    /// Platform for a platform id of the chip-8-database.
+    pub fn from_database_id(id: &str) -> Option<Self> {
+        match id {
+            "originalChip8" | "modernChip8" => Some(Self::Chip8),
+            "hybridVIP" => Some(Self::HybridVip),
+            "chip48" | "superchip1" | "superchip" => Some(Self::SuperChip),
+            "chip8x" => Some(Self::Chip8X),
+            "xochip" => Some(Self::XoChip),
+            "megachip8" => Some(Self::MegaChip),
+            _ => None,
+        }
+    }
+}
+
+impl From<Platform> for CorePlatform {
+    fn from(platform: Platform) -> Self {
+        match platform {
+            Platform::Chip8 => Self::Chip8,
+            Platform::Chip8HiRes => Self::Chip8HiRes,
+            Platform::Eti660 => Self::Eti660,
+            Platform::SuperChip => Self::SuperChip,
+            Platform::XoChip => Self::XoChip,
+            Platform::Chip8X => Self::Chip8X,
+            Platform::MegaChip => Self::MegaChip,
+            Platform::HybridVip => Self::HybridVip,
+        }
+    }
+}
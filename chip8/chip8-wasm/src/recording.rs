@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::image::{self, AnimationFrame};
+use crate::render::Rgb;
+use crate::{Result, FRAME_RATE};
+
+/// Longest a frame can last after merging identical ones, so delays fit in
+/// the image formats.
+const MAX_FRAME_DURATION: u32 = 60 * FRAME_RATE;
+
+/// Frames recorded when no limit is given: a minute.
+pub const DEFAULT_MAX_FRAMES: usize = 60 * FRAME_RATE as usize;
+
+/// Most colours an indexed GIF or PNG can have.
+const MAX_COLORS: usize = 256;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Apng,
+}
+
+/// Display frames captured while recording, before being scaled up, as
+/// indices into the palette of the recording.
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    pixels: Vec<u8>,
+    duration: u32,
+}
+
+/// The colours of a recording, shared by all its frames: the theme first,
+/// then the other colours (CHIP-8X zones, phosphor fades, MegaChip) as they
+/// show up. Once it is full, new colours get the closest one.
+#[derive(Debug, Clone, PartialEq)]
+struct Palette {
+    colors: Vec<Rgb>,
+    indices: HashMap<Rgb, u8>,
+}
+
+impl Palette {
+    fn new(colors: &[Rgb]) -> Self {
+        let mut palette = Self {
+            colors: vec![],
+            indices: HashMap::new(),
+        };
+        for &color in colors {
+            palette.index(color);
+        }
+        palette
+    }
+
+    fn index(&mut self, color: Rgb) -> u8 {
+        if let Some(&index) = self.indices.get(&color) {
+            return index;
+        }
+
+        let index = if self.colors.len() < MAX_COLORS {
+            self.colors.push(color);
+            (self.colors.len() - 1) as u8
+        } else {
+            let distance = |(r, g, b): Rgb| {
+                let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                d(r, color.0) + d(g, color.1) + d(b, color.2)
+            };
+            let closest = (0..self.colors.len()).min_by_key(|&i| distance(self.colors[i]));
+            closest.unwrap_or_default() as u8
+        };
+        self.indices.insert(color, index);
+        index
+    }
+}
+
+/// Records the frames of the display into an animation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorder {
+    format: RecordingFormat,
+    scale: usize,
+    palette: Palette,
+    max_frames: usize,
+    size: Option<(usize, usize)>,
+    frame_count: usize,
+    frames: Vec<Frame>,
+}
+
+impl Recorder {
+    /// Creates a recorder that scales frames up `scale` times and stops
+    /// after `max_frames`. The colours of `palette` come first in the
+    /// palette of the animation.
+    pub fn new(format: RecordingFormat, scale: usize, palette: &[Rgb], max_frames: usize) -> Self {
+        Self {
+            format,
+            scale: scale.max(1),
+            palette: Palette::new(palette),
+            max_frames,
+            size: None,
+            frame_count: 0,
+            frames: vec![],
+        }
+    }
+
+    /// Number of frames recorded, including the ones merged with the frame
+    /// before them.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frame_count >= self.max_frames
+    }
+
+    /// Adds a `width` x `height` frame to the recording. A frame identical to
+    /// the previous one just makes the previous one last longer. Frames are
+    /// ignored once the recording is full or if the display size changed.
+    pub fn push(&mut self, pixels: &[Rgb], width: usize, height: usize) {
+        if self.is_full() || *self.size.get_or_insert((width, height)) != (width, height) {
+            return;
+        }
+        self.frame_count += 1;
+
+        let pixels: Vec<_> = pixels.iter().map(|&c| self.palette.index(c)).collect();
+        match self.frames.last_mut() {
+            Some(last) if last.pixels == pixels && last.duration < MAX_FRAME_DURATION => {
+                last.duration += 1;
+            }
+            _ => self.frames.push(Frame {
+                pixels,
+                duration: 1,
+            }),
+        }
+    }
+
+    /// Encodes the recorded frames.
+    pub fn finish(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.size.unwrap_or((0, 0));
+        let scale = self.scale;
+        let (out_width, out_height) = (width * scale, height * scale);
+
+        let frames: Vec<_> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let pixels = (0..out_width * out_height)
+                    .map(|i| frame.pixels[(i / out_width / scale) * width + i % out_width / scale])
+                    .collect();
+
+                AnimationFrame {
+                    pixels,
+                    duration: frame.duration,
+                }
+            })
+            .collect();
+
+        let palette = &self.palette.colors;
+        match self.format {
+            RecordingFormat::Gif => image::encode_gif(&frames, palette, out_width, out_height),
+            RecordingFormat::Apng => image::encode_apng(&frames, palette, out_width, out_height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: Rgb = (0, 0, 0);
+    const ON: Rgb = (0xff, 0xff, 0xff);
+
+    fn recorder(max_frames: usize) -> Recorder {
+        Recorder::new(RecordingFormat::Gif, 2, &[OFF, ON], max_frames)
+    }
+
+    #[test]
+    fn merges_identical_frames() {
+        let mut recorder = recorder(DEFAULT_MAX_FRAMES);
+
+        recorder.push(&[ON, OFF], 2, 1);
+        recorder.push(&[ON, OFF], 2, 1);
+        recorder.push(&[OFF, ON], 2, 1);
+
+        assert_eq!(recorder.frame_count(), 3);
+        assert_eq!(
+            recorder
+                .frames
+                .iter()
+                .map(|f| f.duration)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+    }
+
+    #[test]
+    fn stops_after_max_frames() {
+        let mut recorder = recorder(2);
+
+        recorder.push(&[ON], 1, 1);
+        recorder.push(&[OFF], 1, 1);
+        assert!(recorder.is_full());
+
+        recorder.push(&[ON], 1, 1);
+        assert_eq!(recorder.frame_count(), 2);
+        assert_eq!(recorder.frames.len(), 2);
+    }
+
+    #[test]
+    fn encodes_scaled_frames() {
+        let mut recorder = recorder(DEFAULT_MAX_FRAMES);
+        recorder.push(&[ON, OFF], 2, 1);
+
+        let bytes = recorder.finish().unwrap();
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(bytes.as_slice())
+            .unwrap();
+
+        assert_eq!((decoder.width(), decoder.height()), (4, 2));
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(*frame.buffer, [1, 1, 0, 0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn shares_one_palette_between_frames() {
+        let mut recorder = recorder(DEFAULT_MAX_FRAMES);
+        let grey = |n: u8| (n, n, n);
+        recorder.push(&(0..=255).map(grey).collect::<Vec<_>>(), 256, 1);
+
+        let colors = &recorder.palette.colors;
+        assert_eq!(colors.len(), 256);
+        assert_eq!(colors[..3], [OFF, ON, grey(1)]);
+        // full: a new colour gets the closest one
+        assert_eq!(recorder.palette.index((0x10, 0x10, 0x11)), 0x11);
+        assert_eq!(recorder.palette.index((0xff, 0xff, 0xfe)), 1);
+    }
+}
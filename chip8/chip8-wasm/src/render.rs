@@ -0,0 +1,317 @@
+use wasm_bindgen::prelude::*;
+
+use chip8_core::Rect;
+
+pub type Rgb = (u8, u8, u8);
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Integer nearest-neighbour scaling.
+    Nearest,
+    /// EPX / Scale2x pixel-art scaler. Always scales by 2.
+    Scale2x,
+    /// AdvMAME3x / Scale3x pixel-art scaler. Always scales by 3.
+    Scale3x,
+    /// Darkens the bottom line of every pixel, like a CRT.
+    Scanlines,
+    /// Draws a thin grid between pixels.
+    PixelGrid,
+    /// LCD dot matrix: separated pixels, with a faint ghost of lit pixels on
+    /// the unlit ones.
+    DotMatrix,
+}
+
+/// Turns an image of `Rgb` pixels into a scaled RGBA image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Renderer {
+    filter: Filter,
+    scale: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new(Filter::Nearest, 1)
+    }
+}
+
+impl Renderer {
+    pub fn new(filter: Filter, scale: usize) -> Self {
+        let scale = match filter {
+            Filter::Scale2x => 2,
+            Filter::Scale3x => 3,
+            _ => scale.max(1),
+        };
+
+        Self { filter, scale }
+    }
+
+    /// Whether the output is the source image as is.
+    pub fn is_identity(&self) -> bool {
+        self.scale == 1
+    }
+
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * self.scale, height * self.scale)
+    }
+
+    /// Area of the output affected by a change in `rect` of the source image.
+    pub fn output_rect(&self, rect: Rect, width: usize, height: usize) -> Rect {
+        let rect = self.source_rect(rect, width, height);
+        Rect::new(
+            rect.x * self.scale,
+            rect.y * self.scale,
+            rect.width * self.scale,
+            rect.height * self.scale,
+        )
+    }
+
+    /// The pixels whose output changes with `rect`: the pixel-art scalers
+    /// look at the neighbours of each pixel.
+    fn source_rect(&self, rect: Rect, width: usize, height: usize) -> Rect {
+        let margin = match self.filter {
+            Filter::Scale2x | Filter::Scale3x => 1,
+            _ => 0,
+        };
+        let x = rect.x.saturating_sub(margin);
+        let y = rect.y.saturating_sub(margin);
+        let right = (rect.x + rect.width + margin).min(width);
+        let bottom = (rect.y + rect.height + margin).min(height);
+
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+
+    /// Renders what changed in `rect` of `src`, a `width` x `height` image,
+    /// into `out` as RGBA, and returns the area of `out` that was drawn.
+    /// `out` must be big enough to hold `output_size(width, height)` pixels.
+    /// `background` is the colour of the unlit pixels, used by the filters
+    /// that draw gaps between pixels.
+    pub fn render(
+        &self,
+        src: &[Rgb],
+        width: usize,
+        height: usize,
+        rect: Rect,
+        background: Rgb,
+        out: &mut [u8],
+    ) -> Rect {
+        let source = self.source_rect(rect, width, height);
+        for y in source.y..source.y + source.height {
+            for x in source.x..source.x + source.width {
+                self.render_pixel(src, width, height, (x, y), background, out);
+            }
+        }
+
+        self.output_rect(rect, width, height)
+    }
+
+    /// Draws the `scale` x `scale` block for the pixel at (x, y).
+    fn render_pixel(
+        &self,
+        src: &[Rgb],
+        width: usize,
+        height: usize,
+        (x, y): (usize, usize),
+        background: Rgb,
+        out: &mut [u8],
+    ) {
+        let scale = self.scale;
+        let out_width = width * scale;
+        let mut put = |dx: usize, dy: usize, (r, g, b): Rgb| {
+            let offset = ((y * scale + dy) * out_width + x * scale + dx) * 4;
+            out[offset..offset + 4].copy_from_slice(&[r, g, b, 0xff]);
+        };
+        let mut fill = |color: &dyn Fn(usize, usize) -> Rgb| {
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    put(dx, dy, color(dx, dy));
+                }
+            }
+        };
+
+        let at = |dx: isize, dy: isize| {
+            let nx = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+            let ny = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+            src[ny * width + nx]
+        };
+        let p = at(0, 0);
+        // whether (dx, dy) is on the bottom line or the right column
+        let bottom = |dy: usize| scale > 1 && dy == scale - 1;
+        let edge = |dx: usize, dy: usize| bottom(dy) || (scale > 1 && dx == scale - 1);
+
+        match self.filter {
+            Filter::Nearest => fill(&|_, _| p),
+            Filter::Scale2x => {
+                let (a, b, c, d) = (at(0, -1), at(1, 0), at(-1, 0), at(0, 1));
+                let block = [
+                    if c == a && c != d && a != b { a } else { p },
+                    if a == b && a != c && b != d { b } else { p },
+                    if d == c && d != b && c != a { c } else { p },
+                    if b == d && b != a && d != c { d } else { p },
+                ];
+                fill(&|dx, dy| block[dy * 2 + dx]);
+            }
+            Filter::Scale3x => {
+                let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+                let (d, e, f) = (at(-1, 0), p, at(1, 0));
+                let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+                let block = if b != h && d != f {
+                    [
+                        if d == b { d } else { e },
+                        if (d == b && e != c) || (b == f && e != a) {
+                            b
+                        } else {
+                            e
+                        },
+                        if b == f { f } else { e },
+                        if (d == b && e != g) || (d == h && e != a) {
+                            d
+                        } else {
+                            e
+                        },
+                        e,
+                        if (b == f && e != i) || (h == f && e != c) {
+                            f
+                        } else {
+                            e
+                        },
+                        if d == h { d } else { e },
+                        if (d == h && e != i) || (h == f && e != g) {
+                            h
+                        } else {
+                            e
+                        },
+                        if h == f { f } else { e },
+                    ]
+                } else {
+                    [e; 9]
+                };
+                fill(&|dx, dy| block[dy * 3 + dx]);
+            }
+            Filter::Scanlines => {
+                let dark = blend(p, (0, 0, 0), 0.5);
+                fill(&|_, dy| if bottom(dy) { dark } else { p });
+            }
+            Filter::PixelGrid => {
+                let dark = blend(p, (0, 0, 0), 0.25);
+                fill(&|dx, dy| if edge(dx, dy) { dark } else { p });
+            }
+            Filter::DotMatrix => {
+                let cell = if p == background {
+                    blend(background, (0xff, 0xff, 0xff), 0.06)
+                } else {
+                    p
+                };
+                fill(&|dx, dy| if edge(dx, dy) { background } else { cell });
+            }
+        }
+    }
+}
+
+/// Mixes `amount` (0.0 to 1.0) of `other` into `color`.
+pub fn blend(color: Rgb, other: Rgb, amount: f32) -> Rgb {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    (
+        mix(color.0, other.0),
+        mix(color.1, other.1),
+        mix(color.2, other.2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: Rgb = (0, 0, 0);
+    const ON: Rgb = (0xff, 0xff, 0xff);
+
+    fn render(renderer: Renderer, src: &[Rgb], width: usize, height: usize) -> Vec<Rgb> {
+        let (out_width, out_height) = renderer.output_size(width, height);
+        let mut out = vec![0; out_width * out_height * 4];
+        let rect = Rect::new(0, 0, width, height);
+        renderer.render(src, width, height, rect, OFF, &mut out);
+
+        out.chunks(4).map(|c| (c[0], c[1], c[2])).collect()
+    }
+
+    #[test]
+    fn nearest_scales_pixels_into_blocks() {
+        let out = render(Renderer::new(Filter::Nearest, 2), &[ON, OFF], 2, 1);
+
+        assert_eq!(out, [ON, ON, OFF, OFF, ON, ON, OFF, OFF]);
+    }
+
+    #[test]
+    fn scale2x_smooths_diagonals() {
+        #[rustfmt::skip]
+        let src = [
+            ON, OFF,
+            OFF, ON,
+        ];
+        let out = render(Renderer::new(Filter::Scale2x, 1), &src, 2, 2);
+
+        #[rustfmt::skip]
+        assert_eq!(out, [
+            ON, ON, OFF, OFF,
+            ON, OFF, ON, OFF,
+            OFF, ON, OFF, ON,
+            OFF, OFF, ON, ON,
+        ]);
+    }
+
+    #[test]
+    fn scale3x_keeps_flat_areas() {
+        let out = render(Renderer::new(Filter::Scale3x, 8), &[ON; 4], 2, 2);
+
+        assert_eq!(out, [ON; 36]);
+    }
+
+    #[test]
+    fn scanlines_darken_bottom_line() {
+        let out = render(Renderer::new(Filter::Scanlines, 2), &[ON], 1, 1);
+
+        assert_eq!(out, [ON, ON, (0x80, 0x80, 0x80), (0x80, 0x80, 0x80)]);
+    }
+
+    #[test]
+    fn dot_matrix_separates_pixels() {
+        let out = render(Renderer::new(Filter::DotMatrix, 2), &[ON, OFF], 2, 1);
+
+        assert_eq!(out[0], ON);
+        assert_eq!(out[1], OFF);
+        assert_eq!(out[2], (0x0f, 0x0f, 0x0f));
+        assert_eq!(out[4..8], [OFF; 4]);
+    }
+
+    #[test]
+    fn renders_only_the_changed_rect() {
+        let renderer = Renderer::new(Filter::Scale2x, 1);
+        let mut out = vec![0; 4 * 4 * 4 * 4];
+        let drawn = renderer.render(&[ON; 4 * 4], 4, 4, Rect::new(3, 3, 1, 1), OFF, &mut out);
+
+        assert_eq!(drawn, Rect::new(4, 4, 4, 4));
+        let lit: Vec<_> = out
+            .chunks(4)
+            .enumerate()
+            .filter(|(_, rgba)| rgba[3] == 0xff)
+            .map(|(i, _)| (i % 8, i / 8))
+            .collect();
+        assert_eq!(lit.len(), 16);
+        assert!(lit.iter().all(|&(x, y)| x >= 4 && y >= 4));
+    }
+
+    #[test]
+    fn output_rect_includes_neighbours_for_scalers() {
+        let rect = Rect::new(4, 4, 1, 1);
+
+        assert_eq!(
+            Renderer::new(Filter::Nearest, 4).output_rect(rect, 64, 32),
+            Rect::new(16, 16, 4, 4)
+        );
+        assert_eq!(
+            Renderer::new(Filter::Scale2x, 1).output_rect(rect, 64, 32),
+            Rect::new(6, 6, 6, 6)
+        );
+    }
+}
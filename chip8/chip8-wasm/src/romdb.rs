@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use chip8_core::Quirks;
+
+use crate::{Error, Result};
+
+const PROGRAMS_JSON: &str = include_str!("../data/programs.json");
+const PLATFORMS_JSON: &str = include_str!("../data/platforms.json");
+
+/// Keyboard keys (as in `KeyboardEvent.code`) for the actions of the
+/// database keymaps.
+const ACTION_KEYS: [(&str, &str); 6] = [
+    ("up", "ArrowUp"),
+    ("down", "ArrowDown"),
+    ("left", "ArrowLeft"),
+    ("right", "ArrowRight"),
+    ("a", "Space"),
+    ("b", "Enter"),
+];
+
+#[derive(Debug, Deserialize)]
+struct Program {
+    title: String,
+    description: Option<String>,
+    release: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkSet>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<Colors>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    name: String,
+    default_tickrate: Option<u32>,
+    quirks: QuirkSet,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct QuirkSet {
+    shift: bool,
+    memory_increment_by_x: bool,
+    memory_leave_i_unchanged: bool,
+    wrap: bool,
+    jump: bool,
+    vblank: bool,
+    logic: bool,
+}
+
+impl From<QuirkSet> for Quirks {
+    fn from(set: QuirkSet) -> Self {
+        Self {
+            shift: set.shift,
+            memory_increment_by_x: set.memory_increment_by_x,
+            memory_leave_i_unchanged: set.memory_leave_i_unchanged,
+            wrap: set.wrap,
+            jump: set.jump,
+            vblank: set.vblank,
+            logic: set.logic,
+        }
+    }
+}
+
+/// What the database knows about a ROM.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    title: String,
+    description: Option<String>,
+    release: Option<String>,
+    authors: Vec<String>,
+    platform: Option<String>,
+    platform_name: Option<String>,
+    #[wasm_bindgen(skip)]
+    pub quirks: Option<Quirks>,
+    tickrate: Option<u32>,
+    #[wasm_bindgen(skip)]
+    pub keymap: Vec<(String, u8)>,
+    colors: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl RomInfo {
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn release(&self) -> Option<String> {
+        self.release.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn authors(&self) -> Vec<String> {
+        self.authors.clone()
+    }
+
+    /// Id of the platform the ROM targets, like `originalChip8` or
+    /// `superchip`.
+    #[wasm_bindgen(getter)]
+    pub fn platform(&self) -> Option<String> {
+        self.platform.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name=platformName)]
+    pub fn platform_name(&self) -> Option<String> {
+        self.platform_name.clone()
+    }
+
+    /// Recommended instructions per frame.
+    #[wasm_bindgen(getter)]
+    pub fn tickrate(&self) -> Option<u32> {
+        self.tickrate
+    }
+
+    /// Colours of the pixels, unlit first, as CSS colours.
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<String> {
+        self.colors.clone()
+    }
+}
+
+impl RomInfo {
+    /// Metadata for a ROM the database does not list, such as one assembled
+    /// from an Octo cartridge.
+    pub(crate) fn unlisted(
+        title: &str,
+        platform: &str,
+        platform_name: &str,
+        quirks: Quirks,
+        tickrate: Option<u32>,
+        colors: Vec<String>,
+    ) -> Self {
+        Self {
+            title: title.to_string(),
+            description: None,
+            release: None,
+            authors: vec![],
+            platform: Some(platform.to_string()),
+            platform_name: Some(platform_name.to_string()),
+            quirks: Some(quirks),
+            tickrate,
+            keymap: vec![],
+            colors,
+        }
+    }
+}
+
+/// ROM metadata keyed by the SHA-1 of the ROMs, in the format of the
+/// community chip-8-database (`programs.json` and `platforms.json`).
+#[derive(Debug)]
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    platforms: Vec<Platform>,
+    index: HashMap<String, usize>,
+}
+
+impl RomDatabase {
+    pub fn from_json(programs: &str, platforms: &str) -> Result<Self> {
+        let programs: Vec<Program> =
+            serde_json::from_str(programs).map_err(|_| Error::InvalidRomDatabase)?;
+        let platforms = serde_json::from_str(platforms).map_err(|_| Error::InvalidRomDatabase)?;
+
+        let index = programs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, program)| {
+                program
+                    .roms
+                    .keys()
+                    .map(move |hash| (hash.to_lowercase(), i))
+            })
+            .collect();
+
+        Ok(Self {
+            programs,
+            platforms,
+            index,
+        })
+    }
+
+    /// The database bundled with the crate.
+    pub fn embedded() -> Self {
+        Self::from_json(PROGRAMS_JSON, PLATFORMS_JSON).expect("embedded ROM database is valid")
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        let hash = rom_hash(rom);
+        let program = &self.programs[*self.index.get(&hash)?];
+        let entry = program
+            .roms
+            .iter()
+            .find(|(key, _)| key.to_lowercase() == hash)
+            .map(|(_, entry)| entry)?;
+
+        let platform_id = entry.platforms.first();
+        let platform = platform_id.and_then(|id| self.platforms.iter().find(|p| &p.id == id));
+        let quirks = platform_id
+            .and_then(|id| entry.quirky_platforms.get(id))
+            .or(platform.map(|p| &p.quirks))
+            .map(|set| Quirks::from(*set));
+
+        let keymap = ACTION_KEYS
+            .iter()
+            .filter_map(|(action, code)| {
+                let key = *entry.keys.get(*action)?;
+                Some((code.to_string(), key)).filter(|_| key < 16)
+            })
+            .collect();
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            description: program.description.clone(),
+            release: program.release.clone(),
+            authors: program.authors.clone(),
+            platform: platform_id.cloned(),
+            platform_name: platform.map(|p| p.name.clone()),
+            quirks,
+            tickrate: entry.tickrate.or(platform.and_then(|p| p.default_tickrate)),
+            keymap,
+            colors: entry
+                .colors
+                .as_ref()
+                .map_or(vec![], |colors| colors.pixels.clone()),
+        })
+    }
+}
+
+/// SHA-1 of `rom`, in lowercase hex.
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAMS: &str = r##"[
+        {
+            "title": "Test",
+            "authors": ["Someone"],
+            "roms": {
+                "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                    "platforms": ["superchip", "xochip"],
+                    "keys": { "up": 5, "a": 6, "b": 99 },
+                    "colors": { "pixels": ["#000", "#fff"] }
+                }
+            }
+        }
+    ]"##;
+
+    const PLATFORMS: &str = r#"[
+        {
+            "id": "superchip",
+            "name": "SUPER-CHIP 1.1",
+            "defaultTickrate": 30,
+            "quirks": { "shift": true, "jump": true, "memoryLeaveIUnchanged": true }
+        }
+    ]"#;
+
+    #[test]
+    fn looks_up_roms_by_sha1() {
+        let db = RomDatabase::from_json(PROGRAMS, PLATFORMS).unwrap();
+
+        let info = db.lookup(b"abc").unwrap();
+        assert_eq!(info.title(), "Test");
+        assert_eq!(info.authors(), ["Someone"]);
+        assert_eq!(info.platform().as_deref(), Some("superchip"));
+        assert_eq!(info.platform_name().as_deref(), Some("SUPER-CHIP 1.1"));
+        assert_eq!(info.quirks, Some(Quirks::superchip()));
+        assert_eq!(info.tickrate(), Some(30));
+        assert_eq!(
+            info.keymap,
+            [("ArrowUp".to_string(), 5), ("Space".to_string(), 6)]
+        );
+        assert_eq!(info.colors(), ["#000", "#fff"]);
+
+        assert_eq!(db.lookup(b"abcd"), None);
+    }
+
+    #[test]
+    fn embedded_database_knows_bundled_roms() {
+        let db = RomDatabase::embedded();
+        let rom = [0x60, 0x3c, 0xf0, 0x18, 0x12, 0x04];
+
+        let info = db.lookup(&rom).unwrap();
+        assert_eq!(info.title(), "Buzz");
+        assert_eq!(info.platform().as_deref(), Some("modernChip8"));
+    }
+
+    #[test]
+    fn returns_error_for_invalid_json() {
+        assert!(matches!(
+            RomDatabase::from_json("{", PLATFORMS),
+            Err(Error::InvalidRomDatabase)
+        ));
+    }
+}
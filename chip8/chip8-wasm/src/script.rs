@@ -0,0 +1,96 @@
+use crate::{Error, Result};
+
+/// A key of the keypad pressed or released at the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: usize,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Parses a scripted key sequence, with one `<frame> <key> <down|up>` event
+/// per line, the key in hex. Empty lines and lines starting with `#` are
+/// skipped.
+///
+/// ```text
+/// # press 5 for half a second
+/// 30 5 down
+/// 60 5 up
+/// ```
+pub fn parse_key_script(script: &str) -> Result<Vec<KeyEvent>> {
+    let mut events = vec![];
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || Error::InvalidKeyScript(number + 1);
+        let mut fields = line.split_whitespace();
+        let frame = fields
+            .next()
+            .and_then(|frame| frame.parse().ok())
+            .ok_or_else(invalid)?;
+        let key = fields
+            .next()
+            .and_then(|key| u8::from_str_radix(key, 16).ok())
+            .filter(|key| *key < 16)
+            .ok_or_else(invalid)?;
+        let pressed = match fields.next() {
+            Some("down") => true,
+            Some("up") => false,
+            _ => return Err(invalid()),
+        };
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+
+        events.push(KeyEvent {
+            frame,
+            key,
+            pressed,
+        });
+    }
+
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_events_sorted_by_frame() {
+        let script = "# comment\n60 a up\n\n30 A down\n";
+
+        assert_eq!(
+            parse_key_script(script),
+            Ok(vec![
+                KeyEvent {
+                    frame: 30,
+                    key: 0xa,
+                    pressed: true
+                },
+                KeyEvent {
+                    frame: 60,
+                    key: 0xa,
+                    pressed: false
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn returns_error_with_line_number() {
+        assert_eq!(
+            parse_key_script("1 2 down\n3 10 up"),
+            Err(Error::InvalidKeyScript(2))
+        );
+        assert_eq!(
+            parse_key_script("x 2 down"),
+            Err(Error::InvalidKeyScript(1))
+        );
+    }
+}
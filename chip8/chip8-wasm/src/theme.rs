@@ -0,0 +1,535 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::Rgb;
+use crate::{Error, Result};
+
+pub const MIN_PALETTE_LEN: usize = 2;
+pub const MAX_PALETTE_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 0xff }
+    }
+
+    const fn from_hex(value: u32) -> Self {
+        Self::rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+    }
+
+    pub fn to_rgb(self) -> Rgb {
+        (self.r, self.g, self.b)
+    }
+
+    /// Parses a CSS colour: `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)`,
+    /// `rgba(r, g, b, a)` or a named colour.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim().to_ascii_lowercase();
+
+        if let Some(hex) = value.strip_prefix('#') {
+            return Self::parse_hex(hex);
+        }
+
+        if let Some(args) = value
+            .strip_prefix("rgba(")
+            .or_else(|| value.strip_prefix("rgb("))
+        {
+            return Self::parse_rgb_function(args);
+        }
+
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == value)
+            .map(|(_, hex)| Self::from_hex(*hex))
+            .ok_or(Error::InvalidTheme)
+    }
+
+    fn parse_hex(hex: &str) -> Result<Self> {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(Error::InvalidTheme)?;
+
+        match digits[..] {
+            [r, g, b] => Ok(Self::rgb(r * 0x11, g * 0x11, b * 0x11)),
+            [r1, r0, g1, g0, b1, b0] => Ok(Self::rgb(r1 << 4 | r0, g1 << 4 | g0, b1 << 4 | b0)),
+            [r1, r0, g1, g0, b1, b0, a1, a0] => Ok(Self {
+                r: r1 << 4 | r0,
+                g: g1 << 4 | g0,
+                b: b1 << 4 | b0,
+                a: a1 << 4 | a0,
+            }),
+            _ => Err(Error::InvalidTheme),
+        }
+    }
+
+    fn parse_rgb_function(args: &str) -> Result<Self> {
+        let args = args.strip_suffix(')').ok_or(Error::InvalidTheme)?;
+        let parts: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .collect();
+
+        let channel = |part: &str| -> Result<u8> {
+            let value = match part.strip_suffix('%') {
+                Some(percent) => percent.parse::<f32>().map(|p| p * 2.55),
+                None => part.parse::<f32>(),
+            }
+            .map_err(|_| Error::InvalidTheme)?;
+            Ok(value.round().clamp(0.0, 255.0) as u8)
+        };
+        let alpha = |part: &str| -> Result<u8> {
+            let value = match part.strip_suffix('%') {
+                Some(percent) => percent.parse::<f32>().map(|p| p / 100.0),
+                None => part.parse::<f32>(),
+            }
+            .map_err(|_| Error::InvalidTheme)?;
+            Ok((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+        };
+
+        match parts[..] {
+            [r, g, b] => Ok(Self::rgb(channel(r)?, channel(g)?, channel(b)?)),
+            [r, g, b, a] => Ok(Self {
+                r: channel(r)?,
+                g: channel(g)?,
+                b: channel(b)?,
+                a: alpha(a)?,
+            }),
+            _ => Err(Error::InvalidTheme),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 0xff {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// Background colours of the VP-590 colour board used by CHIP-8X, in the
+/// order 02A0 cycles through them.
+pub const VP590_BACKGROUNDS: [Rgb; 4] = [
+    (0x00, 0x00, 0x80),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x80, 0x00),
+    (0x80, 0x00, 0x00),
+];
+
+/// Foreground colours of the VP-590, by the colour numbers of BXYN.
+pub const VP590_FOREGROUNDS: [Rgb; 8] = [
+    (0x00, 0x00, 0x00),
+    (0xff, 0x00, 0x00),
+    (0x00, 0x00, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// Colours used to draw the display. The first palette entry is the colour
+/// of unlit pixels and the second one the colour of lit pixels; XO-CHIP
+/// bitplanes use the rest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub palette: Vec<Color>,
+    pub border: Color,
+    pub background: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_palette(
+            "Default",
+            &[Color::rgb(0x00, 0x00, 0x00), Color::rgb(0xff, 0xff, 0xff)],
+        )
+        .unwrap()
+    }
+}
+
+impl Theme {
+    /// Theme with the given palette, and the border and background in the
+    /// colour of unlit pixels.
+    pub fn from_palette(name: &str, palette: &[Color]) -> Result<Self> {
+        if !(MIN_PALETTE_LEN..=MAX_PALETTE_LEN).contains(&palette.len()) {
+            return Err(Error::InvalidTheme);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            palette: palette.to_vec(),
+            border: palette[0],
+            background: palette[0],
+        })
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        presets().into_iter().find(|theme| theme.name == name)
+    }
+
+    pub fn off_color(&self) -> Rgb {
+        self.palette[0].to_rgb()
+    }
+
+    pub fn on_color(&self) -> Rgb {
+        self.palette[1].to_rgb()
+    }
+
+    /// Colour of an XO-CHIP pixel lit on `planes` (bit 0 for the first
+    /// plane): the palette entry at that index, or the lit colour if the
+    /// palette is shorter.
+    pub fn plane_color(&self, planes: u8) -> Rgb {
+        match self.palette.get(planes as usize) {
+            Some(color) => color.to_rgb(),
+            None => self.on_color(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let theme: Self = serde_json::from_str(json).map_err(|_| Error::InvalidTheme)?;
+        if !(MIN_PALETTE_LEN..=MAX_PALETTE_LEN).contains(&theme.palette.len()) {
+            return Err(Error::InvalidTheme);
+        }
+
+        Ok(theme)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Imports a Lospec `.hex` palette: one `rrggbb` colour per line.
+    pub fn from_hex_palette(name: &str, hex: &str) -> Result<Self> {
+        let palette = hex
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| Color::parse(&format!("#{}", line.trim_start_matches('#'))))
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::from_palette(name, &palette)
+    }
+
+    pub fn to_hex_palette(&self) -> String {
+        self.palette
+            .iter()
+            .map(|color| format!("{:02x}{:02x}{:02x}\n", color.r, color.g, color.b))
+            .collect()
+    }
+
+    /// Imports a theme either as JSON or as a `.hex` palette.
+    pub fn import(name: &str, data: &str) -> Result<Self> {
+        if data.trim_start().starts_with('{') {
+            Self::from_json(data)
+        } else {
+            Self::from_hex_palette(name, data)
+        }
+    }
+}
+
+/// Built-in themes.
+pub fn presets() -> Vec<Theme> {
+    let preset = |name: &str, palette: &[u32], border: u32, background: u32| Theme {
+        name: name.to_string(),
+        palette: palette.iter().map(|hex| Color::from_hex(*hex)).collect(),
+        border: Color::from_hex(border),
+        background: Color::from_hex(background),
+    };
+
+    vec![
+        preset("Noire Truth", &[0x1e1c32, 0xc6baac], 0x1e1c32, 0x100f1c),
+        preset(
+            "1-bit Monitor Glow",
+            &[0x222323, 0xf0f6f0],
+            0x222323,
+            0x161717,
+        ),
+        preset("Paperback-2", &[0x382b26, 0xb8c2b9], 0x382b26, 0x241b18),
+        preset(
+            "Gato Roboto Goop",
+            &[0x210009, 0x00ffae],
+            0x210009,
+            0x140005,
+        ),
+        preset(
+            "Y's Postapocalyptic Sunset",
+            &[0x1d0f44, 0xf44e38],
+            0x1d0f44,
+            0x120a2b,
+        ),
+        preset(
+            "Octo Classic",
+            &[0x996600, 0xffcc00, 0xff6600, 0x662200],
+            0x000000,
+            0x000000,
+        ),
+        preset(
+            "Kirokaze Gameboy",
+            &[0x332c50, 0x46878f, 0x94e344, 0xe2f3e4],
+            0x332c50,
+            0x1f1b31,
+        ),
+    ]
+}
+
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Color::parse("#faBAda"), Ok(Color::rgb(0xfa, 0xba, 0xda)));
+        assert_eq!(Color::parse("#f0a"), Ok(Color::rgb(0xff, 0x00, 0xaa)));
+        assert_eq!(
+            Color::parse("#11223380"),
+            Ok(Color {
+                r: 0x11,
+                g: 0x22,
+                b: 0x33,
+                a: 0x80
+            })
+        );
+        assert_eq!(Color::parse("#12345"), Err(Error::InvalidTheme));
+        assert_eq!(Color::parse("#ggg"), Err(Error::InvalidTheme));
+    }
+
+    #[test]
+    fn parses_rgb_functions() {
+        assert_eq!(
+            Color::parse("rgb(255, 0, 10)"),
+            Ok(Color::rgb(0xff, 0x00, 0x0a))
+        );
+        assert_eq!(
+            Color::parse("rgb(100% 0% 50%)"),
+            Ok(Color::rgb(0xff, 0x00, 0x80))
+        );
+        assert_eq!(
+            Color::parse("rgba(1, 2, 3, 0.5)"),
+            Ok(Color {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 0x80
+            })
+        );
+        assert_eq!(Color::parse("rgb(1, 2)"), Err(Error::InvalidTheme));
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_eq!(
+            Color::parse("RebeccaPurple"),
+            Ok(Color::rgb(0x66, 0x33, 0x99))
+        );
+        assert_eq!(Color::parse("notacolor"), Err(Error::InvalidTheme));
+    }
+
+    #[test]
+    fn rejects_palettes_of_invalid_size() {
+        let color = Color::rgb(0, 0, 0);
+
+        assert!(Theme::from_palette("a", &[color]).is_err());
+        assert!(Theme::from_palette("a", &[color; 16]).is_ok());
+        assert!(Theme::from_palette("a", &[color; 17]).is_err());
+    }
+
+    #[test]
+    fn imports_and_exports_json() {
+        let theme = Theme::preset("Octo Classic").unwrap();
+
+        let json = theme.to_json();
+        assert!(json.contains("\"#ffcc00\""));
+        assert_eq!(Theme::import("", &json), Ok(theme));
+    }
+
+    #[test]
+    fn imports_and_exports_hex_palettes() {
+        let theme = Theme::import("Lospec", "1e1c32\nc6baac\n\n").unwrap();
+
+        assert_eq!(theme.name, "Lospec");
+        assert_eq!(theme.off_color(), (0x1e, 0x1c, 0x32));
+        assert_eq!(theme.on_color(), (0xc6, 0xba, 0xac));
+        assert_eq!(theme.to_hex_palette(), "1e1c32\nc6baac\n");
+    }
+}